@@ -0,0 +1,226 @@
+//! Programmatic construction of the plugin, for applications that configure OpenTelemetry in code.
+
+use crate::detector::CustomResourceDetectors;
+use crate::processor::{CustomLogProcessors, CustomSpanProcessors, LogFilter};
+use crate::{KeyValues, OpenTelemetryPlugin};
+use opentelemetry::KeyValue;
+use opentelemetry_sdk::logs::{LogProcessor, SdkLogRecord};
+use opentelemetry_sdk::resource::ResourceDetector;
+use opentelemetry_sdk::trace::{Sampler, SpanProcessor};
+use summer::app::AppBuilder;
+use summer::plugin::Plugin;
+
+/// The options set in code, they take precedence over the `[opentelemetry]` config
+/// and the `OTEL_*` environment variables.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct PluginOptions {
+    pub(crate) service_name: Option<String>,
+    pub(crate) exporter_endpoint: Option<String>,
+    pub(crate) resource_attributes: KeyValues,
+    pub(crate) resource_detectors: CustomResourceDetectors,
+    pub(crate) sampler: Option<Sampler>,
+    pub(crate) span_processors: CustomSpanProcessors,
+    pub(crate) log_processors: CustomLogProcessors,
+    pub(crate) log_filter: Option<LogFilter>,
+    pub(crate) metrics_only: bool,
+    #[cfg(any(feature = "grpc", feature = "http", feature = "http-json"))]
+    pub(crate) headers_provider: Option<crate::exporter::HeadersProvider>,
+}
+
+impl PluginOptions {
+    /// Whether no option is set in code.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.service_name.is_none()
+            && self.exporter_endpoint.is_none()
+            && self.resource_attributes.is_empty()
+            && self.resource_detectors.is_empty()
+            && self.sampler.is_none()
+            && self.span_processors.is_empty()
+            && self.log_processors.is_empty()
+            && self.log_filter.is_none()
+            && !self.metrics_only
+            && self.headers_provider_is_none()
+    }
+
+    #[cfg(any(feature = "grpc", feature = "http", feature = "http-json"))]
+    fn headers_provider_is_none(&self) -> bool {
+        self.headers_provider.is_none()
+    }
+
+    #[cfg(not(any(feature = "grpc", feature = "http", feature = "http-json")))]
+    fn headers_provider_is_none(&self) -> bool {
+        true
+    }
+}
+
+/// Builder of an [`OpenTelemetryPlugin`] customized in code, created with [`OpenTelemetryPlugin::builder`].
+///
+/// ```rust,ignore
+/// use summer_opentelemetry::{KeyValue, OpenTelemetryPlugin};
+/// use opentelemetry_sdk::trace::Sampler;
+///
+/// App::new()
+///     .add_plugin(
+///         OpenTelemetryPlugin::builder()
+///             .with_service_name("payments")
+///             .with_exporter_endpoint("http://collector:4317")
+///             .with_resource_attribute(KeyValue::new("team", "checkout"))
+///             .with_sampler(Sampler::TraceIdRatioBased(0.1))
+///             .build(),
+///     )
+///     .run()
+///     .await;
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct OpenTelemetryPluginBuilder {
+    options: PluginOptions,
+}
+
+impl OpenTelemetryPluginBuilder {
+    /// Sets the `service.name` resource attribute, overriding `service_name` of the config.
+    pub fn with_service_name<S: Into<String>>(mut self, service_name: S) -> Self {
+        self.options.service_name = Some(service_name.into());
+        self
+    }
+
+    /// Sets the base endpoint of the OTLP exporters of all signals, overriding the
+    /// `OTEL_EXPORTER_OTLP_*ENDPOINT` env vars. The HTTP exporters append the signal path,
+    /// e.g. `/v1/traces`.
+    pub fn with_exporter_endpoint<S: Into<String>>(mut self, endpoint: S) -> Self {
+        self.options.exporter_endpoint = Some(endpoint.into());
+        self
+    }
+
+    /// Adds a resource attribute, overriding the `resource_attributes` of the config with the same key.
+    pub fn with_resource_attribute(mut self, attribute: KeyValue) -> Self {
+        self.options.resource_attributes.push(attribute);
+        self
+    }
+
+    /// Adds a resource detector, e.g. reading the attributes of the service from an internal
+    /// service catalog, whose attributes are added to the resource of all signals.
+    ///
+    /// The detectors run concurrently with the detectors of the features when the plugin is
+    /// built, within `resource_detection_timeout_ms`. Their attributes override the detected
+    /// ones and are overridden by the configured `resource_attributes`.
+    pub fn with_resource_detector<D>(mut self, detector: D) -> Self
+    where
+        D: ResourceDetector + Send + Sync + 'static,
+    {
+        self.options.resource_detectors.push(detector);
+        self
+    }
+
+    /// Sets the sampler of the tracer provider, replacing the sampler built from `sampler_ratio`.
+    /// The spans of the `excluded_span_paths` and `excluded_span_names` are still dropped, and
+    /// the spans whose parent is sampled are always sampled.
+    pub fn with_sampler(mut self, sampler: Sampler) -> Self {
+        self.options.sampler = Some(sampler);
+        self
+    }
+
+    /// Adds a span processor to the tracer provider, before the processors exporting the spans,
+    /// e.g. to tag the spans with request-scoped data in `on_start`.
+    ///
+    /// The attributes set in `on_start` are exported, since `tracing` spans are started when
+    /// they close, with their fields. Each processor gets its own copy of the ended spans
+    /// though, so the changes made in `on_end` don't reach the exported spans, and a span
+    /// processor can't remove attributes, the `redact_attributes` config scrubs them instead.
+    pub fn with_span_processor<P: SpanProcessor + 'static>(self, processor: P) -> Self {
+        self.options.span_processors.push(processor);
+        self
+    }
+
+    /// Adds a log processor to the logger provider, before the processors exporting the log
+    /// records, e.g. to add attributes. The processors see the changes of the previous ones,
+    /// see [`with_log_filter`](Self::with_log_filter) to drop records.
+    pub fn with_log_processor<P: LogProcessor + 'static>(self, processor: P) -> Self {
+        self.options.log_processors.push(processor);
+        self
+    }
+
+    /// Sets a filter of the exported log records: the records for which it returns `false`
+    /// are dropped, after the [`with_log_processor`](Self::with_log_processor) processors.
+    ///
+    /// ```rust,ignore
+    /// let health_check = Regex::new("^GET /health")?;
+    /// OpenTelemetryPlugin::builder().with_log_filter(move |record| {
+    ///     !matches!(record.body(), Some(AnyValue::String(body)) if health_check.is_match(body.as_str()))
+    /// })
+    /// ```
+    pub fn with_log_filter<F>(mut self, filter: F) -> Self
+    where
+        F: Fn(&SdkLogRecord) -> bool + Send + Sync + 'static,
+    {
+        self.options.log_filter = Some(LogFilter::new(filter));
+        self
+    }
+
+    /// Only sets up the meter provider and the metrics layer, as if `enable_traces` and
+    /// `enable_logs` were `false`, without installing the propagators either, e.g. for the
+    /// services exporting their RED metrics only. See [`OpenTelemetryPlugin::metrics_only`].
+    pub fn metrics_only(mut self) -> Self {
+        self.options.metrics_only = true;
+        self
+    }
+
+    /// Sets a function supplying headers of each request of the HTTP exporters, or the gRPC
+    /// metadata of each call of the tonic exporters, e.g. a rotating API or OAuth token. It's
+    /// called before each export and its headers replace the configured headers with the same
+    /// name, so the token can be refreshed without restarting.
+    ///
+    /// ```rust,ignore
+    /// OpenTelemetryPlugin::builder().with_headers_provider(move || {
+    ///     HashMap::from([("authorization".to_string(), format!("Bearer {}", tokens.current()))])
+    /// })
+    /// ```
+    ///
+    /// The metadata names must be lowercase with the gRPC exporters, a header that isn't valid
+    /// metadata fails the export.
+    #[cfg(any(feature = "grpc", feature = "http", feature = "http-json"))]
+    pub fn with_headers_provider<F>(mut self, provider: F) -> Self
+    where
+        F: Fn() -> std::collections::HashMap<String, String> + Send + Sync + 'static,
+    {
+        self.options.headers_provider = Some(crate::exporter::HeadersProvider::new(provider));
+        self
+    }
+
+    /// Builds the plugin to add to the application.
+    pub fn build(self) -> ConfiguredOpenTelemetryPlugin {
+        ConfiguredOpenTelemetryPlugin {
+            options: self.options,
+        }
+    }
+}
+
+/// An [`OpenTelemetryPlugin`] with the options of an [`OpenTelemetryPluginBuilder`].
+///
+/// It is named like [`OpenTelemetryPlugin`], so it satisfies the plugin dependencies on it.
+#[derive(Debug, Clone)]
+pub struct ConfiguredOpenTelemetryPlugin {
+    options: PluginOptions,
+}
+
+impl ConfiguredOpenTelemetryPlugin {
+    /// Builds the providers and installs the layers like [`OpenTelemetryPlugin::try_build`].
+    pub fn try_build(&self, app: &mut AppBuilder) -> summer::error::Result<()> {
+        OpenTelemetryPlugin::build_with(app, &self.options)
+    }
+}
+
+impl Plugin for ConfiguredOpenTelemetryPlugin {
+    fn immediately_build(&self, app: &mut AppBuilder) {
+        if let Err(e) = self.try_build(app) {
+            panic!("opentelemetry plugin build failed: {e:?}");
+        }
+    }
+
+    fn name(&self) -> &str {
+        std::any::type_name::<OpenTelemetryPlugin>()
+    }
+
+    fn immediately(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,1541 @@
+use crate::exporter::{RetryPolicy, Signal};
+use opentelemetry::InstrumentationScope;
+use opentelemetry_sdk::trace::{Sampler, SpanLimits};
+use schemars::JsonSchema;
+use serde::{Deserialize, Deserializer};
+use std::collections::HashMap;
+use std::num::{NonZeroU32, NonZeroUsize};
+use std::time::Duration;
+use summer::config::Configurable;
+use tracing_subscriber::filter::LevelFilter;
+
+summer::submit_config_schema!("opentelemetry", OpenTelemetryConfig);
+
+/// summer-opentelemetry Config
+#[derive(Debug, Configurable, Clone, JsonSchema, Deserialize)]
+#[config_prefix = "opentelemetry"]
+pub struct OpenTelemetryConfig {
+    /// Whether to enable the plugin at runtime
+    ///
+    /// When disabled, or when `OTEL_SDK_DISABLED=true`, the plugin installs no providers, layers
+    /// or shutdown hook, so the application runs without any telemetry overhead.
+    #[serde(default)]
+    pub enable: bool,
+
+    /// Logical name of the service, reported as the `service.name` resource attribute.
+    ///
+    /// If not set, `OTEL_SERVICE_NAME` is used, then the `service.name` of the
+    /// `resource_attributes`, of `OTEL_RESOURCE_ATTRIBUTES` or added in code, and finally
+    /// `unknown_service`.
+    pub service_name: Option<String>,
+
+    /// Version of the service, reported as the `service.version` resource attribute.
+    pub service_version: Option<String>,
+
+    /// Name of the deployment environment, reported as the `deployment.environment.name`
+    /// resource attribute.
+    ///
+    /// If not set, the value of `deployment_environment_env_var` is used, and otherwise the
+    /// canonical name of the active [`Env`](summer::config::env::Env) of the application:
+    /// `development`, `test` or `production`.
+    pub deployment_environment: Option<String>,
+
+    /// Name of the environment variable holding the deployment environment, e.g. `APP_ENV`
+    /// set to `staging-eu` by the platform. Its value is used as is when it's set and not empty.
+    pub deployment_environment_env_var: Option<String>,
+
+    /// Name of the environment variable holding the git commit SHA of the build, reported as
+    /// the `vcs.ref.head.revision` resource attribute when it's set and not empty, so that the
+    /// telemetry can be correlated with the exact deploy. Default is `GIT_COMMIT`.
+    pub commit_sha_env_var: Option<String>,
+
+    /// Static attributes added to the resource of all signals, e.g. `{ team = "payments" }`.
+    ///
+    /// They override the detected attributes and the attributes added with
+    /// [`ResourceConfigurator`](crate::ResourceConfigurator), but are overridden by
+    /// `service_name`, `service_version`, the commit SHA and `deployment_environment`.
+    pub resource_attributes: Option<HashMap<String, String>>,
+
+    /// Attributes added to the resource of the traces only, e.g. `{ "trace.source" = "api" }`.
+    /// They override the attributes of the resource shared by all signals with the same key.
+    pub traces_resource_attributes: Option<HashMap<String, String>>,
+
+    /// Attributes added to the resource of the metrics only, like `traces_resource_attributes`.
+    pub metrics_resource_attributes: Option<HashMap<String, String>>,
+
+    /// Attributes added to the resource of the logs only, e.g. `{ "log.source" = "app" }`,
+    /// like `traces_resource_attributes`.
+    pub logs_resource_attributes: Option<HashMap<String, String>>,
+
+    /// Schema URL of the resource, i.e. the version of the semantic conventions of its
+    /// attributes, e.g. to match the version expected by the collector. Defaults to the version
+    /// of the semantic conventions this crate is built with, an empty URL omits it.
+    #[serde(default = "default_resource_schema_url")]
+    pub resource_schema_url: String,
+
+    /// Name of the instrumentation scope of the spans recorded from `tracing`, reported as
+    /// `otel.scope.name`. Defaults to `service_name`, then to the name of this crate.
+    pub instrumentation_scope_name: Option<String>,
+
+    /// Version of the instrumentation scope of the spans recorded from `tracing`.
+    /// Defaults to `service_version` when the scope name isn't set either.
+    pub instrumentation_scope_version: Option<String>,
+
+    /// Timeout in milliseconds of the resource detectors of the `more-resource`, `k8s`, `aws`
+    /// and `gcp` features, default is `3000`. The attributes of detectors that don't finish
+    /// in time are omitted.
+    #[serde(default = "default_resource_detection_timeout_ms")]
+    pub resource_detection_timeout_ms: u64,
+
+    /// Whether to detect the `host.*` attributes with the `more-resource` feature, default `true`.
+    #[serde(default = "default_true")]
+    pub detect_host: bool,
+
+    /// Whether to detect the `os.*` attributes with the `more-resource` feature, default `true`.
+    #[serde(default = "default_true")]
+    pub detect_os: bool,
+
+    /// Whether to detect the `process.*` attributes with the `more-resource` feature, e.g. the
+    /// command line with its arguments, default `true`.
+    #[serde(default = "default_true")]
+    pub detect_process: bool,
+
+    /// Whether to add the attributes of `OTEL_RESOURCE_ATTRIBUTES`, default `true`. Its
+    /// `service.name` is still used when neither `service_name` nor `OTEL_SERVICE_NAME` is set.
+    #[serde(default = "default_true")]
+    pub detect_env: bool,
+
+    /// Exporter of all signals: `otlp` or `stdout`, default is `otlp`.
+    /// Metrics can also be exported with `prometheus`.
+    ///
+    /// The `stdout` exporter prints the telemetry to the console without any network calls,
+    /// which is handy for local development without a collector. The `in_memory` exporter
+    /// captures it into the [`InMemoryTelemetry`](crate::testing::InMemoryTelemetry) component
+    /// for the tests to inspect.
+    #[serde(default)]
+    pub exporter: ExporterKind,
+
+    /// Exporter of the traces, overrides `exporter`.
+    pub traces_exporter: Option<ExporterKind>,
+
+    /// Exporter of the metrics, overrides `exporter`.
+    pub metrics_exporter: Option<ExporterKind>,
+
+    /// Exporter of the logs, overrides `exporter`. The log records can also be printed to the
+    /// console with `logfmt`, one line per record, without the `stdout` feature.
+    pub logs_exporter: Option<ExporterKind>,
+
+    /// Transport protocol of the OTLP exporters: `grpc`, `http/protobuf` or `http/json`.
+    ///
+    /// If not set, `OTEL_EXPORTER_OTLP_*_PROTOCOL` and `OTEL_EXPORTER_OTLP_PROTOCOL` are used,
+    /// then `http/protobuf` when the `http` feature is enabled and `grpc` otherwise.
+    pub protocol: Option<OtlpProtocol>,
+
+    /// Transport protocol of the trace exports, overrides `protocol`, e.g. `grpc` for the
+    /// traces while the logs are sent with `http/protobuf` to a collector only accepting them
+    /// over HTTP.
+    pub traces_protocol: Option<OtlpProtocol>,
+
+    /// Transport protocol of the metrics exports, overrides `protocol`.
+    pub metrics_protocol: Option<OtlpProtocol>,
+
+    /// Transport protocol of the log exports, overrides `protocol`.
+    pub logs_protocol: Option<OtlpProtocol>,
+
+    /// Base endpoint of the OTLP exporters of all signals, e.g. `http://collector:4317`.
+    /// The HTTP exporters append the signal path, e.g. `/v1/traces`.
+    ///
+    /// With the `grpc` protocol, a `unix://` endpoint, e.g. `unix:///var/run/otel.sock`,
+    /// connects to a collector listening on a Unix domain socket, on Unix only.
+    ///
+    /// `OTEL_EXPORTER_OTLP_ENDPOINT` and `OTEL_EXPORTER_OTLP_*_ENDPOINT` take precedence.
+    pub endpoint: Option<String>,
+
+    /// Full endpoint of the trace exporter, e.g. `https://gateway/otlp/traces`, used as is
+    /// without appending the signal path. Overrides `endpoint` for traces.
+    ///
+    /// `OTEL_EXPORTER_OTLP_ENDPOINT` and `OTEL_EXPORTER_OTLP_TRACES_ENDPOINT` take precedence.
+    pub traces_endpoint: Option<String>,
+
+    /// Full endpoint of the metrics exporter, overrides `endpoint` for metrics.
+    pub metrics_endpoint: Option<String>,
+
+    /// Full endpoint of the log exporter, overrides `endpoint` for logs.
+    pub logs_endpoint: Option<String>,
+
+    /// Full endpoints of the trace exporters, e.g. for redundant collectors, used as is like
+    /// `traces_endpoint`: the HTTP exporters don't append `/v1/traces`. Each endpoint gets its
+    /// own exporter, used according to `traces_endpoints_mode`. Overrides `traces_endpoint`
+    /// and `endpoint`.
+    ///
+    /// Ignored when the endpoint is set in code or by the trace endpoint env vars.
+    pub traces_endpoints: Option<Vec<String>>,
+
+    /// How the spans are sent to the `traces_endpoints`: `mirror` or `failover`, default `mirror`.
+    #[serde(default)]
+    pub traces_endpoints_mode: EndpointsMode,
+
+    /// Headers sent with each export of the OTLP exporters, e.g. `{ api-key = "secret" }`.
+    /// They are sent as metadata by the `grpc` exporters.
+    ///
+    /// The headers of `OTEL_EXPORTER_OTLP_HEADERS` and `OTEL_EXPORTER_OTLP_*_HEADERS` take
+    /// precedence over the headers with the same name.
+    pub headers: Option<HashMap<String, String>>,
+
+    /// `User-Agent` header of the requests of the HTTP exporters, e.g. `checkout/1.4.2`,
+    /// so that the backend can identify the application.
+    pub user_agent: Option<String>,
+
+    /// Compression of the OTLP exports of all signals: `none`, `gzip` or `zstd`,
+    /// which require the `gzip` and `zstd` features.
+    ///
+    /// `OTEL_EXPORTER_OTLP_COMPRESSION` and `OTEL_EXPORTER_OTLP_*_COMPRESSION` take precedence.
+    pub compression: Option<OtlpCompression>,
+
+    /// Compression of the trace exports, overrides `compression`.
+    pub traces_compression: Option<OtlpCompression>,
+
+    /// Compression of the metrics exports, overrides `compression`.
+    pub metrics_compression: Option<OtlpCompression>,
+
+    /// Compression of the log exports, overrides `compression`.
+    pub logs_compression: Option<OtlpCompression>,
+
+    /// Formats used to propagate the trace context, e.g. `["tracecontext", "baggage", "b3"]`.
+    ///
+    /// Incoming requests are extracted with all listed propagators and outgoing requests are
+    /// injected with all of them. If not set, `tracecontext` is used, or the format of the
+    /// `jaeger` / `zipkin` feature when enabled, composed with `baggage` unless it is disabled.
+    pub propagators: Option<Vec<PropagatorKind>>,
+
+    /// Whether to propagate [W3C Baggage](https://www.w3.org/TR/baggage/) along with the trace
+    /// context when `propagators` is not set, default is `true`.
+    #[serde(default = "default_true")]
+    pub baggage: bool,
+
+    /// Whether to install the configured propagators as the global text map propagator,
+    /// default is `true`.
+    ///
+    /// When disabled, e.g. because the application embedding summer already installs its own,
+    /// the propagation of the context by the HTTP and gRPC layers and `inject_context` relies on
+    /// the global propagator set elsewhere. The `propagators` are still validated.
+    #[serde(default = "default_true")]
+    pub set_global_propagator: bool,
+
+    /// Path of the PEM encoded CA certificate used to verify the collector of the gRPC exporters,
+    /// requires the `tls` feature. With the `tls-rustls` feature the system roots are trusted
+    /// when it isn't set. The TLS settings are rejected with the HTTP protocols.
+    pub tls_ca_cert_path: Option<String>,
+
+    /// Path of the PEM encoded client certificate of the gRPC exporters for mutual TLS,
+    /// must be set together with `tls_client_key_path`, and `tls_ca_cert_path` without the
+    /// `tls-rustls` feature.
+    pub tls_client_cert_path: Option<String>,
+
+    /// Path of the PEM encoded client private key of the gRPC exporters for mutual TLS,
+    /// must be set together with `tls_client_cert_path`.
+    pub tls_client_key_path: Option<String>,
+
+    /// Interval in milliseconds of the HTTP/2 keepalive pings of the gRPC exporters, so that
+    /// the load balancers and NATs don't drop their idle connection to the collector, no pings
+    /// are sent by default.
+    pub grpc_keepalive_interval_ms: Option<u64>,
+
+    /// Time in milliseconds the gRPC exporters wait for the acknowledgement of a keepalive ping
+    /// before closing the connection, the tonic default is `20000`. Requires
+    /// `grpc_keepalive_interval_ms`.
+    pub grpc_keepalive_timeout_ms: Option<u64>,
+
+    /// Whether the gRPC exporters send the keepalive pings while no export is in flight,
+    /// default is `false`. Requires `grpc_keepalive_interval_ms`.
+    #[serde(default)]
+    pub grpc_keepalive_while_idle: bool,
+
+    /// The OTLP exporter of the traces, e.g. to send them to another vendor than the metrics:
+    /// its settings override the ones above for traces.
+    pub traces: Option<SignalExportConfig>,
+
+    /// The OTLP exporter of the metrics, overriding the settings above for metrics.
+    pub metrics: Option<SignalExportConfig>,
+
+    /// The OTLP exporter of the logs, overriding the settings above for logs.
+    pub logs: Option<SignalExportConfig>,
+
+    /// Whether to export traces, default is `true`.
+    #[serde(default = "default_true")]
+    pub enable_traces: bool,
+
+    /// Whether to export metrics, default is `true`.
+    #[serde(default = "default_true")]
+    pub enable_metrics: bool,
+
+    /// Whether to export logs, default is `true`.
+    #[serde(default = "default_true")]
+    pub enable_logs: bool,
+
+    /// Minimum level of the log events exported, e.g. `info`, all events are exported by default.
+    /// It's independent of the level of the `[logger]` config.
+    pub logs_level: Option<LogsLevel>,
+
+    /// Whether to add the `service.name` and `service.version` of the resource as attributes of
+    /// each exported log record, for the log backends filtering on the record attributes only,
+    /// default is `false`.
+    #[serde(default)]
+    pub logs_include_service_attributes: bool,
+
+    /// Severity numbers and texts of the exported log records by `tracing` level, overriding
+    /// the ones of the logs data model, e.g. `{ warn = { number = 14, text = "WARNING" } }`.
+    pub logs_severity: Option<LogsSeverityMapping>,
+
+    /// Whether to print the name, duration and fields of each span to the console in a human
+    /// readable format, default is `false`. Meant for local development, independent of the
+    /// exporters and of `enable_traces`.
+    #[serde(default)]
+    pub dev_console: bool,
+
+    /// Minimum level of the internal events of the OpenTelemetry crates kept by the subscriber,
+    /// e.g. `warn` to drop their debug and info events but keep the export errors. They are
+    /// `tracing` events with the crate as target, e.g. `opentelemetry_sdk`, all of them are kept
+    /// by default.
+    pub internal_logs_level: Option<LogsLevel>,
+
+    /// Maximum number of the internal events of each kind kept per minute, e.g. the export
+    /// error of every batch while the collector is down, the extra ones are dropped. They are
+    /// not limited by default.
+    pub internal_logs_rate_limit: Option<NonZeroU32>,
+
+    /// Ratio of traces to sample, between `0.0` and `1.0`.
+    ///
+    /// Root spans are sampled by trace id with this ratio, child spans follow the
+    /// sampling decision of their parent. If not set, all traces are sampled.
+    ///
+    /// Only the spans are sampled: the metrics of the HTTP middleware and the
+    /// `monotonic_counter.*` fields of the events are recorded for all requests.
+    ///
+    /// Ratios out of range are clamped with a warning on stderr when the config is loaded,
+    /// `nan` fails the config load.
+    ///
+    /// `OTEL_TRACES_SAMPLER` and `OTEL_TRACES_SAMPLER_ARG` take precedence, e.g.
+    /// `parentbased_traceidratio` and `0.1`.
+    #[serde(default, deserialize_with = "deserialize_optional_ratio")]
+    pub sampler_ratio: Option<f64>,
+
+    /// Sampling rules of the requests by path prefix, e.g. to sample all `/checkout` requests
+    /// while downsampling the rest with `sampler_ratio`. The first matching rule applies.
+    #[serde(default)]
+    pub sampling_rules: Vec<SamplingRule>,
+
+    /// Sampling strategies of the root spans polled from a Jaeger agent or collector with the
+    /// Jaeger remote sampling protocol, so that the sampling rates are changed without
+    /// redeploying. Requires the `jaeger-remote-sampler` feature.
+    ///
+    /// Until the first strategy is fetched, the sampler of `OTEL_TRACES_SAMPLER` or
+    /// `sampler_ratio` is used. The `sampling_rules` still apply first.
+    pub jaeger_remote_sampler: Option<JaegerRemoteSamplerConfig>,
+
+    /// Whether to export the spans that end with an error status even when their trace isn't
+    /// sampled, default is `false`.
+    ///
+    /// The sampling decision is made when a span starts, before its status is known, so the
+    /// spans that aren't sampled are recorded anyway and only the ones that end with an error
+    /// are exported. The spans of the trace that ended without an error are still dropped, and
+    /// recording all spans costs as much as sampling them, up to their export. The processors
+    /// added with the builder see the unsampled spans too.
+    #[serde(default)]
+    pub keep_error_spans: bool,
+
+    /// The HTTP response status codes marking the spans as errors, from their
+    /// `http.response.status_code` attribute, e.g. for the spans of other instrumentations or
+    /// to mark the server spans of the `4xx` responses, unset by default.
+    ///
+    /// The spans whose status is already set are left as is, and the `HttpLayer` marks the
+    /// server spans of the `5xx` responses and the client spans of the `4xx` and `5xx`
+    /// responses as errors anyway.
+    #[serde(default)]
+    pub http_error_status: Option<HttpErrorStatus>,
+
+    /// Maximum number of attributes per span, the SDK default is `128`.
+    /// Attributes added beyond the limit are dropped.
+    pub max_attributes_per_span: Option<u32>,
+
+    /// Maximum number of events per span, the SDK default is `128`.
+    /// Events added beyond the limit are dropped.
+    pub max_events_per_span: Option<u32>,
+
+    /// Maximum number of links per span, the SDK default is `128`.
+    /// Links added beyond the limit are dropped.
+    pub max_links_per_span: Option<u32>,
+
+    /// Maximum length in characters of the string attribute values of the spans, their events
+    /// and links. Longer values are truncated, they are not limited by default.
+    pub max_attribute_value_length: Option<usize>,
+
+    /// Path prefixes of the requests whose spans are not exported, e.g. `["/health", "/metrics"]`.
+    ///
+    /// The spans are matched by their `url.path` (or `http.target`) attribute and dropped by the
+    /// sampler, so they are still recorded by `tracing` locally. Child spans are dropped as well.
+    pub excluded_span_paths: Option<Vec<String>>,
+
+    /// Names of the spans that are not exported, e.g. `["pool.acquire"]` for the housekeeping
+    /// spans of a library, where `*` matches any sequence of characters and `?` a single one.
+    ///
+    /// The spans are dropped by the sampler like the `excluded_span_paths`, so their child
+    /// spans are dropped as well.
+    #[serde(default)]
+    pub excluded_span_names: Vec<String>,
+
+    /// Route templates the names of the exported spans are normalized to, e.g. `["/users/{id}"]`
+    /// renames `GET /users/12345` to `GET /users/{id}`, so that the span names of the raw paths
+    /// don't explode the cardinality in the backend.
+    ///
+    /// The path in a span name matches a template when it has as many segments and the
+    /// segments other than the `{param}` ones are equal. The first matching template is used.
+    pub span_name_templates: Option<Vec<String>>,
+
+    /// Keys of the span attributes whose values are replaced with `"[REDACTED]"` before the
+    /// spans are exported, e.g. `["user.email", "http.request.header.*"]`.
+    ///
+    /// A key ending with `*` matches the attributes starting with the rest of it. The attributes
+    /// of the span events and links are redacted as well.
+    pub redact_attributes: Option<Vec<String>>,
+
+    /// New keys of the span attributes renamed before the spans are exported, by their key,
+    /// e.g. `{ "http.request.method" = "http.method" }` for the dashboards of a backend
+    /// expecting an older version of the semantic conventions.
+    ///
+    /// The attributes of the span events and links are renamed as well. An attribute whose
+    /// new key is already set is dropped rather than duplicated, and the `redact_attributes`
+    /// are matched by their key before the renaming.
+    #[serde(default)]
+    pub rename_attributes: HashMap<String, String>,
+
+    /// Keys of the baggage entries copied as attributes of the spans started in their context,
+    /// e.g. `["tenant.id"]` to search the spans of a tenant propagated by the callers.
+    ///
+    /// The entries are read from the baggage of the parent context, e.g. extracted from the
+    /// `baggage` header of the request, the child spans of the request span get them as well.
+    #[serde(default)]
+    pub baggage_to_span_attributes: Vec<String>,
+
+    /// Aggregation temporality of the exported metrics: `cumulative`, `delta` or `lowmemory`,
+    /// default is `cumulative`.
+    ///
+    /// `delta` exports counters, histograms and their observable variants as deltas while
+    /// up-down counters stay cumulative as required by the specification. The observable
+    /// counters observe totals, e.g. `process.cpu.time`, and each export reports the increase
+    /// since the previous one. Backends that only accept one temporality reject the other one,
+    /// which shows up as an export error of the metrics exporter. The `prometheus` exporter
+    /// only supports `cumulative`.
+    #[serde(default)]
+    pub metrics_temporality: MetricsTemporality,
+
+    /// Interval in milliseconds at which the metrics are exported by the push exporters.
+    /// The SDK default is `60000`, or `OTEL_METRIC_EXPORT_INTERVAL`.
+    pub metrics_export_interval_ms: Option<u64>,
+
+    /// Attributes added to the metrics recorded from the `tracing` events by the
+    /// `MetricsLayer`, e.g. `{ "service.instance.id" = "pod-1" }`, for the backends that don't
+    /// show the resource attributes on the metrics. The fields of an event take precedence
+    /// over the attributes with the same key.
+    #[serde(default)]
+    pub metrics_layer_attributes: HashMap<String, String>,
+
+    /// Request, error and duration metrics derived from the ended spans, like the span metrics
+    /// connector of the collector, recorded with the meter provider of the plugin, e.g. the
+    /// latency histograms by route without running the connector. Requires the metrics to be
+    /// enabled.
+    ///
+    /// The metrics are derived from the exported spans, so with sampling they only count the
+    /// sampled spans, and the unsampled errors too with `keep_error_spans`. They see the spans
+    /// as exported, e.g. renamed to the `span_name_templates`, marked as errors by
+    /// `http_error_status` and with the `redact_attributes` redacted.
+    pub span_metrics: Option<SpanMetricsConfig>,
+
+    /// Whether to record the `process.uptime`, `process.cpu.time` and `process.memory.usage`
+    /// metrics, default is `false`. The CPU time and memory usage are only recorded on Linux.
+    #[serde(default)]
+    pub process_metrics: bool,
+
+    /// Interval in milliseconds at which the process metrics are sampled, default is `10000`.
+    #[serde(default = "default_process_metrics_interval_ms")]
+    pub process_metrics_interval_ms: u64,
+
+    /// Whether to report the `otel.batch.dropped_spans` counter of the spans dropped because the
+    /// queue of the batch span processor was full, and the `otel.batch.queue_size` gauge of the
+    /// spans waiting in the queue or being exported, default is `false`.
+    ///
+    /// The spans being exported count against `max_queue_size` too, so that the drops are counted.
+    #[serde(default)]
+    pub batch_metrics: bool,
+
+    /// Views customizing the metrics streams of the matching instruments, e.g. the bucket
+    /// boundaries of a histogram. The first view matching an instrument is applied.
+    #[serde(default)]
+    pub metrics_views: Vec<MetricsView>,
+
+    /// Maximum number of attribute sets recorded per instrument and collection, the
+    /// measurements of further attribute sets are aggregated into a single data point with the
+    /// `otel.metric.overflow = true` attribute. The SDK default is `2000`.
+    ///
+    /// It caps the memory of a metric recorded with a runaway attribute, e.g. a raw user id.
+    pub metrics_cardinality_limit: Option<usize>,
+
+    /// Prefix of the names of all the exported metrics, e.g. `myapp_`, to avoid collisions in a
+    /// metrics store shared with other services. It must start with a letter.
+    ///
+    /// The Prometheus exporter converts the `.` of the prefixed names to `_`, e.g.
+    /// `myapp_http_server_request_duration_seconds`.
+    pub metrics_namespace: Option<String>,
+
+    /// Processor of the spans exported with OTLP: `batch` or `simple`, default `batch`.
+    ///
+    /// The `simple` processor exports each span synchronously when it ends, so short-lived
+    /// programs and tests don't lose the spans recorded before exiting without a flush, at the
+    /// cost of the throughput. With the `grpc` exporters it requires a multi-threaded Tokio
+    /// runtime, and the `batch_metrics` aren't recorded.
+    #[serde(default)]
+    pub span_processor: SpanProcessorKind,
+
+    /// Maximum number of spans and log records buffered by the batch processors,
+    /// further records are dropped when the queue is full.
+    ///
+    /// The SDK default is `2048`, or `OTEL_BSP_MAX_QUEUE_SIZE` / `OTEL_BLRP_MAX_QUEUE_SIZE`.
+    pub max_queue_size: Option<usize>,
+
+    /// Maximum number of spans and log records exported in a single batch.
+    ///
+    /// The SDK default is `512`,
+    /// or `OTEL_BSP_MAX_EXPORT_BATCH_SIZE` / `OTEL_BLRP_MAX_EXPORT_BATCH_SIZE`.
+    pub max_export_batch_size: Option<usize>,
+
+    /// Delay in milliseconds between two consecutive exports of the batch processors.
+    ///
+    /// The SDK default is `5000` for traces and `1000` for logs,
+    /// or `OTEL_BSP_SCHEDULE_DELAY` / `OTEL_BLRP_SCHEDULE_DELAY`.
+    pub scheduled_delay_ms: Option<u64>,
+
+    /// Timeout in milliseconds of each export of the OTLP exporters of all signals, including
+    /// the exports of the batch processors, which leave the deadline to the exporters.
+    ///
+    /// `OTEL_EXPORTER_OTLP_TIMEOUT` and `OTEL_EXPORTER_OTLP_*_TIMEOUT` take precedence.
+    /// The SDK default is `10000`, `max_export_timeout_ms` is accepted as an alias.
+    #[serde(alias = "max_export_timeout_ms")]
+    pub export_timeout_ms: Option<u64>,
+
+    /// Maximum size in bytes of each export of spans or logs, the larger batches are split into
+    /// several exports, e.g. `4000000` for the 4 MiB limit of the gRPC messages received by the
+    /// collector. The sizes are estimated slightly above the encoded size of the records, a
+    /// record larger than the limit is exported alone. Not set by default.
+    pub max_export_batch_bytes: Option<NonZeroUsize>,
+
+    /// Whether the plugin build fails when the OTLP exporter of a signal can't be built, e.g.
+    /// because its HTTP client or TLS transport can't be created, default is `false`: the error
+    /// is printed to stderr and the app starts without exporting the signal. Invalid configs
+    /// always fail the build.
+    ///
+    /// The exporters don't connect to the collector at startup, so an unreachable collector never
+    /// fails the build: the exports fail and the telemetry is dropped until it's reachable again,
+    /// including the telemetry recorded early at startup.
+    #[serde(default)]
+    pub fail_on_export_error: bool,
+
+    /// Whether the OTLP exporters are built on their first export rather than when the plugin
+    /// is built, default is `false`, e.g. to shorten the cold start of serverless functions:
+    /// their TLS settings are read and their HTTP clients are started when the first batch of
+    /// spans or logs, or the first metrics, are exported.
+    ///
+    /// The errors of the exporter settings, e.g. an unreadable certificate, are then logged as
+    /// a warning on the first export instead of failing the build, and the signal isn't
+    /// exported. The other settings are still validated when the plugin is built.
+    #[serde(default)]
+    pub lazy_init: bool,
+
+    /// Timeout in milliseconds of the shutdown of all providers when the application stops,
+    /// default is `5000`. The telemetry that isn't exported in time is dropped and the shutdown
+    /// hook returns the timeout error.
+    #[serde(default = "default_shutdown_timeout_ms")]
+    pub shutdown_timeout_ms: u64,
+
+    /// Number of times a failed export of the OTLP exporters is retried, default is `0`.
+    ///
+    /// Retries mitigate data loss while the collector restarts, e.g. during a rolling upgrade.
+    /// Each retry waits for a backoff starting at `initial_backoff_ms` that doubles after each
+    /// retry, on the background thread of the batch processor or the periodic reader. The
+    /// spans aren't retried with the `simple` span processor, which exports them on the thread
+    /// ending the span.
+    ///
+    /// Only transient failures are retried: timeouts, connection failures, the retryable gRPC
+    /// statuses such as `UNAVAILABLE` and the HTTP statuses `429`, `502`, `503` and `504`.
+    /// The exports aren't retried anymore once the application shuts down.
+    #[serde(default)]
+    pub max_retries: u32,
+
+    /// Backoff in milliseconds before the first retry of a failed export, default is `500`.
+    #[serde(default = "default_initial_backoff_ms")]
+    pub initial_backoff_ms: u64,
+
+    /// Whether to print the health of the OTLP exports of each signal to stdout every
+    /// `exporter_diagnostics_interval_ms`, default is `false`: the number of successful and
+    /// failed exports, the time since the last successful export and the last error.
+    ///
+    /// The summary isn't exported with the logs, so it's printed when the exports fail too.
+    #[serde(default)]
+    pub exporter_diagnostics: bool,
+
+    /// Interval in milliseconds at which the exporter diagnostics are printed, default is `60000`.
+    #[serde(default = "default_exporter_diagnostics_interval_ms")]
+    pub exporter_diagnostics_interval_ms: u64,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_resource_detection_timeout_ms() -> u64 {
+    3000
+}
+
+fn default_resource_schema_url() -> String {
+    opentelemetry_semantic_conventions::SCHEMA_URL.to_string()
+}
+
+fn default_process_metrics_interval_ms() -> u64 {
+    10000
+}
+
+fn default_exporter_diagnostics_interval_ms() -> u64 {
+    60000
+}
+
+fn default_shutdown_timeout_ms() -> u64 {
+    5000
+}
+
+fn default_initial_backoff_ms() -> u64 {
+    500
+}
+
+/// Where the telemetry of a signal is exported to.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, JsonSchema, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExporterKind {
+    /// Export to an OTLP endpoint with the configured `protocol`.
+    #[default]
+    Otlp,
+    /// Print to the console, requires the `stdout` feature.
+    Stdout,
+    /// Expose the metrics for Prometheus to scrape through the
+    /// [`PrometheusRegistry`](crate::metrics::PrometheusRegistry) component,
+    /// only supported by metrics and requires the `prometheus` feature.
+    Prometheus,
+    /// Capture the telemetry into the
+    /// [`InMemoryTelemetry`](crate::testing::InMemoryTelemetry) component,
+    /// requires the `testing` feature.
+    #[serde(rename = "in_memory")]
+    InMemory,
+    /// Print the log records to the console in the logfmt format, with their severity, trace
+    /// context and attributes, only supported by logs.
+    Logfmt,
+}
+
+impl std::fmt::Display for ExporterKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Otlp => f.write_str("otlp"),
+            Self::Stdout => f.write_str("stdout"),
+            Self::Prometheus => f.write_str("prometheus"),
+            Self::InMemory => f.write_str("in_memory"),
+            Self::Logfmt => f.write_str("logfmt"),
+        }
+    }
+}
+
+/// Format used to propagate the trace context across service boundaries.
+///
+/// refs: <https://opentelemetry.io/docs/languages/sdk-configuration/general/#otel_propagators>
+#[derive(Debug, Clone, Copy, PartialEq, Eq, JsonSchema, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PropagatorKind {
+    /// [W3C Trace Context](https://www.w3.org/TR/trace-context/)
+    TraceContext,
+    /// [W3C Baggage](https://www.w3.org/TR/baggage/)
+    Baggage,
+    /// [B3 single header](https://github.com/openzipkin/b3-propagation#single-header),
+    /// requires the `b3` or `zipkin` feature.
+    B3,
+    /// [B3 multiple headers](https://github.com/openzipkin/b3-propagation#multiple-headers),
+    /// requires the `b3` or `zipkin` feature.
+    B3Multi,
+    /// [Jaeger](https://www.jaegertracing.io/docs/1.18/client-libraries/#propagation-format),
+    /// requires the `jaeger` feature.
+    Jaeger,
+    /// [Datadog](https://docs.datadoghq.com/tracing/trace_collection/trace_context_propagation/)
+    /// `x-datadog-*` headers, requires the `datadog` feature.
+    Datadog,
+}
+
+impl std::fmt::Display for PropagatorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TraceContext => f.write_str("tracecontext"),
+            Self::Baggage => f.write_str("baggage"),
+            Self::B3 => f.write_str("b3"),
+            Self::B3Multi => f.write_str("b3multi"),
+            Self::Jaeger => f.write_str("jaeger"),
+            Self::Datadog => f.write_str("datadog"),
+        }
+    }
+}
+
+/// A view customizing the metrics stream of the instruments matching `instrument_name`.
+///
+/// refs: <https://opentelemetry.io/docs/specs/otel/metrics/sdk/#view>
+#[derive(Debug, Clone, JsonSchema, Deserialize)]
+pub struct MetricsView {
+    /// Name of the instruments to match, `*` matches any sequence of characters
+    /// and `?` matches a single character.
+    pub instrument_name: String,
+
+    /// Explicit bucket boundaries of the matching histograms, must be strictly increasing.
+    pub bucket_boundaries: Option<Vec<f64>>,
+
+    /// Maximum number of attribute sets of the matching instruments, overriding
+    /// `metrics_cardinality_limit`.
+    pub cardinality_limit: Option<usize>,
+
+    /// Keys of the attributes kept on the measurements of the matching instruments, e.g.
+    /// `["http.request.method", "http.route", "http.response.status_code"]`, the other
+    /// attributes are dropped before aggregation. All attributes are kept if not set, none if
+    /// it's empty.
+    pub attribute_keys: Option<Vec<String>>,
+}
+
+/// The OTLP exporter settings of a signal, the `[opentelemetry.traces]`,
+/// `[opentelemetry.metrics]` and `[opentelemetry.logs]` sections. The unset settings are
+/// inherited from the `[opentelemetry]` section, and the env vars still take precedence.
+#[derive(Debug, Default, Clone, JsonSchema, Deserialize)]
+pub struct SignalExportConfig {
+    /// Full endpoint of the exporter, used as is like `traces_endpoint`.
+    pub endpoint: Option<String>,
+
+    /// Headers of each export, merged with the `headers` of the common section by name.
+    pub headers: Option<HashMap<String, String>>,
+
+    /// Compression of the exports: `none`, `gzip` or `zstd`.
+    pub compression: Option<OtlpCompression>,
+
+    /// Transport protocol of the exports: `grpc`, `http/protobuf` or `http/json`.
+    pub protocol: Option<OtlpProtocol>,
+
+    /// Timeout in milliseconds of each export.
+    pub export_timeout_ms: Option<u64>,
+
+    /// Path of the PEM encoded CA certificate used to verify the collector.
+    pub tls_ca_cert_path: Option<String>,
+
+    /// Path of the PEM encoded client certificate for mutual TLS.
+    pub tls_client_cert_path: Option<String>,
+
+    /// Path of the PEM encoded client private key for mutual TLS.
+    pub tls_client_key_path: Option<String>,
+}
+
+/// Sampling ratio of the requests whose path starts with a prefix.
+#[derive(Debug, Clone, JsonSchema, Deserialize)]
+pub struct SamplingRule {
+    /// Prefix of the request paths, matched against `url.path` or `http.target`.
+    pub path_prefix: String,
+
+    /// Ratio of the matching traces to sample, between `0.0` and `1.0`, validated like
+    /// `sampler_ratio`.
+    #[serde(deserialize_with = "deserialize_ratio")]
+    pub ratio: f64,
+}
+
+/// Dimensions of the metrics derived from the spans.
+#[derive(Debug, Clone, JsonSchema, Deserialize)]
+pub struct SpanMetricsConfig {
+    /// The attributes of the `traces.span.metrics.calls` and `traces.span.metrics.duration`
+    /// metrics: `span.name`, `span.kind`, `status.code` and span attribute keys, e.g.
+    /// `http.route`, default is `["span.name", "span.kind", "status.code"]`.
+    ///
+    /// The span attributes are omitted from the metrics of the spans that don't have them.
+    #[serde(default = "default_span_metrics_dimensions")]
+    pub dimensions: Vec<String>,
+}
+
+fn default_span_metrics_dimensions() -> Vec<String> {
+    ["span.name", "span.kind", "status.code"]
+        .map(String::from)
+        .to_vec()
+}
+
+/// Endpoint and polling of the Jaeger remote sampler.
+#[derive(Debug, Clone, JsonSchema, Deserialize)]
+pub struct JaegerRemoteSamplerConfig {
+    /// Sampling endpoint of the Jaeger agent or collector, default is
+    /// `http://localhost:5778/sampling`.
+    #[serde(default = "default_jaeger_remote_sampler_endpoint")]
+    pub endpoint: String,
+
+    /// Service whose strategy is fetched, the `service.name` of the resource by default.
+    pub service_name: Option<String>,
+
+    /// Interval at which the strategy is fetched, default is `60000`.
+    #[serde(default = "default_jaeger_remote_sampler_polling_interval_ms")]
+    pub polling_interval_ms: u64,
+}
+
+fn default_jaeger_remote_sampler_endpoint() -> String {
+    "http://localhost:5778/sampling".to_string()
+}
+
+fn default_jaeger_remote_sampler_polling_interval_ms() -> u64 {
+    60_000
+}
+
+/// Aggregation temporality preference of the metrics exporter.
+///
+/// refs: <https://opentelemetry.io/docs/specs/otel/metrics/sdk_exporters/otlp/#additional-environment-variable-configuration>
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, JsonSchema, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MetricsTemporality {
+    /// Measurements are accumulated since the start of the process.
+    #[default]
+    Cumulative,
+    /// Measurements are reset after each export, except for up-down counters.
+    Delta,
+    /// Delta for synchronous counters and histograms, cumulative otherwise.
+    LowMemory,
+}
+
+impl From<MetricsTemporality> for opentelemetry_sdk::metrics::Temporality {
+    fn from(temporality: MetricsTemporality) -> Self {
+        match temporality {
+            MetricsTemporality::Cumulative => Self::Cumulative,
+            MetricsTemporality::Delta => Self::Delta,
+            MetricsTemporality::LowMemory => Self::LowMemory,
+        }
+    }
+}
+
+/// Transport protocol used by the OTLP exporters.
+///
+/// refs: <https://opentelemetry.io/docs/specs/otlp/#otlpgrpc>
+#[derive(Debug, Clone, Copy, PartialEq, Eq, JsonSchema, Deserialize)]
+pub enum OtlpProtocol {
+    /// OTLP over gRPC, requires the `grpc` feature.
+    #[serde(rename = "grpc")]
+    Grpc,
+    /// OTLP over HTTP with binary protobuf payloads, requires the `http` feature.
+    #[serde(rename = "http/protobuf")]
+    HttpProtobuf,
+    /// OTLP over HTTP with JSON payloads, requires the `http-json` feature.
+    #[serde(rename = "http/json")]
+    HttpJson,
+}
+
+impl OtlpProtocol {
+    fn from_env(var: &str) -> Option<Self> {
+        match std::env::var(var).ok()?.as_str() {
+            "grpc" => Some(Self::Grpc),
+            "http/protobuf" => Some(Self::HttpProtobuf),
+            "http/json" => Some(Self::HttpJson),
+            other => {
+                // the config is loaded before the log subscriber is installed
+                eprintln!("unsupported OTLP protocol of {var}: {other}");
+                None
+            }
+        }
+    }
+}
+
+impl Default for OtlpProtocol {
+    fn default() -> Self {
+        if cfg!(feature = "http") {
+            Self::HttpProtobuf
+        } else if cfg!(feature = "grpc") {
+            Self::Grpc
+        } else {
+            Self::HttpJson
+        }
+    }
+}
+
+impl std::fmt::Display for OtlpProtocol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Grpc => f.write_str("grpc"),
+            Self::HttpProtobuf => f.write_str("http/protobuf"),
+            Self::HttpJson => f.write_str("http/json"),
+        }
+    }
+}
+
+/// Clamps a sampling ratio to the `0.0..=1.0` range, rejecting `NaN`.
+fn clamp_ratio(ratio: f64) -> Result<f64, String> {
+    if ratio.is_nan() {
+        return Err("sampling ratio must be a number between 0.0 and 1.0, got NaN".into());
+    }
+    let clamped = ratio.clamp(0.0, 1.0);
+    if clamped != ratio {
+        // the config is loaded before the log subscriber is installed
+        eprintln!("sampling ratio {ratio} is out of range, clamped to {clamped}");
+    }
+    Ok(clamped)
+}
+
+fn deserialize_ratio<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+    clamp_ratio(f64::deserialize(deserializer)?).map_err(serde::de::Error::custom)
+}
+
+fn deserialize_optional_ratio<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<f64>, D::Error> {
+    Option::<f64>::deserialize(deserializer)?
+        .map(clamp_ratio)
+        .transpose()
+        .map_err(serde::de::Error::custom)
+}
+
+fn sdk_disabled(value: Option<&str>) -> bool {
+    value.is_some_and(|value| value.trim().eq_ignore_ascii_case("true"))
+}
+
+/// The root sampler named by `OTEL_TRACES_SAMPLER`, the ratio of `traceidratio` is `arg`,
+/// `1.0` if it's not set or invalid. `None` if the sampler isn't set or isn't supported.
+///
+/// The `parentbased_` prefix makes no difference, the plugin always follows the sampling
+/// decision of the parent.
+fn traces_sampler(sampler: Option<&str>, arg: Option<&str>) -> Option<Sampler> {
+    let sampler = sampler
+        .map(str::trim)
+        .filter(|sampler| !sampler.is_empty())?;
+    let name = sampler.strip_prefix("parentbased_").unwrap_or(sampler);
+    let root = match name {
+        "always_on" => Sampler::AlwaysOn,
+        "always_off" => Sampler::AlwaysOff,
+        "traceidratio" => {
+            let ratio = match arg.map(|arg| arg.trim().parse::<f64>().map(clamp_ratio)) {
+                Some(Ok(Ok(ratio))) => ratio,
+                Some(_) => {
+                    // the config is loaded before the log subscriber is installed
+                    eprintln!("{OTEL_TRACES_SAMPLER_ARG} must be a ratio between 0.0 and 1.0, all traces are sampled");
+                    1.0
+                }
+                None => 1.0,
+            };
+            Sampler::TraceIdRatioBased(ratio)
+        }
+        _ => {
+            eprintln!(
+                "{OTEL_TRACES_SAMPLER} `{sampler}` isn't supported, the configured sampler is used"
+            );
+            return None;
+        }
+    };
+    Some(root)
+}
+
+/// How the ended spans are handed to the exporter.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, JsonSchema, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SpanProcessorKind {
+    /// Export the spans in batches from a background thread.
+    #[default]
+    Batch,
+    /// Export each span when it ends.
+    Simple,
+}
+
+/// The HTTP response status codes marking the spans as errors.
+///
+/// refs: <https://opentelemetry.io/docs/specs/semconv/http/http-spans/#status>
+#[derive(Debug, Clone, Copy, PartialEq, Eq, JsonSchema, Deserialize)]
+pub enum HttpErrorStatus {
+    /// The `5xx` server errors.
+    #[serde(rename = "5xx")]
+    ServerErrors,
+    /// The `4xx` client errors and the `5xx` server errors.
+    #[serde(rename = "4xx")]
+    ClientErrors,
+}
+
+impl HttpErrorStatus {
+    /// The lowest status code marking the spans as errors.
+    pub(crate) fn min_status_code(&self) -> i64 {
+        match self {
+            Self::ServerErrors => 500,
+            Self::ClientErrors => 400,
+        }
+    }
+}
+
+/// How the spans are sent to several endpoints.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, JsonSchema, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EndpointsMode {
+    /// Send the spans to all endpoints, each with its own span processor.
+    #[default]
+    Mirror,
+    /// Send the spans to the first endpoint, and to the next endpoints in order when the
+    /// previous ones fail.
+    Failover,
+}
+
+/// Compression of the OTLP exports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, JsonSchema, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OtlpCompression {
+    /// Don't compress the exports.
+    None,
+    /// Compress the exports with gzip, requires the `gzip` feature.
+    Gzip,
+    /// Compress the exports with zstd, requires the `zstd` feature.
+    Zstd,
+}
+
+impl std::fmt::Display for OtlpCompression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::None => f.write_str("none"),
+            Self::Gzip => f.write_str("gzip"),
+            Self::Zstd => f.write_str("zstd"),
+        }
+    }
+}
+
+/// Minimum level of the exported log events.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, JsonSchema, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogsLevel {
+    /// Export no log events.
+    Off,
+    /// Export the events of all levels.
+    Trace,
+    /// Export the `debug` events and above.
+    Debug,
+    /// Export the `info` events and above.
+    Info,
+    /// Export the `warn` and `error` events.
+    Warn,
+    /// Export the `error` events.
+    Error,
+}
+
+impl From<LogsLevel> for LevelFilter {
+    fn from(level: LogsLevel) -> Self {
+        match level {
+            LogsLevel::Off => LevelFilter::OFF,
+            LogsLevel::Trace => LevelFilter::TRACE,
+            LogsLevel::Debug => LevelFilter::DEBUG,
+            LogsLevel::Info => LevelFilter::INFO,
+            LogsLevel::Warn => LevelFilter::WARN,
+            LogsLevel::Error => LevelFilter::ERROR,
+        }
+    }
+}
+
+/// The severities of the log records by `tracing` level, the unset ones are those of the logs
+/// data model, e.g. `WARN` is `13`.
+///
+/// refs: <https://opentelemetry.io/docs/specs/otel/logs/data-model/#field-severitynumber>
+#[derive(Debug, Clone, Default, JsonSchema, Deserialize)]
+pub struct LogsSeverityMapping {
+    pub trace: Option<LogSeverity>,
+    pub debug: Option<LogSeverity>,
+    pub info: Option<LogSeverity>,
+    pub warn: Option<LogSeverity>,
+    pub error: Option<LogSeverity>,
+}
+
+/// The severity of the log records of a `tracing` level.
+#[derive(Debug, Clone, JsonSchema, Deserialize)]
+pub struct LogSeverity {
+    /// Severity number between `1` (`TRACE`) and `24` (`FATAL4`), unchanged if not set.
+    #[serde(default, deserialize_with = "deserialize_severity_number")]
+    pub number: Option<u8>,
+
+    /// Severity text, the name of the level, e.g. `WARN`, if not set.
+    pub text: Option<String>,
+}
+
+fn deserialize_severity_number<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<u8>, D::Error> {
+    let number = Option::<u8>::deserialize(deserializer)?;
+    match number {
+        Some(number) if !(1..=24).contains(&number) => Err(serde::de::Error::custom(format!(
+            "severity number must be between 1 and 24, got {number}"
+        ))),
+        number => Ok(number),
+    }
+}
+
+/// Disables the plugin regardless of `enable` when set to `true`, as specified for the SDKs.
+pub const OTEL_SDK_DISABLED: &str = "OTEL_SDK_DISABLED";
+
+/// The `service.name` when `service_name` isn't set, overriding the `service.name` of
+/// `OTEL_RESOURCE_ATTRIBUTES` as specified for the SDKs.
+pub const OTEL_SERVICE_NAME: &str = "OTEL_SERVICE_NAME";
+
+/// The sampler of the root spans, overriding `sampler_ratio` as specified for the SDKs:
+/// `always_on`, `always_off`, `traceidratio` or their `parentbased_` variants.
+pub const OTEL_TRACES_SAMPLER: &str = "OTEL_TRACES_SAMPLER";
+
+/// The ratio of the `traceidratio` samplers of `OTEL_TRACES_SAMPLER`, `1.0` if not set.
+pub const OTEL_TRACES_SAMPLER_ARG: &str = "OTEL_TRACES_SAMPLER_ARG";
+
+impl OpenTelemetryConfig {
+    /// Whether the plugin is enabled: `enable` is set and `OTEL_SDK_DISABLED` isn't `true`.
+    pub(crate) fn enabled(&self) -> bool {
+        self.enable && !sdk_disabled(std::env::var(OTEL_SDK_DISABLED).ok().as_deref())
+    }
+
+    /// The configured `service_name`, or the value of `OTEL_SERVICE_NAME` if it's not empty.
+    pub(crate) fn service_name(&self) -> Option<String> {
+        self.service_name.clone().or_else(|| {
+            std::env::var(OTEL_SERVICE_NAME)
+                .ok()
+                .filter(|name| !name.trim().is_empty())
+        })
+    }
+
+    /// The git commit SHA read from `commit_sha_env_var`, `GIT_COMMIT` by default.
+    pub(crate) fn commit_sha(&self) -> Option<String> {
+        let var = self.commit_sha_env_var.as_deref().unwrap_or("GIT_COMMIT");
+        std::env::var(var)
+            .ok()
+            .map(|sha| sha.trim().to_string())
+            .filter(|sha| !sha.is_empty())
+    }
+
+    /// The configured `propagators`, or the default ones of the enabled features.
+    pub(crate) fn propagators(&self) -> Vec<PropagatorKind> {
+        if let Some(propagators) = &self.propagators {
+            return propagators.clone();
+        }
+        let mut propagators = Vec::with_capacity(2);
+        if self.baggage {
+            propagators.push(PropagatorKind::Baggage);
+        }
+        if cfg!(feature = "zipkin") {
+            propagators.push(PropagatorKind::B3Multi);
+        } else if cfg!(feature = "jaeger") {
+            propagators.push(PropagatorKind::Jaeger);
+        } else {
+            propagators.push(PropagatorKind::TraceContext);
+        }
+        propagators
+    }
+
+    /// The exporter of the given signal: the signal specific config first, then `exporter`.
+    pub(crate) fn exporter(&self, signal: Signal) -> ExporterKind {
+        let exporter = match signal {
+            Signal::Traces => self.traces_exporter,
+            Signal::Metrics => self.metrics_exporter,
+            Signal::Logs => self.logs_exporter,
+        };
+        exporter.unwrap_or(self.exporter)
+    }
+
+    /// The instrumentation scope of the tracer of the `tracing` layer.
+    pub(crate) fn instrumentation_scope(&self) -> InstrumentationScope {
+        let (name, default_version) = match (&self.instrumentation_scope_name, &self.service_name) {
+            (Some(name), _) => (name.clone(), None),
+            (None, Some(service_name)) => (service_name.clone(), self.service_version.clone()),
+            (None, None) => (
+                env!("CARGO_PKG_NAME").to_string(),
+                Some(env!("CARGO_PKG_VERSION").to_string()),
+            ),
+        };
+        let version = self
+            .instrumentation_scope_version
+            .clone()
+            .or(default_version);
+        let builder = InstrumentationScope::builder(name);
+        match version {
+            Some(version) => builder.with_version(version).build(),
+            None => builder.build(),
+        }
+    }
+
+    /// The resource attributes of the signal only.
+    pub(crate) fn signal_resource_attributes(
+        &self,
+        signal: Signal,
+    ) -> Option<&HashMap<String, String>> {
+        match signal {
+            Signal::Traces => self.traces_resource_attributes.as_ref(),
+            Signal::Metrics => self.metrics_resource_attributes.as_ref(),
+            Signal::Logs => self.logs_resource_attributes.as_ref(),
+        }
+    }
+
+    /// The configured compression of the given signal, `None` when the compression is set by
+    /// the env vars.
+    pub(crate) fn compression(&self, signal: Signal) -> Option<OtlpCompression> {
+        if signal.compression_from_env() {
+            return None;
+        }
+        let compression = match signal {
+            Signal::Traces => self.traces_compression,
+            Signal::Metrics => self.metrics_compression,
+            Signal::Logs => self.logs_compression,
+        };
+        compression.or(self.compression)
+    }
+
+    /// The OTLP protocol of the given signal: the `*_protocol` and `protocol` config first,
+    /// then the `OTEL_EXPORTER_OTLP_*_PROTOCOL` and `OTEL_EXPORTER_OTLP_PROTOCOL` environment
+    /// variables, and finally the default of the enabled features.
+    pub(crate) fn protocol(&self, signal: Signal) -> OtlpProtocol {
+        let protocol = match signal {
+            Signal::Traces => self.traces_protocol,
+            Signal::Metrics => self.metrics_protocol,
+            Signal::Logs => self.logs_protocol,
+        };
+        protocol
+            .or(self.protocol)
+            .or_else(|| OtlpProtocol::from_env(signal.protocol_var()))
+            .or_else(|| OtlpProtocol::from_env(opentelemetry_otlp::OTEL_EXPORTER_OTLP_PROTOCOL))
+            .unwrap_or_default()
+    }
+
+    /// The root sampler set by `OTEL_TRACES_SAMPLER` and `OTEL_TRACES_SAMPLER_ARG`.
+    pub(crate) fn env_traces_sampler(&self) -> Option<Sampler> {
+        traces_sampler(
+            std::env::var(OTEL_TRACES_SAMPLER).ok().as_deref(),
+            std::env::var(OTEL_TRACES_SAMPLER_ARG).ok().as_deref(),
+        )
+    }
+
+    /// The configured `sampler_ratio`, clamped to the `0.0..=1.0` range.
+    pub(crate) fn sampler_ratio(&self) -> Option<f64> {
+        self.sampler_ratio
+    }
+
+    /// The configured `sampling_rules` as path prefixes and ratios.
+    pub(crate) fn sampling_rules(&self) -> Vec<(String, f64)> {
+        self.sampling_rules
+            .iter()
+            .map(|rule| (rule.path_prefix.clone(), rule.ratio))
+            .collect()
+    }
+
+    /// The span limits of the tracer provider,
+    /// keeping the SDK defaults for the limits that aren't configured.
+    pub(crate) fn span_limits(&self) -> SpanLimits {
+        let mut span_limits = SpanLimits::default();
+        if let Some(max_attributes_per_span) = self.max_attributes_per_span {
+            span_limits.max_attributes_per_span = max_attributes_per_span;
+        }
+        if let Some(max_events_per_span) = self.max_events_per_span {
+            span_limits.max_events_per_span = max_events_per_span;
+        }
+        if let Some(max_links_per_span) = self.max_links_per_span {
+            span_limits.max_links_per_span = max_links_per_span;
+        }
+        span_limits
+    }
+
+    /// The `max_queue_size` of the batch span processor, or its default.
+    pub(crate) fn max_queue_size(&self) -> usize {
+        self.max_queue_size
+            .or_else(|| std::env::var("OTEL_BSP_MAX_QUEUE_SIZE").ok()?.parse().ok())
+            .unwrap_or(2048)
+    }
+
+    /// The batch config of the trace batch span processor,
+    /// keeping the SDK defaults for the options that aren't configured.
+    pub(crate) fn trace_batch_config(&self) -> opentelemetry_sdk::trace::BatchConfig {
+        let mut builder = opentelemetry_sdk::trace::BatchConfigBuilder::default();
+        if let Some(max_queue_size) = self.max_queue_size {
+            builder = builder.with_max_queue_size(max_queue_size);
+        }
+        if let Some(max_export_batch_size) = self.max_export_batch_size {
+            builder = builder.with_max_export_batch_size(max_export_batch_size);
+        }
+        if let Some(scheduled_delay_ms) = self.scheduled_delay_ms {
+            builder = builder.with_scheduled_delay(Duration::from_millis(scheduled_delay_ms));
+        }
+        builder.build()
+    }
+
+    /// The batch config of the batch log processor,
+    /// keeping the SDK defaults for the options that aren't configured.
+    pub(crate) fn log_batch_config(&self) -> opentelemetry_sdk::logs::BatchConfig {
+        let mut builder = opentelemetry_sdk::logs::BatchConfigBuilder::default();
+        if let Some(max_queue_size) = self.max_queue_size {
+            builder = builder.with_max_queue_size(max_queue_size);
+        }
+        if let Some(max_export_batch_size) = self.max_export_batch_size {
+            builder = builder.with_max_export_batch_size(max_export_batch_size);
+        }
+        if let Some(scheduled_delay_ms) = self.scheduled_delay_ms {
+            builder = builder.with_scheduled_delay(Duration::from_millis(scheduled_delay_ms));
+        }
+        builder.build()
+    }
+
+    /// The `export_timeout_ms` of the exporters, `None` when the timeout of the signal is set by
+    /// the env vars.
+    pub(crate) fn export_timeout(&self, signal: Signal) -> Option<Duration> {
+        if signal.timeout_from_env() {
+            return None;
+        }
+        self.export_timeout_ms.map(Duration::from_millis)
+    }
+
+    /// This config with the settings of the section of `signal` applied, read by the
+    /// `init_*` functions of the signal.
+    pub(crate) fn for_signal(&self, signal: Signal) -> Self {
+        let section = match signal {
+            Signal::Traces => &self.traces,
+            Signal::Metrics => &self.metrics,
+            Signal::Logs => &self.logs,
+        };
+        let mut config = self.clone();
+        let Some(section) = section else {
+            return config;
+        };
+        if let Some(endpoint) = &section.endpoint {
+            let signal_endpoint = match signal {
+                Signal::Traces => &mut config.traces_endpoint,
+                Signal::Metrics => &mut config.metrics_endpoint,
+                Signal::Logs => &mut config.logs_endpoint,
+            };
+            *signal_endpoint = Some(endpoint.clone());
+        }
+        if let Some(headers) = &section.headers {
+            config
+                .headers
+                .get_or_insert_with(HashMap::new)
+                .extend(headers.clone());
+        }
+        if let Some(compression) = section.compression {
+            let signal_compression = match signal {
+                Signal::Traces => &mut config.traces_compression,
+                Signal::Metrics => &mut config.metrics_compression,
+                Signal::Logs => &mut config.logs_compression,
+            };
+            *signal_compression = Some(compression);
+        }
+        if let Some(protocol) = section.protocol {
+            let signal_protocol = match signal {
+                Signal::Traces => &mut config.traces_protocol,
+                Signal::Metrics => &mut config.metrics_protocol,
+                Signal::Logs => &mut config.logs_protocol,
+            };
+            *signal_protocol = Some(protocol);
+        }
+        config.export_timeout_ms = section.export_timeout_ms.or(config.export_timeout_ms);
+        // the TLS settings are replaced together, e.g. a vendor needing no client certificate
+        if section.tls_ca_cert_path.is_some()
+            || section.tls_client_cert_path.is_some()
+            || section.tls_client_key_path.is_some()
+        {
+            config.tls_ca_cert_path = section.tls_ca_cert_path.clone();
+            config.tls_client_cert_path = section.tls_client_cert_path.clone();
+            config.tls_client_key_path = section.tls_client_key_path.clone();
+        }
+        config
+    }
+
+    /// This config with the full `traces_endpoint` of one of the `traces_endpoints`.
+    pub(crate) fn with_traces_endpoint(&self, endpoint: &str) -> Self {
+        Self {
+            traces_endpoint: Some(endpoint.to_string()),
+            ..self.clone()
+        }
+    }
+
+    /// The retry policy of the OTLP exporter of a signal, without retries when the exports
+    /// aren't run on a background thread.
+    pub(crate) fn retry_policy(&self, signal: Signal) -> RetryPolicy {
+        let max_retries = match signal {
+            Signal::Traces if self.span_processor == SpanProcessorKind::Simple => 0,
+            _ => self.max_retries,
+        };
+        RetryPolicy::new(max_retries, Duration::from_millis(self.initial_backoff_ms))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config_with_ratio(ratio: f64) -> OpenTelemetryConfig {
+        let table = toml::toml! { sampler_ratio = ratio };
+        OpenTelemetryConfig::deserialize(table).expect("config deserialize failed")
+    }
+
+    #[test]
+    fn test_exporter_per_signal() {
+        let table = toml::toml! {
+            exporter = "stdout"
+            metrics_exporter = "otlp"
+        };
+        let config = OpenTelemetryConfig::deserialize(table).expect("config deserialize failed");
+        assert_eq!(config.exporter(Signal::Traces), ExporterKind::Stdout);
+        assert_eq!(config.exporter(Signal::Metrics), ExporterKind::Otlp);
+        assert_eq!(config.exporter(Signal::Logs), ExporterKind::Stdout);
+    }
+
+    #[test]
+    fn test_signal_sections() {
+        let table = toml::toml! {
+            endpoint = "http://collector:4317"
+            headers = { team = "payments" }
+            compression = "gzip"
+            tls_ca_cert_path = "ca.pem"
+            tls_client_cert_path = "client.pem"
+            tls_client_key_path = "client.key"
+
+            [traces]
+            endpoint = "https://api.honeycomb.io:443"
+            headers = { x-honeycomb-team = "secret" }
+            tls_ca_cert_path = "honeycomb-ca.pem"
+
+            [metrics]
+            compression = "none"
+        };
+        let config = OpenTelemetryConfig::deserialize(table).expect("config deserialize failed");
+
+        let traces = config.for_signal(Signal::Traces);
+        assert_eq!(
+            traces.traces_endpoint.as_deref(),
+            Some("https://api.honeycomb.io:443")
+        );
+        let headers = traces.headers.unwrap();
+        assert_eq!(headers["team"], "payments");
+        assert_eq!(headers["x-honeycomb-team"], "secret");
+        assert_eq!(traces.tls_ca_cert_path.as_deref(), Some("honeycomb-ca.pem"));
+        assert_eq!(traces.tls_client_cert_path, None);
+
+        let metrics = config.for_signal(Signal::Metrics);
+        assert_eq!(metrics.metrics_endpoint, None);
+        assert_eq!(metrics.endpoint.as_deref(), Some("http://collector:4317"));
+        assert_eq!(
+            metrics.compression(Signal::Metrics),
+            Some(OtlpCompression::None)
+        );
+        assert_eq!(metrics.tls_client_cert_path.as_deref(), Some("client.pem"));
+        assert_eq!(metrics.headers.unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_signal_protocol() {
+        let table = toml::toml! {
+            protocol = "grpc"
+            logs_protocol = "http/protobuf"
+
+            [metrics]
+            protocol = "http/json"
+        };
+        let config = OpenTelemetryConfig::deserialize(table).expect("config deserialize failed");
+        assert_eq!(config.protocol(Signal::Traces), OtlpProtocol::Grpc);
+        assert_eq!(config.protocol(Signal::Logs), OtlpProtocol::HttpProtobuf);
+        assert_eq!(
+            config.for_signal(Signal::Metrics).protocol(Signal::Metrics),
+            OtlpProtocol::HttpJson
+        );
+    }
+
+    #[test]
+    fn test_compression() {
+        let table = toml::toml! {
+            compression = "gzip"
+            metrics_compression = "none"
+        };
+        let config = OpenTelemetryConfig::deserialize(table).expect("config deserialize failed");
+        assert_eq!(
+            config.compression(Signal::Traces),
+            Some(OtlpCompression::Gzip)
+        );
+        assert_eq!(
+            config.compression(Signal::Metrics),
+            Some(OtlpCompression::None)
+        );
+
+        let table = toml::toml! { compression = "brotli" };
+        let err = OpenTelemetryConfig::deserialize(table).expect_err("brotli is not supported");
+        assert!(err.to_string().contains("unknown variant `brotli`"));
+    }
+
+    #[test]
+    fn test_instrumentation_scope() {
+        let table = toml::toml! {
+            service_name = "payments"
+            service_version = "1.2.0"
+        };
+        let config = OpenTelemetryConfig::deserialize(table).expect("config deserialize failed");
+        let scope = config.instrumentation_scope();
+        assert_eq!(scope.name(), "payments");
+        assert_eq!(scope.version(), Some("1.2.0"));
+
+        let table = toml::toml! {
+            service_name = "payments"
+            instrumentation_scope_name = "checkout"
+        };
+        let config = OpenTelemetryConfig::deserialize(table).expect("config deserialize failed");
+        let scope = config.instrumentation_scope();
+        assert_eq!(scope.name(), "checkout");
+        assert_eq!(scope.version(), None);
+    }
+
+    #[test]
+    fn test_sdk_disabled() {
+        assert!(sdk_disabled(Some("true")));
+        assert!(sdk_disabled(Some(" TRUE ")));
+        assert!(!sdk_disabled(Some("false")));
+        assert!(!sdk_disabled(Some("")));
+        assert!(!sdk_disabled(None));
+    }
+
+    #[test]
+    fn test_traces_sampler() {
+        let sampler =
+            |sampler, arg| traces_sampler(Some(sampler), arg).map(|sampler| format!("{sampler:?}"));
+        assert_eq!(
+            sampler("parentbased_traceidratio", Some("0.1")).as_deref(),
+            Some("TraceIdRatioBased(0.1)")
+        );
+        assert_eq!(
+            sampler("traceidratio", Some("abc")).as_deref(),
+            Some("TraceIdRatioBased(1.0)")
+        );
+        assert_eq!(
+            sampler("traceidratio", Some("2")).as_deref(),
+            Some("TraceIdRatioBased(1.0)")
+        );
+        assert_eq!(sampler(" always_off ", None).as_deref(), Some("AlwaysOff"));
+        assert_eq!(
+            sampler("parentbased_always_on", Some("0.1")).as_deref(),
+            Some("AlwaysOn")
+        );
+        assert_eq!(sampler("parentbased_jaeger_remote", None), None);
+        assert!(traces_sampler(None, Some("0.1")).is_none());
+    }
+
+    #[test]
+    fn test_sampler_ratio_clamped() {
+        assert_eq!(config_with_ratio(0.25).sampler_ratio(), Some(0.25));
+        assert_eq!(config_with_ratio(1.5).sampler_ratio(), Some(1.0));
+        assert_eq!(config_with_ratio(-0.5).sampler_ratio(), Some(0.0));
+
+        let table = toml::from_str::<toml::Table>("sampler_ratio = nan").expect("parse failed");
+        assert!(OpenTelemetryConfig::deserialize(table).is_err());
+    }
+
+    #[test]
+    fn test_no_retries_with_simple_span_processor() {
+        let config = OpenTelemetryConfig::deserialize(toml::toml! {
+            span_processor = "simple"
+            max_retries = 3
+        })
+        .expect("config deserialize failed");
+        assert_eq!(config.retry_policy(Signal::Traces).max_retries, 0);
+        assert_eq!(config.retry_policy(Signal::Logs).max_retries, 3);
+    }
+}
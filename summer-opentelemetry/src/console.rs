@@ -0,0 +1,198 @@
+//! Human readable console output of the spans and the log records, for local development.
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use opentelemetry::logs::AnyValue;
+use opentelemetry_sdk::error::{OTelSdkError, OTelSdkResult};
+use opentelemetry_sdk::logs::{LogBatch, LogExporter, SdkLogRecord};
+use std::fmt::Write;
+use std::time::Instant;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::Subscriber;
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+/// Prints the name, duration and fields of each closed span, indented by its depth:
+///
+/// ```plain
+///   SELECT 1.2ms db.system=postgresql db.statement="SELECT 1"
+/// HTTP request 3.8ms http.request.method=GET url.path=/orders
+/// ```
+///
+/// Children close before their parent, so they are printed first.
+#[derive(Debug, Default)]
+pub(crate) struct ConsoleLayer;
+
+/// The start and recorded fields of a span, stored in its extensions.
+struct ConsoleSpan {
+    start: Instant,
+    fields: String,
+}
+
+/// Formats the fields as `key=value` pairs.
+struct FieldsVisitor<'a>(&'a mut String);
+
+impl Visit for FieldsVisitor<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        let value = if value.contains(char::is_whitespace) {
+            format!("{value:?}")
+        } else {
+            value.to_string()
+        };
+        let _ = write!(self.0, " {}={value}", field.name());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        let _ = write!(self.0, " {}={value:?}", field.name());
+    }
+}
+
+impl<S> Layer<S> for ConsoleLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut fields = String::new();
+        attrs.record(&mut FieldsVisitor(&mut fields));
+        span.extensions_mut().insert(ConsoleSpan {
+            start: Instant::now(),
+            fields,
+        });
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut extensions = span.extensions_mut();
+        if let Some(console_span) = extensions.get_mut::<ConsoleSpan>() {
+            values.record(&mut FieldsVisitor(&mut console_span.fields));
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let depth = span.scope().skip(1).count();
+        let extensions = span.extensions();
+        if let Some(console_span) = extensions.get::<ConsoleSpan>() {
+            println!(
+                "{:indent$}{} {:.1?}{}",
+                "",
+                span.name(),
+                console_span.start.elapsed(),
+                console_span.fields,
+                indent = depth * 2
+            );
+        }
+    }
+}
+
+/// Prints the log records of the OpenTelemetry log pipeline in the logfmt format, one line per
+/// record with its timestamp, severity, target, body, trace context and attributes:
+///
+/// ```plain
+/// time=2026-10-14T08:30:00.000000Z level=INFO target=app msg="order created" trace_id=4bf92f3577b34da6a3ce929d0e0e4736 span_id=00f067aa0ba902b7 tenant.id=acme
+/// ```
+#[derive(Debug, Default)]
+pub(crate) struct LogfmtExporter;
+
+impl LogExporter for LogfmtExporter {
+    async fn export(&self, batch: LogBatch<'_>) -> OTelSdkResult {
+        let mut lines = String::new();
+        for (record, _scope) in batch.iter() {
+            lines.push_str(&format_record(record));
+            lines.push('\n');
+        }
+        std::io::Write::write_all(&mut std::io::stdout().lock(), lines.as_bytes())
+            .map_err(|e| OTelSdkError::InternalFailure(format!("print log records failed: {e}")))
+    }
+}
+
+/// Formats a log record as a logfmt line, without the line break.
+fn format_record(record: &SdkLogRecord) -> String {
+    let mut line = String::new();
+    if let Some(time) = record.timestamp().or(record.observed_timestamp()) {
+        let time = DateTime::<Utc>::from(time).to_rfc3339_opts(SecondsFormat::Micros, true);
+        let _ = write!(line, "time={time} ");
+    }
+    match (record.severity_text(), record.severity_number()) {
+        (Some(level), _) => write_pair(&mut line, "level", level),
+        (None, Some(severity)) => write_pair(&mut line, "level", severity.name()),
+        (None, None) => {}
+    }
+    if let Some(target) = record.target() {
+        write_pair(&mut line, "target", target);
+    }
+    if let Some(body) = record.body() {
+        write_pair(&mut line, "msg", &format_value(body));
+    }
+    if let Some(trace_context) = record.trace_context() {
+        write_pair(&mut line, "trace_id", &trace_context.trace_id.to_string());
+        write_pair(&mut line, "span_id", &trace_context.span_id.to_string());
+    }
+    for (key, value) in record.attributes_iter() {
+        write_pair(&mut line, key.as_str(), &format_value(value));
+    }
+    line.truncate(line.trim_end().len());
+    line
+}
+
+/// Writes `key=value `, quoting the values that are empty or contain spaces, `=` or quotes.
+fn write_pair(line: &mut String, key: &str, value: &str) {
+    let _ = if value.is_empty()
+        || value.contains(|c: char| c.is_whitespace() || c == '=' || c == '"')
+    {
+        write!(line, "{key}={value:?} ")
+    } else {
+        write!(line, "{key}={value} ")
+    };
+}
+
+fn format_value(value: &AnyValue) -> String {
+    match value {
+        AnyValue::String(value) => value.to_string(),
+        AnyValue::Int(value) => value.to_string(),
+        AnyValue::Double(value) => value.to_string(),
+        AnyValue::Boolean(value) => value.to_string(),
+        value => format!("{value:?}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry::logs::{LogRecord as _, Logger, LoggerProvider, Severity};
+    use opentelemetry_sdk::logs::{InMemoryLogExporter, SdkLoggerProvider};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn test_format_record() {
+        let exporter = InMemoryLogExporter::default();
+        let provider = SdkLoggerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+        let logger = provider.logger("test");
+        let mut record = logger.create_log_record();
+        record.set_timestamp(UNIX_EPOCH + Duration::from_millis(1_500));
+        record.set_severity_number(Severity::Warn);
+        record.set_target("app");
+        record.set_body("payment declined".into());
+        record.add_attribute("tenant.id", "acme");
+        record.add_attribute("amount", 42);
+        record.add_attribute("reason", "card expired");
+        logger.emit(record);
+
+        let logs = exporter.get_emitted_logs().expect("get logs failed");
+        assert_eq!(
+            format_record(&logs[0].record),
+            "time=1970-01-01T00:00:01.500000Z level=WARN target=app msg=\"payment declined\" \
+             tenant.id=acme amount=42 reason=\"card expired\""
+        );
+    }
+}
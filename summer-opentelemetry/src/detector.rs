@@ -0,0 +1,248 @@
+//! Resource detectors of the environments the application runs in.
+
+use opentelemetry_sdk::resource::ResourceDetector;
+use opentelemetry_sdk::Resource;
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "aws")]
+mod aws;
+#[cfg(feature = "gcp")]
+mod gcp;
+#[cfg(any(feature = "aws", feature = "gcp"))]
+mod metadata;
+
+#[cfg(feature = "aws")]
+pub use aws::AwsResourceDetector;
+#[cfg(feature = "gcp")]
+pub use gcp::GcpResourceDetector;
+#[cfg(feature = "k8s")]
+pub use k8s::K8sResourceDetector;
+
+/// The resource detectors added in code, run after the detectors of the features.
+#[derive(Clone, Default)]
+pub(crate) struct CustomResourceDetectors(Vec<Arc<dyn ResourceDetector + Send + Sync>>);
+
+impl CustomResourceDetectors {
+    pub(crate) fn push<D: ResourceDetector + Send + Sync + 'static>(&mut self, detector: D) {
+        self.0.push(Arc::new(detector));
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &Arc<dyn ResourceDetector + Send + Sync>> {
+        self.0.iter()
+    }
+}
+
+impl std::fmt::Debug for CustomResourceDetectors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CustomResourceDetectors")
+            .field("len", &self.0.len())
+            .finish()
+    }
+}
+
+/// Runs the inner detectors concurrently and omits the attributes of the detectors that
+/// don't finish within the timeout, e.g. because a metadata endpoint is slow or unreachable.
+pub(crate) struct TimeoutDetector {
+    detectors: Vec<Arc<dyn ResourceDetector + Send + Sync>>,
+    timeout: Duration,
+}
+
+impl TimeoutDetector {
+    pub(crate) fn new(
+        detectors: Vec<Arc<dyn ResourceDetector + Send + Sync>>,
+        timeout: Duration,
+    ) -> Self {
+        Self { detectors, timeout }
+    }
+}
+
+impl ResourceDetector for TimeoutDetector {
+    fn detect(&self) -> Resource {
+        let deadline = Instant::now() + self.timeout;
+        let (tx, rx) = mpsc::channel();
+        for (index, detector) in self.detectors.iter().enumerate() {
+            let detector = detector.clone();
+            let tx = tx.clone();
+            std::thread::spawn(move || {
+                let _ = tx.send((index, detector.detect()));
+            });
+        }
+        drop(tx);
+
+        let mut resources = Vec::with_capacity(self.detectors.len());
+        while resources.len() < self.detectors.len() {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            match rx.recv_timeout(timeout) {
+                Ok(resource) => resources.push(resource),
+                Err(_) => break,
+            }
+        }
+        // later detectors take precedence, regardless of the order they finished in
+        resources.sort_by_key(|(index, _)| *index);
+        let attributes = resources
+            .iter()
+            .flat_map(|(_, resource)| resource.iter())
+            .map(|(key, value)| opentelemetry::KeyValue::new(key.clone(), value.clone()))
+            .collect::<Vec<_>>();
+        Resource::builder_empty()
+            .with_attributes(attributes)
+            .build()
+    }
+}
+
+#[cfg(feature = "k8s")]
+mod k8s {
+    use opentelemetry::KeyValue;
+    use opentelemetry_sdk::resource::ResourceDetector;
+    use opentelemetry_sdk::Resource;
+    use opentelemetry_semantic_conventions::resource::{
+        K8S_DEPLOYMENT_NAME, K8S_NAMESPACE_NAME, K8S_NODE_NAME, K8S_POD_NAME, K8S_POD_UID,
+    };
+
+    /// File of the namespace mounted into every pod with the service account token.
+    const NAMESPACE_FILE: &str = "/var/run/secrets/kubernetes.io/serviceaccount/namespace";
+
+    /// Environment variables of each attribute, the first one that is set wins.
+    const ATTRIBUTE_VARS: [(&str, &[&str]); 5] = [
+        (K8S_POD_NAME, &["K8S_POD_NAME", "POD_NAME"]),
+        (K8S_POD_UID, &["K8S_POD_UID", "POD_UID"]),
+        (K8S_NAMESPACE_NAME, &["K8S_NAMESPACE_NAME", "POD_NAMESPACE"]),
+        (K8S_NODE_NAME, &["K8S_NODE_NAME", "NODE_NAME"]),
+        (
+            K8S_DEPLOYMENT_NAME,
+            &["K8S_DEPLOYMENT_NAME", "DEPLOYMENT_NAME"],
+        ),
+    ];
+
+    /// Detects the `k8s.*` attributes of the pod the application runs in.
+    ///
+    /// The attributes are read from environment variables populated with the
+    /// [downward API](https://kubernetes.io/docs/concepts/workloads/pods/downward-api/),
+    /// e.g. `K8S_POD_NAME` from `metadata.name` and `K8S_NODE_NAME` from `spec.nodeName`.
+    /// The namespace falls back to the one of the mounted service account, and the pod name
+    /// to `HOSTNAME`. Attributes that can't be detected are omitted, so outside of
+    /// Kubernetes the detected resource is empty.
+    #[derive(Debug, Default)]
+    pub struct K8sResourceDetector;
+
+    impl ResourceDetector for K8sResourceDetector {
+        fn detect(&self) -> Resource {
+            let in_cluster = std::env::var_os("KUBERNETES_SERVICE_HOST").is_some();
+            let attributes = detect_attributes(
+                |var| std::env::var(var).ok(),
+                || std::fs::read_to_string(NAMESPACE_FILE).ok(),
+                in_cluster,
+            );
+            Resource::builder_empty()
+                .with_attributes(attributes)
+                .build()
+        }
+    }
+
+    fn detect_attributes(
+        env: impl Fn(&str) -> Option<String>,
+        namespace_file: impl Fn() -> Option<String>,
+        in_cluster: bool,
+    ) -> Vec<KeyValue> {
+        let mut attributes = Vec::new();
+        for (key, vars) in ATTRIBUTE_VARS {
+            let value = vars
+                .iter()
+                .filter_map(|var| env(var))
+                .find(|value| !value.is_empty())
+                .or_else(|| match key {
+                    K8S_NAMESPACE_NAME if in_cluster => namespace_file()
+                        .map(|namespace| namespace.trim().to_string())
+                        .filter(|namespace| !namespace.is_empty()),
+                    K8S_POD_NAME if in_cluster => env("HOSTNAME"),
+                    _ => None,
+                });
+            if let Some(value) = value {
+                attributes.push(KeyValue::new(key, value));
+            }
+        }
+        attributes
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use std::collections::HashMap;
+
+        fn detect(vars: &[(&str, &str)], in_cluster: bool) -> HashMap<String, String> {
+            let vars: HashMap<_, _> = vars.iter().copied().collect();
+            detect_attributes(
+                |var| vars.get(var).map(|value| value.to_string()),
+                || Some("payments\n".to_string()),
+                in_cluster,
+            )
+            .into_iter()
+            .map(|kv| (kv.key.to_string(), kv.value.to_string()))
+            .collect()
+        }
+
+        #[test]
+        fn test_detect_from_downward_api() {
+            let attributes = detect(
+                &[
+                    ("K8S_POD_NAME", "checkout-7d9f8b6c5-x2x4z"),
+                    ("POD_NAMESPACE", "shop"),
+                    ("NODE_NAME", "node-1"),
+                    ("K8S_DEPLOYMENT_NAME", "checkout"),
+                ],
+                true,
+            );
+            assert_eq!(attributes[K8S_POD_NAME], "checkout-7d9f8b6c5-x2x4z");
+            assert_eq!(attributes[K8S_NAMESPACE_NAME], "shop");
+            assert_eq!(attributes[K8S_NODE_NAME], "node-1");
+            assert_eq!(attributes[K8S_DEPLOYMENT_NAME], "checkout");
+            assert!(!attributes.contains_key(K8S_POD_UID));
+        }
+
+        #[test]
+        fn test_detect_fallbacks() {
+            let attributes = detect(&[("HOSTNAME", "checkout-7d9f8b6c5-x2x4z")], true);
+            assert_eq!(attributes[K8S_POD_NAME], "checkout-7d9f8b6c5-x2x4z");
+            assert_eq!(attributes[K8S_NAMESPACE_NAME], "payments");
+
+            assert!(detect(&[("HOSTNAME", "laptop")], false).is_empty());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry::{Key, KeyValue};
+
+    struct SlowDetector(&'static str, Duration);
+
+    impl ResourceDetector for SlowDetector {
+        fn detect(&self) -> Resource {
+            std::thread::sleep(self.1);
+            Resource::builder_empty()
+                .with_attribute(KeyValue::new(self.0, "detected"))
+                .build()
+        }
+    }
+
+    #[test]
+    fn test_timeout_detector_omits_slow_detectors() {
+        let detector = TimeoutDetector::new(
+            vec![
+                Arc::new(SlowDetector("fast", Duration::ZERO)),
+                Arc::new(SlowDetector("slow", Duration::from_secs(5))),
+            ],
+            Duration::from_millis(200),
+        );
+        let resource = detector.detect();
+        assert_eq!(resource.get(&Key::new("fast")), Some("detected".into()));
+        assert_eq!(resource.get(&Key::new("slow")), None);
+    }
+}
@@ -0,0 +1,231 @@
+//! Detector of the AWS environments: ECS tasks, EKS pods and EC2 instances.
+
+use super::metadata;
+use opentelemetry::KeyValue;
+use opentelemetry_sdk::resource::ResourceDetector;
+use opentelemetry_sdk::Resource;
+use opentelemetry_semantic_conventions::resource::{
+    AWS_ECS_CLUSTER_ARN, AWS_ECS_CONTAINER_ARN, AWS_ECS_LAUNCHTYPE, AWS_ECS_TASK_ARN,
+    AWS_ECS_TASK_FAMILY, AWS_ECS_TASK_REVISION, AWS_LOG_GROUP_NAMES, AWS_LOG_STREAM_NAMES,
+    CLOUD_ACCOUNT_ID, CLOUD_AVAILABILITY_ZONE, CLOUD_PLATFORM, CLOUD_PROVIDER, CLOUD_REGION,
+    CONTAINER_ID, CONTAINER_NAME, HOST_ID, HOST_IMAGE_ID, HOST_NAME, HOST_TYPE,
+};
+use serde_json::Value;
+
+/// Variable of the ECS task metadata endpoint v4, set by the ECS agent in each container.
+const ECS_METADATA_VAR: &str = "ECS_CONTAINER_METADATA_URI_V4";
+
+/// The EC2 instance metadata service.
+const IMDS_URL: &str = "http://169.254.169.254";
+
+/// Detects the `cloud.*`, `aws.*`, `container.*` and `host.*` attributes of the AWS
+/// environment the application runs in.
+///
+/// * ECS, including Fargate: when `ECS_CONTAINER_METADATA_URI_V4` is set, the attributes of
+///   the task and container are read from the task metadata endpoint
+/// * EKS and EC2: the attributes of the instance are read from the instance metadata service
+///   with an IMDSv2 token, `cloud.platform` is `aws_eks` inside Kubernetes
+///
+/// Attributes that can't be detected are omitted, so outside of AWS the detected resource
+/// is empty.
+#[derive(Debug, Default)]
+pub struct AwsResourceDetector;
+
+impl ResourceDetector for AwsResourceDetector {
+    fn detect(&self) -> Resource {
+        let attributes = match std::env::var(ECS_METADATA_VAR) {
+            Ok(url) => detect_ecs(&url),
+            Err(_) => detect_ec2(std::env::var_os("KUBERNETES_SERVICE_HOST").is_some()),
+        };
+        Resource::builder_empty()
+            .with_attributes(attributes)
+            .build()
+    }
+}
+
+fn get_json(url: &str, headers: &[(&str, &str)]) -> Option<Value> {
+    let body = metadata::request("GET", url, headers)?;
+    serde_json::from_str(&body).ok()
+}
+
+fn detect_ecs(url: &str) -> Vec<KeyValue> {
+    match (get_json(url, &[]), get_json(&format!("{url}/task"), &[])) {
+        (Some(container), Some(task)) => ecs_attributes(&container, &task),
+        _ => Vec::new(),
+    }
+}
+
+fn detect_ec2(in_kubernetes: bool) -> Vec<KeyValue> {
+    let token_ttl = [("X-aws-ec2-metadata-token-ttl-seconds", "60")];
+    let Some(token) = metadata::request("PUT", &format!("{IMDS_URL}/latest/api/token"), &token_ttl)
+    else {
+        return Vec::new();
+    };
+    let token = [("X-aws-ec2-metadata-token", token.trim())];
+    let Some(identity) = get_json(
+        &format!("{IMDS_URL}/latest/dynamic/instance-identity/document"),
+        &token,
+    ) else {
+        return Vec::new();
+    };
+    let hostname = metadata::request(
+        "GET",
+        &format!("{IMDS_URL}/latest/meta-data/hostname"),
+        &token,
+    );
+    ec2_attributes(&identity, hostname, in_kubernetes)
+}
+
+/// Pushes the string `field` of `value` as the `key` attribute, if present.
+fn push_str(attributes: &mut Vec<KeyValue>, key: &'static str, value: &Value, field: &str) {
+    if let Some(field) = value.get(field).and_then(Value::as_str) {
+        attributes.push(KeyValue::new(key, field.to_string()));
+    }
+}
+
+fn ecs_attributes(container: &Value, task: &Value) -> Vec<KeyValue> {
+    let mut attributes = vec![
+        KeyValue::new(CLOUD_PROVIDER, "aws"),
+        KeyValue::new(CLOUD_PLATFORM, "aws_ecs"),
+    ];
+    // arn:aws:ecs:<region>:<account>:task/<cluster>/<id>
+    let task_arn = task
+        .get("TaskARN")
+        .and_then(Value::as_str)
+        .unwrap_or_default();
+    let mut arn = task_arn.split(':').skip(3);
+    if let (Some(region), Some(account)) = (arn.next(), arn.next()) {
+        attributes.push(KeyValue::new(CLOUD_REGION, region.to_string()));
+        attributes.push(KeyValue::new(CLOUD_ACCOUNT_ID, account.to_string()));
+    }
+    push_str(
+        &mut attributes,
+        CLOUD_AVAILABILITY_ZONE,
+        task,
+        "AvailabilityZone",
+    );
+    push_str(&mut attributes, AWS_ECS_TASK_ARN, task, "TaskARN");
+    push_str(&mut attributes, AWS_ECS_TASK_FAMILY, task, "Family");
+    push_str(&mut attributes, AWS_ECS_TASK_REVISION, task, "Revision");
+    push_str(&mut attributes, AWS_ECS_CLUSTER_ARN, task, "Cluster");
+    if let Some(launch_type) = task.get("LaunchType").and_then(Value::as_str) {
+        attributes.push(KeyValue::new(
+            AWS_ECS_LAUNCHTYPE,
+            launch_type.to_lowercase(),
+        ));
+    }
+    push_str(
+        &mut attributes,
+        AWS_ECS_CONTAINER_ARN,
+        container,
+        "ContainerARN",
+    );
+    push_str(&mut attributes, CONTAINER_ID, container, "DockerId");
+    push_str(&mut attributes, CONTAINER_NAME, container, "Name");
+    let log_options = &container["LogOptions"];
+    push_str(
+        &mut attributes,
+        AWS_LOG_GROUP_NAMES,
+        log_options,
+        "awslogs-group",
+    );
+    push_str(
+        &mut attributes,
+        AWS_LOG_STREAM_NAMES,
+        log_options,
+        "awslogs-stream",
+    );
+    attributes
+}
+
+fn ec2_attributes(
+    identity: &Value,
+    hostname: Option<String>,
+    in_kubernetes: bool,
+) -> Vec<KeyValue> {
+    let platform = if in_kubernetes { "aws_eks" } else { "aws_ec2" };
+    let mut attributes = vec![
+        KeyValue::new(CLOUD_PROVIDER, "aws"),
+        KeyValue::new(CLOUD_PLATFORM, platform),
+    ];
+    push_str(&mut attributes, CLOUD_REGION, identity, "region");
+    push_str(
+        &mut attributes,
+        CLOUD_AVAILABILITY_ZONE,
+        identity,
+        "availabilityZone",
+    );
+    push_str(&mut attributes, CLOUD_ACCOUNT_ID, identity, "accountId");
+    push_str(&mut attributes, HOST_ID, identity, "instanceId");
+    push_str(&mut attributes, HOST_TYPE, identity, "instanceType");
+    push_str(&mut attributes, HOST_IMAGE_ID, identity, "imageId");
+    if let Some(hostname) = hostname {
+        attributes.push(KeyValue::new(HOST_NAME, hostname.trim().to_string()));
+    }
+    attributes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    fn to_map(attributes: Vec<KeyValue>) -> HashMap<String, String> {
+        attributes
+            .into_iter()
+            .map(|kv| (kv.key.to_string(), kv.value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_ecs_attributes() {
+        let container = json!({
+            "DockerId": "cd189a933e5849daa93386466019ab50-2495160603",
+            "Name": "checkout",
+            "ContainerARN": "arn:aws:ecs:us-west-2:111122223333:container/05966557-f16c",
+            "LogOptions": { "awslogs-group": "/ecs/checkout", "awslogs-stream": "ecs/checkout/cd18" }
+        });
+        let task = json!({
+            "Cluster": "arn:aws:ecs:us-west-2:111122223333:cluster/shop",
+            "TaskARN": "arn:aws:ecs:us-west-2:111122223333:task/shop/cd189a933e58",
+            "Family": "checkout",
+            "Revision": "7",
+            "AvailabilityZone": "us-west-2a",
+            "LaunchType": "FARGATE"
+        });
+        let attributes = to_map(ecs_attributes(&container, &task));
+        assert_eq!(attributes[CLOUD_PLATFORM], "aws_ecs");
+        assert_eq!(attributes[CLOUD_REGION], "us-west-2");
+        assert_eq!(attributes[CLOUD_ACCOUNT_ID], "111122223333");
+        assert_eq!(attributes[AWS_ECS_LAUNCHTYPE], "fargate");
+        assert_eq!(attributes[AWS_ECS_TASK_REVISION], "7");
+        assert_eq!(attributes[CONTAINER_NAME], "checkout");
+        assert_eq!(attributes[AWS_LOG_GROUP_NAMES], "/ecs/checkout");
+    }
+
+    #[test]
+    fn test_ec2_attributes() {
+        let identity = json!({
+            "accountId": "111122223333",
+            "region": "eu-west-1",
+            "availabilityZone": "eu-west-1b",
+            "instanceId": "i-0123456789abcdef0",
+            "instanceType": "m5.large",
+            "imageId": "ami-0abcdef1234567890"
+        });
+        let attributes = to_map(ec2_attributes(
+            &identity,
+            Some("ip-10-0-0-1.eu-west-1.compute.internal".into()),
+            true,
+        ));
+        assert_eq!(attributes[CLOUD_PROVIDER], "aws");
+        assert_eq!(attributes[CLOUD_PLATFORM], "aws_eks");
+        assert_eq!(attributes[CLOUD_REGION], "eu-west-1");
+        assert_eq!(attributes[HOST_ID], "i-0123456789abcdef0");
+        assert_eq!(
+            attributes[HOST_NAME],
+            "ip-10-0-0-1.eu-west-1.compute.internal"
+        );
+    }
+}
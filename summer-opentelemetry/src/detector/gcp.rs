@@ -0,0 +1,162 @@
+//! Detector of the Google Cloud environments: Cloud Run services, GKE pods and GCE instances.
+
+use super::metadata;
+use opentelemetry::KeyValue;
+use opentelemetry_sdk::resource::ResourceDetector;
+use opentelemetry_sdk::Resource;
+use opentelemetry_semantic_conventions::resource::{
+    CLOUD_ACCOUNT_ID, CLOUD_AVAILABILITY_ZONE, CLOUD_PLATFORM, CLOUD_PROVIDER, CLOUD_REGION,
+    FAAS_INSTANCE, FAAS_NAME, FAAS_VERSION, HOST_ID, HOST_NAME, HOST_TYPE, K8S_CLUSTER_NAME,
+};
+
+/// The metadata server, by address so that its name isn't resolved outside of Google Cloud.
+const METADATA_URL: &str = "http://169.254.169.254/computeMetadata/v1";
+
+/// Detects the `cloud.*`, `faas.*`, `host.*` and `k8s.cluster.name` attributes of the
+/// Google Cloud environment the application runs in, read from the metadata server.
+///
+/// * Cloud Run: when `K_SERVICE` is set, the service and revision are read from the
+///   environment, and the region and instance from the metadata server
+/// * GKE and GCE: the attributes of the instance are read from the metadata server,
+///   `cloud.platform` is `gcp_kubernetes_engine` inside Kubernetes
+///
+/// Attributes that can't be detected are omitted, so outside of Google Cloud the detected
+/// resource is empty.
+#[derive(Debug, Default)]
+pub struct GcpResourceDetector;
+
+impl ResourceDetector for GcpResourceDetector {
+    fn detect(&self) -> Resource {
+        let attributes = detect_attributes(
+            |path| {
+                let url = format!("{METADATA_URL}/{path}");
+                metadata::request("GET", &url, &[("Metadata-Flavor", "Google")])
+            },
+            |var| std::env::var(var).ok(),
+        );
+        Resource::builder_empty()
+            .with_attributes(attributes)
+            .build()
+    }
+}
+
+fn detect_attributes(
+    metadata: impl Fn(&str) -> Option<String>,
+    env: impl Fn(&str) -> Option<String>,
+) -> Vec<KeyValue> {
+    let metadata = |path: &str| {
+        metadata(path)
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    };
+    // e.g. `projects/123/zones/us-central1-a`
+    let last_segment = |path: &str| {
+        metadata(path).map(|value| value.rsplit('/').next().unwrap_or_default().to_string())
+    };
+    let Some(project_id) = metadata("project/project-id") else {
+        return Vec::new();
+    };
+    let mut attributes = vec![
+        KeyValue::new(CLOUD_PROVIDER, "gcp"),
+        KeyValue::new(CLOUD_ACCOUNT_ID, project_id),
+    ];
+    let mut push = |key: &'static str, value: Option<String>| {
+        if let Some(value) = value {
+            attributes.push(KeyValue::new(key, value));
+        }
+    };
+
+    if let Some(service) = env("K_SERVICE") {
+        push(CLOUD_PLATFORM, Some("gcp_cloud_run".into()));
+        push(FAAS_NAME, Some(service));
+        push(FAAS_VERSION, env("K_REVISION"));
+        push(FAAS_INSTANCE, metadata("instance/id"));
+        push(CLOUD_REGION, last_segment("instance/region"));
+        return attributes;
+    }
+
+    let platform = match env("KUBERNETES_SERVICE_HOST") {
+        Some(_) => {
+            push(
+                K8S_CLUSTER_NAME,
+                metadata("instance/attributes/cluster-name"),
+            );
+            "gcp_kubernetes_engine"
+        }
+        None => "gcp_compute_engine",
+    };
+    push(CLOUD_PLATFORM, Some(platform.into()));
+    let zone = last_segment("instance/zone");
+    // the region of the `us-central1-a` zone is `us-central1`
+    let region = zone
+        .as_deref()
+        .and_then(|zone| zone.rsplit_once('-'))
+        .map(|(region, _)| region.to_string());
+    push(CLOUD_AVAILABILITY_ZONE, zone);
+    push(CLOUD_REGION, region);
+    push(HOST_ID, metadata("instance/id"));
+    push(HOST_NAME, metadata("instance/name"));
+    push(HOST_TYPE, last_segment("instance/machine-type"));
+    attributes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    const METADATA: [(&str, &str); 6] = [
+        ("project/project-id", "shop-prod"),
+        ("instance/id", "4520031799277582377"),
+        ("instance/name", "gke-shop-default-pool-1"),
+        ("instance/region", "projects/123/regions/europe-west1"),
+        ("instance/zone", "projects/123/zones/europe-west1-b"),
+        (
+            "instance/machine-type",
+            "projects/123/machineTypes/e2-medium",
+        ),
+    ];
+
+    fn detect(vars: &[(&str, &str)]) -> HashMap<String, String> {
+        let metadata: HashMap<_, _> = METADATA
+            .into_iter()
+            .chain([("instance/attributes/cluster-name", "shop\n")])
+            .collect();
+        let vars: HashMap<_, _> = vars.iter().copied().collect();
+        detect_attributes(
+            |path| metadata.get(path).map(|value| value.to_string()),
+            |var| vars.get(var).map(|value| value.to_string()),
+        )
+        .into_iter()
+        .map(|kv| (kv.key.to_string(), kv.value.to_string()))
+        .collect()
+    }
+
+    #[test]
+    fn test_cloud_run_attributes() {
+        let attributes = detect(&[("K_SERVICE", "checkout"), ("K_REVISION", "checkout-00042")]);
+        assert_eq!(attributes[CLOUD_PROVIDER], "gcp");
+        assert_eq!(attributes[CLOUD_PLATFORM], "gcp_cloud_run");
+        assert_eq!(attributes[CLOUD_ACCOUNT_ID], "shop-prod");
+        assert_eq!(attributes[CLOUD_REGION], "europe-west1");
+        assert_eq!(attributes[FAAS_NAME], "checkout");
+        assert_eq!(attributes[FAAS_VERSION], "checkout-00042");
+        assert_eq!(attributes[FAAS_INSTANCE], "4520031799277582377");
+    }
+
+    #[test]
+    fn test_gke_attributes() {
+        let attributes = detect(&[("KUBERNETES_SERVICE_HOST", "10.0.0.1")]);
+        assert_eq!(attributes[CLOUD_PLATFORM], "gcp_kubernetes_engine");
+        assert_eq!(attributes[K8S_CLUSTER_NAME], "shop");
+        assert_eq!(attributes[CLOUD_AVAILABILITY_ZONE], "europe-west1-b");
+        assert_eq!(attributes[CLOUD_REGION], "europe-west1");
+        assert_eq!(attributes[HOST_TYPE], "e2-medium");
+    }
+
+    #[test]
+    fn test_outside_gcp() {
+        let attributes = detect_attributes(|_| None, |_| None);
+        assert!(attributes.is_empty());
+    }
+}
@@ -0,0 +1,39 @@
+//! Minimal client of the link-local metadata endpoints of the cloud providers.
+
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+/// Timeout of the connection and of each read and write, the metadata endpoints answer
+/// quickly from inside the cloud and aren't reachable outside of it.
+const TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Sends a plain HTTP request to a metadata endpoint, returning the body of a `200` response.
+///
+/// HTTP/1.0 is used so that the responses are never chunked. Any failure, e.g. outside of
+/// the cloud, returns `None`.
+pub(super) fn request(method: &str, url: &str, headers: &[(&str, &str)]) -> Option<String> {
+    let uri = url.parse::<http::Uri>().ok()?;
+    let host = uri.host()?;
+    let addr = (host, uri.port_u16().unwrap_or(80))
+        .to_socket_addrs()
+        .ok()?
+        .next()?;
+    let mut stream = TcpStream::connect_timeout(&addr, TIMEOUT).ok()?;
+    stream.set_read_timeout(Some(TIMEOUT)).ok()?;
+    stream.set_write_timeout(Some(TIMEOUT)).ok()?;
+
+    let path = uri.path_and_query().map_or("/", |path| path.as_str());
+    let mut request = format!("{method} {path} HTTP/1.0\r\nHost: {host}\r\nContent-Length: 0\r\n");
+    for (name, value) in headers {
+        request.push_str(&format!("{name}: {value}\r\n"));
+    }
+    request.push_str("\r\n");
+    stream.write_all(request.as_bytes()).ok()?;
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).ok()?;
+    let (head, body) = response.split_once("\r\n\r\n")?;
+    let status = head.split_whitespace().nth(1)?;
+    (status == "200").then(|| body.to_string())
+}
@@ -0,0 +1,716 @@
+//! Construction of the OTLP exporters shared by all signals.
+
+mod diagnostics;
+mod failover;
+#[cfg(any(feature = "grpc", feature = "http", feature = "http-json"))]
+mod headers;
+mod lazy;
+mod reload;
+mod retry;
+mod split;
+
+pub(crate) use diagnostics::{start as start_diagnostics, DiagnosticsExporter};
+pub(crate) use failover::FailoverExporter;
+#[cfg(any(feature = "http", feature = "http-json"))]
+pub(crate) use headers::HeadersHttpClient;
+#[cfg(feature = "grpc")]
+pub(crate) use headers::HeadersInterceptor;
+#[cfg(any(feature = "grpc", feature = "http", feature = "http-json"))]
+pub(crate) use headers::HeadersProvider;
+pub(crate) use lazy::LazyExporter;
+pub(crate) use reload::ReloadableExporters;
+pub(crate) use retry::{stop_retries, RetryExporter, RetryPolicy};
+#[cfg(any(feature = "http", feature = "http-json"))]
+pub(crate) use retry::{StatusHttpClient, TransientFailure};
+pub(crate) use split::SplitExporter;
+
+use crate::builder::PluginOptions;
+use crate::config::{ExporterKind, OpenTelemetryConfig, OtlpCompression, OtlpProtocol};
+#[cfg(any(feature = "gzip", feature = "zstd"))]
+use opentelemetry_otlp::Compression;
+use opentelemetry_otlp::WithExportConfig;
+use std::fmt::Display;
+use std::time::Duration;
+#[cfg(feature = "tls")]
+use tonic::transport::ClientTlsConfig;
+
+/// The telemetry signals exported by the plugin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Signal {
+    Traces,
+    Metrics,
+    Logs,
+}
+
+impl Signal {
+    fn endpoint_var(&self) -> &'static str {
+        match self {
+            Self::Traces => opentelemetry_otlp::OTEL_EXPORTER_OTLP_TRACES_ENDPOINT,
+            Self::Metrics => opentelemetry_otlp::OTEL_EXPORTER_OTLP_METRICS_ENDPOINT,
+            Self::Logs => opentelemetry_otlp::OTEL_EXPORTER_OTLP_LOGS_ENDPOINT,
+        }
+    }
+
+    fn timeout_var(&self) -> &'static str {
+        match self {
+            Self::Traces => opentelemetry_otlp::OTEL_EXPORTER_OTLP_TRACES_TIMEOUT,
+            Self::Metrics => opentelemetry_otlp::OTEL_EXPORTER_OTLP_METRICS_TIMEOUT,
+            Self::Logs => opentelemetry_otlp::OTEL_EXPORTER_OTLP_LOGS_TIMEOUT,
+        }
+    }
+
+    fn compression_var(&self) -> &'static str {
+        match self {
+            Self::Traces => opentelemetry_otlp::OTEL_EXPORTER_OTLP_TRACES_COMPRESSION,
+            Self::Metrics => opentelemetry_otlp::OTEL_EXPORTER_OTLP_METRICS_COMPRESSION,
+            Self::Logs => opentelemetry_otlp::OTEL_EXPORTER_OTLP_LOGS_COMPRESSION,
+        }
+    }
+
+    pub(crate) fn protocol_var(&self) -> &'static str {
+        match self {
+            Self::Traces => "OTEL_EXPORTER_OTLP_TRACES_PROTOCOL",
+            Self::Metrics => "OTEL_EXPORTER_OTLP_METRICS_PROTOCOL",
+            Self::Logs => "OTEL_EXPORTER_OTLP_LOGS_PROTOCOL",
+        }
+    }
+
+    /// Whether the endpoint of this signal is set by the env vars.
+    pub(crate) fn endpoint_from_env(&self) -> bool {
+        env_is_set(
+            self.endpoint_var(),
+            opentelemetry_otlp::OTEL_EXPORTER_OTLP_ENDPOINT,
+        )
+    }
+
+    /// Whether the compression of this signal is set by the env vars.
+    pub(crate) fn compression_from_env(&self) -> bool {
+        env_is_set(
+            self.compression_var(),
+            opentelemetry_otlp::OTEL_EXPORTER_OTLP_COMPRESSION,
+        )
+    }
+
+    /// Whether the export timeout of this signal is set by the env vars.
+    pub(crate) fn timeout_from_env(&self) -> bool {
+        env_is_set(
+            self.timeout_var(),
+            opentelemetry_otlp::OTEL_EXPORTER_OTLP_TIMEOUT,
+        )
+    }
+
+    fn path(&self) -> &'static str {
+        match self {
+            Self::Traces => "v1/traces",
+            Self::Metrics => "v1/metrics",
+            Self::Logs => "v1/logs",
+        }
+    }
+
+    /// The endpoint provided to the exporter of this signal: the endpoint set in code, then the
+    /// signal endpoint and the `endpoint` of the config unless the endpoint env vars are set.
+    /// The signal endpoint is used as is, the base endpoint is used as is for gRPC and with
+    /// the signal path appended for HTTP, like the OTLP exporter does for the env vars.
+    pub(crate) fn provided_endpoint(
+        &self,
+        protocol: OtlpProtocol,
+        config: &OpenTelemetryConfig,
+        options: &PluginOptions,
+    ) -> Option<String> {
+        let signal_endpoint = match self {
+            Self::Traces => &config.traces_endpoint,
+            Self::Metrics => &config.metrics_endpoint,
+            Self::Logs => &config.logs_endpoint,
+        };
+        let endpoint = match (&options.exporter_endpoint, signal_endpoint) {
+            (Some(endpoint), _) => endpoint,
+            (None, _) if self.endpoint_from_env() => return None,
+            (None, Some(signal_endpoint)) => return Some(signal_endpoint.clone()),
+            (None, None) => config.endpoint.as_ref()?,
+        };
+        Some(match protocol {
+            OtlpProtocol::Grpc => endpoint.to_string(),
+            OtlpProtocol::HttpProtobuf | OtlpProtocol::HttpJson => {
+                format!("{}/{}", endpoint.trim_end_matches('/'), self.path())
+            }
+        })
+    }
+
+    /// The endpoint the exporter of this signal sends to, resolved the same way as the OTLP exporter does.
+    /// It is only used to describe the exporter in error messages.
+    pub(crate) fn endpoint(
+        &self,
+        protocol: OtlpProtocol,
+        config: &OpenTelemetryConfig,
+        options: &PluginOptions,
+    ) -> String {
+        if let Some(endpoint) = self.provided_endpoint(protocol, config, options) {
+            return endpoint;
+        }
+        std::env::var(self.endpoint_var())
+            .or_else(|_| std::env::var(opentelemetry_otlp::OTEL_EXPORTER_OTLP_ENDPOINT))
+            .unwrap_or_else(|_| match protocol {
+                OtlpProtocol::Grpc => "http://localhost:4317".to_string(),
+                OtlpProtocol::HttpProtobuf | OtlpProtocol::HttpJson => {
+                    "http://localhost:4318".to_string()
+                }
+            })
+    }
+}
+
+fn env_is_set(signal_var: &str, var: &str) -> bool {
+    std::env::var_os(signal_var).is_some() || std::env::var_os(var).is_some()
+}
+
+impl Display for Signal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Traces => f.write_str("traces"),
+            Self::Metrics => f.write_str("metrics"),
+            Self::Logs => f.write_str("logs"),
+        }
+    }
+}
+
+/// Applies the endpoint set in code to a transport specific exporter builder,
+/// leaving the `OTEL_EXPORTER_OTLP_*ENDPOINT` resolution of the SDK in place when unset.
+pub(crate) fn with_endpoint<B: WithExportConfig>(builder: B, endpoint: Option<String>) -> B {
+    match endpoint {
+        Some(endpoint) => builder.with_endpoint(endpoint),
+        None => builder,
+    }
+}
+
+/// Applies the configured export timeout to a transport specific exporter builder,
+/// leaving the `OTEL_EXPORTER_OTLP_*TIMEOUT` resolution of the SDK in place when unset.
+pub(crate) fn with_timeout<B: WithExportConfig>(builder: B, timeout: Option<Duration>) -> B {
+    match timeout {
+        Some(timeout) => builder.with_timeout(timeout),
+        None => builder,
+    }
+}
+
+/// The compression of the exports of a signal, `None` to leave the
+/// `OTEL_EXPORTER_OTLP_*COMPRESSION` resolution of the SDK in place.
+fn compression(
+    signal: Signal,
+    config: &OpenTelemetryConfig,
+) -> anyhow::Result<Option<opentelemetry_otlp::Compression>> {
+    match config.compression(signal) {
+        None | Some(OtlpCompression::None) => Ok(None),
+        #[cfg(feature = "gzip")]
+        Some(OtlpCompression::Gzip) => Ok(Some(Compression::Gzip)),
+        #[cfg(feature = "zstd")]
+        Some(OtlpCompression::Zstd) => Ok(Some(Compression::Zstd)),
+        #[allow(unreachable_patterns)]
+        Some(compression) => {
+            anyhow::bail!(
+                "{signal} compression `{compression}` requires the `{compression}` feature"
+            )
+        }
+    }
+}
+
+/// Applies the headers, `User-Agent` and compression of the config, and the headers provider
+/// of the plugin options, to an HTTP exporter builder.
+///
+/// The requests are sent with the blocking client the OTLP HTTP exporters use by default,
+/// recording whether they failed with a transient error in the returned [`TransientFailure`].
+#[cfg(any(feature = "http", feature = "http-json"))]
+pub(crate) fn with_http_config<B: opentelemetry_otlp::WithHttpConfig>(
+    builder: B,
+    signal: Signal,
+    config: &OpenTelemetryConfig,
+    options: &PluginOptions,
+) -> anyhow::Result<(B, TransientFailure)> {
+    let endpoint = signal.endpoint(config.protocol(signal), config, options);
+    if endpoint.starts_with("unix://") {
+        anyhow::bail!(
+            "the {signal} endpoint `{endpoint}` is a Unix domain socket, only supported by the \
+             `grpc` protocol"
+        );
+    }
+    if config.tls_ca_cert_path.is_some()
+        || config.tls_client_cert_path.is_some()
+        || config.tls_client_key_path.is_some()
+    {
+        anyhow::bail!(
+            "the TLS settings of the {signal} exporter are only supported by the `grpc` protocol"
+        );
+    }
+    let mut headers = config.headers.clone().unwrap_or_default();
+    if let Some(user_agent) = &config.user_agent {
+        headers.insert(http::header::USER_AGENT.to_string(), user_agent.clone());
+    }
+    let builder = builder.with_headers(headers);
+    let mut client: std::sync::Arc<dyn opentelemetry_http::HttpClient> =
+        std::sync::Arc::new(blocking_http_client(signal, config)?);
+    if let Some(provider) = &options.headers_provider {
+        client = std::sync::Arc::new(HeadersHttpClient::new(client, provider.clone()));
+    }
+    let failure = TransientFailure::default();
+    let builder = builder.with_http_client(StatusHttpClient::new(client, failure.clone()));
+    let builder = match compression(signal, config)? {
+        Some(compression) => builder.with_compression(compression),
+        None => builder,
+    };
+    Ok((builder, failure))
+}
+
+/// The blocking client the OTLP HTTP exporters use by default, with the export timeout.
+#[cfg(any(feature = "http", feature = "http-json"))]
+fn blocking_http_client(
+    signal: Signal,
+    config: &OpenTelemetryConfig,
+) -> anyhow::Result<reqwest::blocking::Client> {
+    use opentelemetry_otlp::ExporterBuildError;
+
+    let timeout = export_timeout(signal, config);
+    // reqwest can't create a blocking client on a thread of the Tokio runtime
+    let client = std::thread::spawn(move || {
+        reqwest::blocking::Client::builder()
+            .timeout(timeout)
+            .build()
+    })
+    .join()
+    .map_err(|_| ExporterBuildError::ThreadSpawnFailed)?
+    .map_err(|e| ExporterBuildError::InternalFailure(e.to_string()))?;
+    Ok(client)
+}
+
+/// Applies the headers, compression and TLS settings of the config, and the headers provider
+/// of the plugin options, to a tonic exporter builder.
+#[cfg(feature = "grpc")]
+pub(crate) fn with_tonic_config<B: opentelemetry_otlp::WithTonicConfig>(
+    builder: B,
+    signal: Signal,
+    config: &OpenTelemetryConfig,
+    options: &PluginOptions,
+) -> anyhow::Result<B> {
+    let builder = match &config.headers {
+        Some(headers) => builder.with_metadata(metadata(headers)?),
+        None => builder,
+    };
+    let builder = match &options.headers_provider {
+        Some(provider) => builder.with_interceptor(HeadersInterceptor::new(provider.clone())),
+        None => builder,
+    };
+    let builder = match compression(signal, config)? {
+        Some(compression) => builder.with_compression(compression),
+        None => builder,
+    };
+    #[cfg(not(feature = "tls"))]
+    if config.tls_ca_cert_path.is_some()
+        || config.tls_client_cert_path.is_some()
+        || config.tls_client_key_path.is_some()
+    {
+        anyhow::bail!("gRPC TLS requires the `tls` feature");
+    }
+    if let Some(channel) = grpc_channel(signal, config, options)? {
+        return Ok(builder.with_channel(channel));
+    }
+    #[cfg(feature = "tls")]
+    if let Some(tls_config) = tls_config(config)? {
+        return Ok(builder.with_tls_config(tls_config));
+    }
+    Ok(builder)
+}
+
+/// The channel of the tonic exporter, built like the exporter builds its own channel when the
+/// builder can't: to send the HTTP/2 keepalive pings of the config, or to connect to the
+/// `unix://` endpoint of a collector listening on a Unix domain socket.
+#[cfg(feature = "grpc")]
+fn grpc_channel(
+    signal: Signal,
+    config: &OpenTelemetryConfig,
+    options: &PluginOptions,
+) -> anyhow::Result<Option<tonic::transport::Channel>> {
+    use anyhow::Context;
+
+    if config.grpc_keepalive_interval_ms.is_none()
+        && (config.grpc_keepalive_timeout_ms.is_some() || config.grpc_keepalive_while_idle)
+    {
+        anyhow::bail!(
+            "grpc_keepalive_timeout_ms and grpc_keepalive_while_idle require \
+             grpc_keepalive_interval_ms"
+        );
+    }
+    let uri = signal.endpoint(OtlpProtocol::Grpc, config, options);
+    let socket = unix_socket_path(&uri).map(str::to_string);
+    if config.grpc_keepalive_interval_ms.is_none() && socket.is_none() {
+        return Ok(None);
+    }
+    // the authority of the requests sent over a Unix domain socket
+    let endpoint_uri = match socket {
+        Some(_) => "http://localhost".to_string(),
+        None => uri.clone(),
+    };
+    let endpoint = tonic::transport::Endpoint::from_shared(endpoint_uri)
+        .with_context(|| format!("invalid {signal} endpoint `{uri}`"))?
+        .timeout(export_timeout(signal, config));
+    let endpoint = match config.grpc_keepalive_interval_ms {
+        Some(interval) => endpoint
+            .http2_keep_alive_interval(Duration::from_millis(interval))
+            .keep_alive_while_idle(config.grpc_keepalive_while_idle),
+        None => endpoint,
+    };
+    let endpoint = match config.grpc_keepalive_timeout_ms {
+        Some(timeout) => endpoint.keep_alive_timeout(Duration::from_millis(timeout)),
+        None => endpoint,
+    };
+    #[cfg(feature = "tls")]
+    let endpoint = match tls_config(config)? {
+        Some(tls_config) => endpoint
+            .tls_config(tls_config)
+            .with_context(|| format!("invalid TLS config of the {signal} endpoint `{uri}`"))?,
+        None => endpoint,
+    };
+    match socket {
+        Some(path) => unix_socket_channel(endpoint, path),
+        None => Ok(Some(endpoint.connect_lazy())),
+    }
+}
+
+/// The path of the Unix domain socket of a `unix://` endpoint, e.g. `/var/run/otel.sock` for
+/// `unix:///var/run/otel.sock`.
+#[cfg(feature = "grpc")]
+fn unix_socket_path(endpoint: &str) -> Option<&str> {
+    endpoint
+        .strip_prefix("unix://")
+        .filter(|path| !path.is_empty())
+}
+
+/// The channel connecting lazily to the Unix domain socket at `path`.
+#[cfg(all(feature = "grpc", unix))]
+fn unix_socket_channel(
+    endpoint: tonic::transport::Endpoint,
+    path: String,
+) -> anyhow::Result<Option<tonic::transport::Channel>> {
+    let connector = tower::service_fn(move |_: http::Uri| {
+        let path = path.clone();
+        async move {
+            let stream = tokio::net::UnixStream::connect(path).await?;
+            Ok::<_, std::io::Error>(hyper_util::rt::TokioIo::new(stream))
+        }
+    });
+    Ok(Some(endpoint.connect_with_connector_lazy(connector)))
+}
+
+#[cfg(all(feature = "grpc", not(unix)))]
+fn unix_socket_channel(
+    _endpoint: tonic::transport::Endpoint,
+    path: String,
+) -> anyhow::Result<Option<tonic::transport::Channel>> {
+    anyhow::bail!("the Unix domain socket endpoint `unix://{path}` is only supported on Unix")
+}
+
+/// The export timeout of the signal, resolved like the OTLP exporters do.
+#[cfg(any(feature = "grpc", feature = "http", feature = "http-json"))]
+fn export_timeout(signal: Signal, config: &OpenTelemetryConfig) -> Duration {
+    let from_env = |var| {
+        std::env::var(var)
+            .ok()
+            .and_then(|timeout| timeout.parse().ok())
+            .map(Duration::from_millis)
+    };
+    config
+        .export_timeout(signal)
+        .or_else(|| from_env(signal.timeout_var()))
+        .or_else(|| from_env(opentelemetry_otlp::OTEL_EXPORTER_OTLP_TIMEOUT))
+        .unwrap_or(opentelemetry_otlp::OTEL_EXPORTER_OTLP_TIMEOUT_DEFAULT)
+}
+
+/// The gRPC metadata of the configured headers.
+#[cfg(feature = "grpc")]
+fn metadata(
+    headers: &std::collections::HashMap<String, String>,
+) -> anyhow::Result<tonic::metadata::MetadataMap> {
+    use anyhow::Context;
+
+    let mut metadata = tonic::metadata::MetadataMap::new();
+    for (key, value) in headers {
+        let key = key
+            .parse::<tonic::metadata::MetadataKey<tonic::metadata::Ascii>>()
+            .with_context(|| format!("invalid header name `{key}`"))?;
+        let value = value
+            .parse()
+            .with_context(|| format!("invalid value of header `{key}`"))?;
+        metadata.insert(key, value);
+    }
+    Ok(metadata)
+}
+
+/// The TLS config of the tonic exporters: server authentication with the configured CA,
+/// and mutual TLS when the client certificate and key are configured too.
+///
+/// Without the `tls-rustls` feature no root certificates are trusted by default, so the CA is
+/// required for mutual TLS as well. With it, the system roots are trusted when no CA is set.
+#[cfg(feature = "tls")]
+fn tls_config(config: &OpenTelemetryConfig) -> anyhow::Result<Option<ClientTlsConfig>> {
+    use anyhow::Context;
+    use tonic::transport::{Certificate, Identity};
+
+    let read = |kind: &str, path: &str| {
+        std::fs::read(path).with_context(|| format!("read TLS {kind} `{path}` failed"))
+    };
+    let mut tls_config = None;
+    if let Some(ca_cert_path) = &config.tls_ca_cert_path {
+        let ca_cert = read("CA certificate", ca_cert_path)?;
+        tls_config = Some(ClientTlsConfig::new().ca_certificate(Certificate::from_pem(ca_cert)));
+    }
+    #[cfg(feature = "tls-rustls")]
+    let mut tls_config = tls_config.or_else(|| Some(ClientTlsConfig::new().with_native_roots()));
+    match (&config.tls_client_cert_path, &config.tls_client_key_path) {
+        (Some(_), Some(_)) if tls_config.is_none() => {
+            anyhow::bail!("tls_ca_cert_path must be set to verify the collector with mutual TLS")
+        }
+        (Some(cert_path), Some(key_path)) => {
+            let cert = read("client certificate", cert_path)?;
+            let key = read("client key", key_path)?;
+            let identity = Identity::from_pem(cert, key);
+            tls_config = Some(tls_config.unwrap_or_default().identity(identity));
+        }
+        (None, None) => {}
+        _ => anyhow::bail!("tls_client_cert_path and tls_client_key_path must be set together"),
+    }
+    Ok(tls_config)
+}
+
+/// Builds an OTLP exporter from a signal specific exporter builder
+/// (e.g. [`SpanExporter::builder()`](opentelemetry_otlp::SpanExporter::builder))
+/// with the transport and options of the [`OpenTelemetryConfig`] and the plugin options set in code.
+///
+/// Build errors are reported together with the signal, endpoint and protocol.
+macro_rules! build_otlp_exporter {
+    ($signal:expr, $builder:expr, $config:expr, $options:expr) => {{
+        #[allow(unused_imports)]
+        use opentelemetry_otlp::WithExportConfig;
+        use $crate::exporter::{with_endpoint, with_timeout};
+        let signal: $crate::exporter::Signal = $signal;
+        let config: &$crate::config::OpenTelemetryConfig = $config;
+        let options: &$crate::builder::PluginOptions = $options;
+        let protocol = config.protocol(signal);
+        let timeout = config.export_timeout(signal);
+        let endpoint = signal.provided_endpoint(protocol, config, options);
+        // the failures of the HTTP exporters are classified by their client
+        let (exporter, failure) = match protocol {
+            #[cfg(feature = "grpc")]
+            $crate::config::OtlpProtocol::Grpc => {
+                let builder = $crate::exporter::with_tonic_config(
+                    $builder.with_tonic(),
+                    signal,
+                    config,
+                    options,
+                )?;
+                (
+                    with_timeout(with_endpoint(builder, endpoint), timeout).build(),
+                    None,
+                )
+            }
+            #[cfg(feature = "http")]
+            $crate::config::OtlpProtocol::HttpProtobuf => {
+                let (builder, failure) = $crate::exporter::with_http_config(
+                    $builder.with_http(),
+                    signal,
+                    config,
+                    options,
+                )?;
+                let exporter = with_timeout(with_endpoint(builder, endpoint), timeout)
+                    .with_protocol(opentelemetry_otlp::Protocol::HttpBinary)
+                    .build();
+                (exporter, Some(failure))
+            }
+            #[cfg(feature = "http-json")]
+            $crate::config::OtlpProtocol::HttpJson => {
+                let (builder, failure) = $crate::exporter::with_http_config(
+                    $builder.with_http(),
+                    signal,
+                    config,
+                    options,
+                )?;
+                let exporter = with_timeout(with_endpoint(builder, endpoint), timeout)
+                    .with_protocol(opentelemetry_otlp::Protocol::HttpJson)
+                    .build();
+                (exporter, Some(failure))
+            }
+            #[allow(unreachable_patterns)]
+            protocol => {
+                return Err(anyhow::anyhow!(
+                    "OTLP protocol `{protocol}` requires the corresponding feature"
+                )
+                .into())
+            }
+        };
+        let stats = config.exporter_diagnostics.then(|| signal.export_stats());
+        let exporter = exporter.map(|exporter| {
+            let policy = config.retry_policy(signal);
+            let exporter = $crate::exporter::RetryExporter::new(exporter, policy, failure);
+            let exporter = $crate::exporter::DiagnosticsExporter::new(exporter, stats);
+            $crate::exporter::SplitExporter::new(
+                exporter,
+                config
+                    .max_export_batch_bytes
+                    .map(std::num::NonZeroUsize::get),
+            )
+        });
+        anyhow::Context::with_context(exporter, || {
+            format!(
+                "build {signal} exporter failed (endpoint: {}, protocol: {protocol})",
+                signal.endpoint(protocol, config, options)
+            )
+        })
+    }};
+}
+
+pub(crate) use build_otlp_exporter;
+
+/// Whether `error` comes from the transport of an OTLP exporter, e.g. its HTTP client can't be
+/// created, rather than from an invalid config.
+pub(crate) fn is_transport_error(error: &summer::error::AppError) -> bool {
+    use opentelemetry_otlp::ExporterBuildError;
+    let summer::error::AppError::OtherError(error) = error else {
+        return false;
+    };
+    error.chain().any(|cause| {
+        matches!(
+            cause.downcast_ref::<ExporterBuildError>(),
+            Some(
+                ExporterBuildError::ThreadSpawnFailed
+                    | ExporterBuildError::NoHttpClient
+                    | ExporterBuildError::InternalFailure(_)
+            )
+        )
+    })
+}
+
+/// The error returned when the configured exporter of a signal isn't compiled in
+/// or doesn't support the signal.
+pub(crate) fn unsupported_exporter(signal: Signal, exporter: ExporterKind) -> anyhow::Error {
+    match exporter {
+        ExporterKind::Prometheus if signal != Signal::Metrics => {
+            anyhow::anyhow!("{signal} exporter `{exporter}` is only supported for metrics")
+        }
+        ExporterKind::Logfmt if signal != Signal::Logs => {
+            anyhow::anyhow!("{signal} exporter `{exporter}` is only supported for logs")
+        }
+        ExporterKind::InMemory => {
+            anyhow::anyhow!("{signal} exporter `{exporter}` requires the `testing` feature")
+        }
+        _ => anyhow::anyhow!("{signal} exporter `{exporter}` requires the `{exporter}` feature"),
+    }
+}
+
+#[cfg(all(test, feature = "grpc"))]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[cfg(all(feature = "tls", not(feature = "tls-rustls")))]
+    #[test]
+    fn test_tls_config_requires_ca_for_mutual_tls() {
+        let config = OpenTelemetryConfig::deserialize(toml::toml! {
+            tls_client_cert_path = "client.pem"
+            tls_client_key_path = "client.key"
+        })
+        .expect("config deserialize failed");
+        let error = tls_config(&config).expect_err("mutual TLS without CA must fail");
+        assert!(error.to_string().contains("tls_ca_cert_path"));
+    }
+
+    #[test]
+    fn test_keepalive_channel() {
+        let options = PluginOptions::default();
+        let config = OpenTelemetryConfig::deserialize(toml::toml! {
+            grpc_keepalive_while_idle = true
+        })
+        .expect("config deserialize failed");
+        let error = grpc_channel(Signal::Traces, &config, &options)
+            .expect_err("keepalive without interval must fail");
+        assert!(error.to_string().contains("grpc_keepalive_interval_ms"));
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("build runtime failed");
+        let _guard = runtime.enter();
+        let config = OpenTelemetryConfig::deserialize(toml::toml! {
+            endpoint = "http://collector:4317"
+            grpc_keepalive_interval_ms = 30000
+            grpc_keepalive_timeout_ms = 5000
+            grpc_keepalive_while_idle = true
+        })
+        .expect("config deserialize failed");
+        let channel = grpc_channel(Signal::Traces, &config, &options);
+        assert!(matches!(channel, Ok(Some(_))));
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn test_http_tls_rejected() {
+        let config = OpenTelemetryConfig::deserialize(toml::toml! {
+            protocol = "http/protobuf"
+            tls_ca_cert_path = "ca.pem"
+        })
+        .expect("config deserialize failed");
+        let builder = opentelemetry_otlp::SpanExporter::builder().with_http();
+        let error = with_http_config(builder, Signal::Traces, &config, &PluginOptions::default())
+            .expect_err("TLS with the HTTP protocol must fail");
+        assert!(error
+            .to_string()
+            .contains("only supported by the `grpc` protocol"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unix_socket_endpoint() {
+        use opentelemetry::trace::{Tracer, TracerProvider};
+        use opentelemetry_sdk::trace::SpanExporter as _;
+        use opentelemetry_sdk::trace::{InMemorySpanExporter, SdkTracerProvider};
+
+        assert_eq!(
+            unix_socket_path("unix:///var/run/otel.sock"),
+            Some("/var/run/otel.sock")
+        );
+        assert_eq!(unix_socket_path("http://localhost:4317"), None);
+
+        let path = std::env::temp_dir().join(format!("otel-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = std::os::unix::net::UnixListener::bind(&path).expect("bind failed");
+        let accepted = std::thread::spawn(move || listener.accept().is_ok());
+
+        let spans = InMemorySpanExporter::default();
+        let provider = SdkTracerProvider::builder()
+            .with_simple_exporter(spans.clone())
+            .build();
+        provider.tracer("test").in_span("checkout", |_| {});
+        let batch = spans.get_finished_spans().unwrap();
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("build runtime failed");
+        let _guard = runtime.enter();
+        let table = format!(
+            "endpoint = \"unix://{}\"\nprotocol = \"grpc\"",
+            path.display()
+        );
+        let config = OpenTelemetryConfig::deserialize(
+            toml::from_str::<toml::Table>(&table).expect("parse failed"),
+        )
+        .expect("config deserialize failed");
+        let options = PluginOptions::default();
+        let build = || {
+            build_otlp_exporter!(
+                Signal::Traces,
+                opentelemetry_otlp::SpanExporter::builder(),
+                &config,
+                &options
+            )
+        };
+        let exporter = build().expect("build exporter failed");
+        // the socket isn't a collector, only the connection matters
+        let _ = runtime.block_on(exporter.export(batch));
+        assert!(accepted.join().unwrap());
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,235 @@
+//! Health of the OTLP exports printed periodically when `exporter_diagnostics = true`, to
+//! notice the telemetry that silently stops reaching the collector without a backend.
+
+use super::Signal;
+use opentelemetry_sdk::error::OTelSdkResult;
+use opentelemetry_sdk::logs::{LogBatch, LogExporter};
+use opentelemetry_sdk::metrics::data::ResourceMetrics;
+use opentelemetry_sdk::metrics::exporter::PushMetricExporter;
+use opentelemetry_sdk::metrics::Temporality;
+use opentelemetry_sdk::trace::{SpanData, SpanExporter};
+use opentelemetry_sdk::Resource;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::task::AbortHandle;
+
+static TRACES_STATS: ExportStats = ExportStats::new();
+static METRICS_STATS: ExportStats = ExportStats::new();
+static LOGS_STATS: ExportStats = ExportStats::new();
+
+impl Signal {
+    /// The outcomes of the exports of this signal, shared by all its exporters.
+    pub(crate) fn export_stats(&self) -> &'static ExportStats {
+        match self {
+            Self::Traces => &TRACES_STATS,
+            Self::Metrics => &METRICS_STATS,
+            Self::Logs => &LOGS_STATS,
+        }
+    }
+}
+
+/// The outcomes of the exports of a signal, once retried.
+#[derive(Debug)]
+pub(crate) struct ExportStats {
+    succeeded: AtomicU64,
+    failed: AtomicU64,
+    last_success: Mutex<Option<Instant>>,
+    last_error: Mutex<Option<String>>,
+}
+
+impl ExportStats {
+    pub(crate) const fn new() -> Self {
+        Self {
+            succeeded: AtomicU64::new(0),
+            failed: AtomicU64::new(0),
+            last_success: Mutex::new(None),
+            last_error: Mutex::new(None),
+        }
+    }
+
+    fn record(&self, result: &OTelSdkResult) {
+        match result {
+            Ok(()) => {
+                self.succeeded.fetch_add(1, Ordering::Relaxed);
+                if let Ok(mut last_success) = self.last_success.lock() {
+                    *last_success = Some(Instant::now());
+                }
+            }
+            Err(e) => {
+                self.failed.fetch_add(1, Ordering::Relaxed);
+                if let Ok(mut last_error) = self.last_error.lock() {
+                    *last_error = Some(e.to_string());
+                }
+            }
+        }
+    }
+
+    /// e.g. `traces: 12 exports succeeded, 1 failed, last successful export 3s ago, last error: ...`
+    fn summary(&self, signal: Signal, now: Instant) -> String {
+        let mut summary = format!(
+            "{signal}: {} exports succeeded, {} failed",
+            self.succeeded.load(Ordering::Relaxed),
+            self.failed.load(Ordering::Relaxed)
+        );
+        match *self.last_success.lock().unwrap_or_else(|e| e.into_inner()) {
+            Some(last_success) => {
+                let elapsed = now.saturating_duration_since(last_success).as_secs();
+                let _ = write!(summary, ", last successful export {elapsed}s ago");
+            }
+            None => summary.push_str(", no successful export yet"),
+        }
+        if let Some(last_error) = &*self.last_error.lock().unwrap_or_else(|e| e.into_inner()) {
+            let _ = write!(summary, ", last error: {last_error}");
+        }
+        summary
+    }
+}
+
+/// Exporter recording the outcome of each export of the inner exporter in the [`ExportStats`]
+/// of its signal, when the diagnostics are enabled.
+#[derive(Debug)]
+pub(crate) struct DiagnosticsExporter<E> {
+    inner: E,
+    stats: Option<&'static ExportStats>,
+}
+
+impl<E> DiagnosticsExporter<E> {
+    pub(crate) fn new(inner: E, stats: Option<&'static ExportStats>) -> Self {
+        Self { inner, stats }
+    }
+
+    fn record(&self, result: OTelSdkResult) -> OTelSdkResult {
+        if let Some(stats) = self.stats {
+            stats.record(&result);
+        }
+        result
+    }
+}
+
+impl<E: SpanExporter> SpanExporter for DiagnosticsExporter<E> {
+    async fn export(&self, batch: Vec<SpanData>) -> OTelSdkResult {
+        self.record(self.inner.export(batch).await)
+    }
+
+    fn shutdown_with_timeout(&mut self, timeout: Duration) -> OTelSdkResult {
+        self.inner.shutdown_with_timeout(timeout)
+    }
+
+    fn shutdown(&mut self) -> OTelSdkResult {
+        self.inner.shutdown()
+    }
+
+    fn force_flush(&mut self) -> OTelSdkResult {
+        self.inner.force_flush()
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.inner.set_resource(resource)
+    }
+}
+
+impl<E: LogExporter> LogExporter for DiagnosticsExporter<E> {
+    async fn export(&self, batch: LogBatch<'_>) -> OTelSdkResult {
+        self.record(self.inner.export(batch).await)
+    }
+
+    fn shutdown_with_timeout(&self, timeout: Duration) -> OTelSdkResult {
+        self.inner.shutdown_with_timeout(timeout)
+    }
+
+    fn shutdown(&self) -> OTelSdkResult {
+        self.inner.shutdown()
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.inner.set_resource(resource)
+    }
+}
+
+impl<E: PushMetricExporter> PushMetricExporter for DiagnosticsExporter<E> {
+    async fn export(&self, metrics: &ResourceMetrics) -> OTelSdkResult {
+        self.record(self.inner.export(metrics).await)
+    }
+
+    fn force_flush(&self) -> OTelSdkResult {
+        self.inner.force_flush()
+    }
+
+    fn shutdown_with_timeout(&self, timeout: Duration) -> OTelSdkResult {
+        self.inner.shutdown_with_timeout(timeout)
+    }
+
+    fn shutdown(&self) -> OTelSdkResult {
+        self.inner.shutdown()
+    }
+
+    fn temporality(&self) -> Temporality {
+        self.inner.temporality()
+    }
+}
+
+/// Spawns the task printing the summary of the exports of the signals to stdout every
+/// `interval`, rather than through the log exporter whose exports may be failing too.
+///
+/// The returned handle aborts the task.
+pub(crate) fn start(signals: Vec<Signal>, interval: Duration) -> AbortHandle {
+    let task = tokio::spawn(async move {
+        let mut interval = tokio::time::interval(interval);
+        // the first tick completes immediately, before any export
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            let now = Instant::now();
+            for signal in &signals {
+                println!(
+                    "opentelemetry exporter diagnostics: {}",
+                    signal.export_stats().summary(*signal, now)
+                );
+            }
+        }
+    });
+    task.abort_handle()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::FutureExt;
+    use opentelemetry_sdk::error::OTelSdkError;
+    use opentelemetry_sdk::trace::InMemorySpanExporter;
+
+    #[derive(Debug)]
+    struct FailingExporter;
+
+    impl SpanExporter for FailingExporter {
+        async fn export(&self, _batch: Vec<SpanData>) -> OTelSdkResult {
+            Err(OTelSdkError::InternalFailure("unavailable".into()))
+        }
+    }
+
+    #[test]
+    fn test_export_stats() {
+        let stats: &'static ExportStats = Box::leak(Box::new(ExportStats::new()));
+        let now = Instant::now();
+        assert_eq!(
+            stats.summary(Signal::Traces, now),
+            "traces: 0 exports succeeded, 0 failed, no successful export yet"
+        );
+
+        let exporter = DiagnosticsExporter::new(InMemorySpanExporter::default(), Some(stats));
+        for _ in 0..2 {
+            assert!(exporter.export(vec![]).now_or_never().unwrap().is_ok());
+        }
+        let exporter = DiagnosticsExporter::new(FailingExporter, Some(stats));
+        assert!(exporter.export(vec![]).now_or_never().unwrap().is_err());
+
+        let later = Instant::now() + Duration::from_secs(3);
+        assert_eq!(
+            stats.summary(Signal::Traces, later),
+            "traces: 2 exports succeeded, 1 failed, last successful export 3s ago, \
+             last error: Operation failed: unavailable"
+        );
+    }
+}
@@ -0,0 +1,113 @@
+//! Failover between the exporters of several endpoints.
+
+use opentelemetry_sdk::error::{OTelSdkError, OTelSdkResult};
+use opentelemetry_sdk::trace::{SpanData, SpanExporter};
+use opentelemetry_sdk::Resource;
+use std::time::Duration;
+
+/// Exporter sending each batch to the first exporter, and to the next exporters in order
+/// only when the previous ones fail.
+#[derive(Debug)]
+pub(crate) struct FailoverExporter<E> {
+    exporters: Vec<E>,
+}
+
+impl<E> FailoverExporter<E> {
+    pub(crate) fn new(exporters: Vec<E>) -> Self {
+        Self { exporters }
+    }
+}
+
+/// Applies `f` to all exporters, returning the first error.
+fn for_all<E>(exporters: &mut [E], f: impl Fn(&mut E) -> OTelSdkResult) -> OTelSdkResult {
+    let results = exporters.iter_mut().map(f).collect::<Vec<_>>();
+    results.into_iter().collect()
+}
+
+impl<E: SpanExporter> SpanExporter for FailoverExporter<E> {
+    async fn export(&self, batch: Vec<SpanData>) -> OTelSdkResult {
+        let mut result = Err(OTelSdkError::InternalFailure(
+            "no exporter to fail over to".into(),
+        ));
+        for exporter in &self.exporters {
+            result = exporter.export(batch.clone()).await;
+            if result.is_ok() {
+                break;
+            }
+        }
+        result
+    }
+
+    fn shutdown_with_timeout(&mut self, timeout: Duration) -> OTelSdkResult {
+        for_all(&mut self.exporters, |exporter| {
+            exporter.shutdown_with_timeout(timeout)
+        })
+    }
+
+    fn shutdown(&mut self) -> OTelSdkResult {
+        for_all(&mut self.exporters, |exporter| exporter.shutdown())
+    }
+
+    fn force_flush(&mut self) -> OTelSdkResult {
+        for_all(&mut self.exporters, |exporter| exporter.force_flush())
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.exporters
+            .iter_mut()
+            .for_each(|exporter| exporter.set_resource(resource));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::FutureExt;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+
+    #[derive(Debug)]
+    struct CountingExporter {
+        exports: Arc<AtomicU32>,
+        fail: bool,
+    }
+
+    impl SpanExporter for CountingExporter {
+        async fn export(&self, _batch: Vec<SpanData>) -> OTelSdkResult {
+            self.exports.fetch_add(1, Ordering::SeqCst);
+            if self.fail {
+                Err(OTelSdkError::InternalFailure("unavailable".into()))
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    fn exporters(fail: [bool; 2]) -> (FailoverExporter<CountingExporter>, [Arc<AtomicU32>; 2]) {
+        let exports = [Arc::new(AtomicU32::new(0)), Arc::new(AtomicU32::new(0))];
+        let exporters = fail
+            .iter()
+            .zip(&exports)
+            .map(|(fail, exports)| CountingExporter {
+                exports: exports.clone(),
+                fail: *fail,
+            })
+            .collect();
+        (FailoverExporter::new(exporters), exports)
+    }
+
+    #[test]
+    fn test_failover() {
+        let (exporter, exports) = exporters([false, false]);
+        assert!(exporter.export(vec![]).now_or_never().unwrap().is_ok());
+        assert_eq!(exports.each_ref().map(|e| e.load(Ordering::SeqCst)), [1, 0]);
+
+        let (exporter, exports) = exporters([true, false]);
+        assert!(exporter.export(vec![]).now_or_never().unwrap().is_ok());
+        assert_eq!(exports.each_ref().map(|e| e.load(Ordering::SeqCst)), [1, 1]);
+
+        let (exporter, exports) = exporters([true, true]);
+        assert!(exporter.export(vec![]).now_or_never().unwrap().is_err());
+        assert_eq!(exports.each_ref().map(|e| e.load(Ordering::SeqCst)), [1, 1]);
+    }
+}
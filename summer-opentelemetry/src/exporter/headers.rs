@@ -0,0 +1,163 @@
+//! Headers of the HTTP exports and gRPC metadata of the tonic exports evaluated before each
+//! request, e.g. a rotating API token.
+
+#[cfg(any(feature = "http", feature = "http-json"))]
+use http::{HeaderName, HeaderValue};
+#[cfg(any(feature = "http", feature = "http-json"))]
+use opentelemetry_http::{Bytes, HttpClient, HttpError, Request, Response};
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+/// Supplies headers added to each request of the OTLP exporters, set with
+/// [`OpenTelemetryPluginBuilder::with_headers_provider`](crate::OpenTelemetryPluginBuilder::with_headers_provider).
+#[derive(Clone)]
+pub(crate) struct HeadersProvider(Arc<dyn Fn() -> HashMap<String, String> + Send + Sync>);
+
+impl HeadersProvider {
+    pub(crate) fn new<F>(provider: F) -> Self
+    where
+        F: Fn() -> HashMap<String, String> + Send + Sync + 'static,
+    {
+        Self(Arc::new(provider))
+    }
+}
+
+impl Debug for HeadersProvider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("HeadersProvider")
+    }
+}
+
+/// An HTTP client adding the headers of a [`HeadersProvider`] to each request, replacing the
+/// configured headers with the same name.
+#[cfg(any(feature = "http", feature = "http-json"))]
+#[derive(Debug)]
+pub(crate) struct HeadersHttpClient {
+    inner: Arc<dyn HttpClient>,
+    provider: HeadersProvider,
+}
+
+#[cfg(any(feature = "http", feature = "http-json"))]
+impl HeadersHttpClient {
+    pub(crate) fn new(inner: Arc<dyn HttpClient>, provider: HeadersProvider) -> Self {
+        Self { inner, provider }
+    }
+}
+
+#[cfg(any(feature = "http", feature = "http-json"))]
+#[async_trait::async_trait]
+impl HttpClient for HeadersHttpClient {
+    async fn send_bytes(&self, mut request: Request<Bytes>) -> Result<Response<Bytes>, HttpError> {
+        for (name, value) in (self.provider.0)() {
+            let name = HeaderName::try_from(name)?;
+            let value = HeaderValue::try_from(value)?;
+            request.headers_mut().insert(name, value);
+        }
+        self.inner.send_bytes(request).await
+    }
+}
+
+/// A tonic interceptor adding the headers of a [`HeadersProvider`] to the metadata of each
+/// request, replacing the configured headers with the same name.
+#[cfg(feature = "grpc")]
+#[derive(Debug, Clone)]
+pub(crate) struct HeadersInterceptor(HeadersProvider);
+
+#[cfg(feature = "grpc")]
+impl HeadersInterceptor {
+    pub(crate) fn new(provider: HeadersProvider) -> Self {
+        Self(provider)
+    }
+}
+
+#[cfg(feature = "grpc")]
+impl tonic::service::Interceptor for HeadersInterceptor {
+    fn call(
+        &mut self,
+        mut request: tonic::Request<()>,
+    ) -> Result<tonic::Request<()>, tonic::Status> {
+        use tonic::metadata::{Ascii, MetadataKey, MetadataValue};
+
+        for (name, value) in (self.0 .0)() {
+            let key = MetadataKey::<Ascii>::from_bytes(name.as_bytes()).map_err(|_| {
+                tonic::Status::invalid_argument(format!("invalid header name `{name}`"))
+            })?;
+            let value = MetadataValue::try_from(value).map_err(|_| {
+                tonic::Status::invalid_argument(format!("invalid value of header `{name}`"))
+            })?;
+            request.metadata_mut().insert(key, value);
+        }
+        Ok(request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    /// A provider of a new bearer token on each call.
+    fn rotating_tokens() -> HeadersProvider {
+        let refreshes = Arc::new(AtomicU32::new(0));
+        HeadersProvider::new(move || {
+            let token = refreshes.fetch_add(1, Ordering::SeqCst);
+            HashMap::from([("authorization".to_string(), format!("Bearer {token}"))])
+        })
+    }
+
+    /// Records the `authorization` header of the requests.
+    #[cfg(any(feature = "http", feature = "http-json"))]
+    #[derive(Debug, Default)]
+    struct RecordingClient(std::sync::Mutex<Vec<String>>);
+
+    #[cfg(any(feature = "http", feature = "http-json"))]
+    #[async_trait::async_trait]
+    impl HttpClient for RecordingClient {
+        async fn send_bytes(&self, request: Request<Bytes>) -> Result<Response<Bytes>, HttpError> {
+            let authorization = request.headers()["authorization"].to_str()?.to_string();
+            self.0.lock().unwrap().push(authorization);
+            Ok(Response::new(Bytes::new()))
+        }
+    }
+
+    #[cfg(any(feature = "http", feature = "http-json"))]
+    #[test]
+    fn test_headers_evaluated_per_request() {
+        use futures_util::FutureExt;
+
+        let recording = Arc::new(RecordingClient::default());
+        let client = HeadersHttpClient::new(recording.clone(), rotating_tokens());
+
+        for _ in 0..2 {
+            let request = Request::builder()
+                .header("authorization", "Bearer static")
+                .body(Bytes::new())
+                .unwrap();
+            let response = client.send_bytes(request).now_or_never();
+            assert!(matches!(response, Some(Ok(_))));
+        }
+        assert_eq!(*recording.0.lock().unwrap(), ["Bearer 0", "Bearer 1"]);
+    }
+
+    #[cfg(feature = "grpc")]
+    #[test]
+    fn test_metadata_evaluated_per_request() {
+        use tonic::service::Interceptor;
+
+        let mut interceptor = HeadersInterceptor::new(rotating_tokens());
+        for expected in ["Bearer 0", "Bearer 1"] {
+            let mut request = tonic::Request::new(());
+            let static_token = "Bearer static".parse().unwrap();
+            request.metadata_mut().insert("authorization", static_token);
+            let request = interceptor.call(request).expect("intercept failed");
+            let values: Vec<_> = request
+                .metadata()
+                .get_all("authorization")
+                .iter()
+                .map(|value| value.to_str().unwrap().to_string())
+                .collect();
+            assert_eq!(values, [expected]);
+        }
+    }
+}
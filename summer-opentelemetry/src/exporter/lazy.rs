@@ -0,0 +1,285 @@
+//! Deferred construction of the OTLP exporters with `lazy_init`, so that their TLS settings
+//! are read and their HTTP clients started on the first export rather than at startup.
+
+use super::Signal;
+use opentelemetry_sdk::error::{OTelSdkError, OTelSdkResult};
+use opentelemetry_sdk::logs::LogBatch;
+use opentelemetry_sdk::metrics::data::ResourceMetrics;
+use opentelemetry_sdk::metrics::exporter::PushMetricExporter;
+use opentelemetry_sdk::metrics::Temporality;
+use opentelemetry_sdk::trace::SpanData;
+use opentelemetry_sdk::Resource;
+use std::fmt;
+use std::sync::{Mutex, OnceLock, PoisonError};
+use std::time::Duration;
+
+type Build<E> = Box<dyn FnOnce() -> anyhow::Result<E> + Send>;
+
+/// Exporter built by `build` on its first export, within the Tokio runtime the exporter was
+/// created in, e.g. for the tonic channel of the `grpc` exporters. When the build fails, a
+/// warning is logged and the exports of the signal fail.
+pub(crate) struct LazyExporter<E> {
+    signal: Signal,
+    build: Mutex<Option<Build<E>>>,
+    exporter: OnceLock<Option<E>>,
+    runtime: Option<tokio::runtime::Handle>,
+    /// The resource set by the provider before the exporter is built.
+    resource: Mutex<Option<Resource>>,
+    /// The temporality of the metrics exporter, read by the periodic reader when it's built.
+    temporality: Temporality,
+}
+
+impl<E> LazyExporter<E> {
+    pub(crate) fn new(
+        signal: Signal,
+        temporality: Temporality,
+        build: impl FnOnce() -> anyhow::Result<E> + Send + 'static,
+    ) -> Self {
+        Self {
+            signal,
+            build: Mutex::new(Some(Box::new(build))),
+            exporter: OnceLock::new(),
+            runtime: tokio::runtime::Handle::try_current().ok(),
+            resource: Mutex::default(),
+            temporality,
+        }
+    }
+
+    /// The exporter already built, e.g. when `lazy_init` isn't set.
+    pub(crate) fn ready(signal: Signal, temporality: Temporality, exporter: E) -> Self {
+        Self {
+            signal,
+            build: Mutex::default(),
+            exporter: OnceLock::from(Some(exporter)),
+            runtime: None,
+            resource: Mutex::default(),
+            temporality,
+        }
+    }
+
+    /// Whether the exporter has been built, successfully or not.
+    #[cfg(test)]
+    fn is_built(&self) -> bool {
+        self.exporter.get().is_some()
+    }
+
+    /// The exporter, built on the first call.
+    fn exporter(&self, set_resource: impl FnOnce(&mut E, &Resource)) -> Option<&E> {
+        self.exporter
+            .get_or_init(|| {
+                let build = self
+                    .build
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .take()?;
+                let _guard = self.runtime.as_ref().map(|runtime| runtime.enter());
+                match build() {
+                    Ok(mut exporter) => {
+                        let resource = self.resource.lock().unwrap_or_else(PoisonError::into_inner);
+                        if let Some(resource) = &*resource {
+                            set_resource(&mut exporter, resource);
+                        }
+                        Some(exporter)
+                    }
+                    Err(e) => {
+                        tracing::warn!(
+                            "{} are not exported, the exporter build failed: {e:?}",
+                            self.signal
+                        );
+                        None
+                    }
+                }
+            })
+            .as_ref()
+    }
+
+    /// The exporter if it has been built, to shut it down or flush it.
+    fn built(&mut self) -> Option<&mut E> {
+        self.exporter.get_mut().and_then(Option::as_mut)
+    }
+
+    fn set_resource_with(&mut self, resource: &Resource, f: impl FnOnce(&mut E, &Resource)) {
+        match self.built() {
+            Some(exporter) => f(exporter, resource),
+            None => {
+                *self.resource.lock().unwrap_or_else(PoisonError::into_inner) =
+                    Some(resource.clone())
+            }
+        }
+    }
+
+    fn build_failed(&self) -> OTelSdkError {
+        OTelSdkError::InternalFailure(format!("the {} exporter build failed", self.signal))
+    }
+}
+
+impl<E> fmt::Debug for LazyExporter<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LazyExporter")
+            .field("signal", &self.signal)
+            .field("built", &self.exporter.get().is_some())
+            .finish()
+    }
+}
+
+impl<E: opentelemetry_sdk::trace::SpanExporter> opentelemetry_sdk::trace::SpanExporter
+    for LazyExporter<E>
+{
+    async fn export(&self, batch: Vec<SpanData>) -> OTelSdkResult {
+        match self.exporter(E::set_resource) {
+            Some(exporter) => exporter.export(batch).await,
+            None => Err(self.build_failed()),
+        }
+    }
+
+    fn shutdown_with_timeout(&mut self, timeout: Duration) -> OTelSdkResult {
+        self.built()
+            .map_or(Ok(()), |exporter| exporter.shutdown_with_timeout(timeout))
+    }
+
+    fn shutdown(&mut self) -> OTelSdkResult {
+        self.built().map_or(Ok(()), |exporter| exporter.shutdown())
+    }
+
+    fn force_flush(&mut self) -> OTelSdkResult {
+        self.built()
+            .map_or(Ok(()), |exporter| exporter.force_flush())
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.set_resource_with(resource, E::set_resource)
+    }
+}
+
+impl<E: opentelemetry_sdk::logs::LogExporter> opentelemetry_sdk::logs::LogExporter
+    for LazyExporter<E>
+{
+    async fn export(&self, batch: LogBatch<'_>) -> OTelSdkResult {
+        match self.exporter(E::set_resource) {
+            Some(exporter) => exporter.export(batch).await,
+            None => Err(self.build_failed()),
+        }
+    }
+
+    fn shutdown_with_timeout(&self, timeout: Duration) -> OTelSdkResult {
+        match self.exporter.get() {
+            Some(Some(exporter)) => exporter.shutdown_with_timeout(timeout),
+            _ => Ok(()),
+        }
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.set_resource_with(resource, E::set_resource)
+    }
+}
+
+impl<E: PushMetricExporter> PushMetricExporter for LazyExporter<E> {
+    async fn export(&self, metrics: &ResourceMetrics) -> OTelSdkResult {
+        // the metrics exporters get the resource with the metrics
+        match self.exporter(|_, _| {}) {
+            Some(exporter) => exporter.export(metrics).await,
+            None => Err(self.build_failed()),
+        }
+    }
+
+    fn force_flush(&self) -> OTelSdkResult {
+        match self.exporter.get() {
+            Some(Some(exporter)) => exporter.force_flush(),
+            _ => Ok(()),
+        }
+    }
+
+    fn shutdown_with_timeout(&self, timeout: Duration) -> OTelSdkResult {
+        match self.exporter.get() {
+            Some(Some(exporter)) => exporter.shutdown_with_timeout(timeout),
+            _ => Ok(()),
+        }
+    }
+
+    fn temporality(&self) -> Temporality {
+        self.temporality
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry::trace::{Tracer, TracerProvider};
+    use opentelemetry::KeyValue;
+    use opentelemetry_sdk::trace::{InMemorySpanExporter, SdkTracerProvider, SpanExporter};
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+
+    /// Records the exported span names and the resource set on it.
+    #[derive(Debug, Default, Clone)]
+    struct RecordingExporter {
+        names: Arc<Mutex<Vec<String>>>,
+        resource: Arc<Mutex<Option<Resource>>>,
+    }
+
+    impl SpanExporter for RecordingExporter {
+        async fn export(&self, batch: Vec<SpanData>) -> OTelSdkResult {
+            let names = batch.into_iter().map(|span| span.name.into_owned());
+            self.names.lock().unwrap().extend(names);
+            Ok(())
+        }
+
+        fn set_resource(&mut self, resource: &Resource) {
+            *self.resource.lock().unwrap() = Some(resource.clone());
+        }
+    }
+
+    #[test]
+    fn test_exporter_built_on_first_export() {
+        let recording = RecordingExporter::default();
+        let built = recording.clone();
+        let builds = Arc::new(AtomicU32::new(0));
+        let counted = builds.clone();
+        let exporter = LazyExporter::new(Signal::Traces, Temporality::Cumulative, move || {
+            counted.fetch_add(1, Ordering::SeqCst);
+            Ok(built)
+        });
+        let provider = SdkTracerProvider::builder()
+            .with_resource(
+                Resource::builder_empty()
+                    .with_attribute(KeyValue::new("service.name", "payments"))
+                    .build(),
+            )
+            .with_simple_exporter(exporter)
+            .build();
+        assert_eq!(builds.load(Ordering::SeqCst), 0);
+        let tracer = provider.tracer("test");
+        tracer.in_span("checkout", |_| {});
+        tracer.in_span("pay", |_| {});
+
+        assert_eq!(builds.load(Ordering::SeqCst), 1);
+        assert_eq!(*recording.names.lock().unwrap(), ["checkout", "pay"]);
+        // the resource set by the provider before the build applies to the exporter
+        let resource = recording.resource.lock().unwrap().clone().unwrap();
+        assert_eq!(
+            resource.get(&opentelemetry::Key::from_static_str("service.name")),
+            Some("payments".into())
+        );
+    }
+
+    #[test]
+    fn test_exporter_build_failed() {
+        let builds = Arc::new(AtomicU32::new(0));
+        let counted = builds.clone();
+        let exporter = LazyExporter::<InMemorySpanExporter>::new(
+            Signal::Traces,
+            Temporality::Cumulative,
+            move || {
+                counted.fetch_add(1, Ordering::SeqCst);
+                Err(anyhow::anyhow!("invalid certificate"))
+            },
+        );
+        assert!(!exporter.is_built());
+        for _ in 0..2 {
+            let result = futures_util::FutureExt::now_or_never(exporter.export(Vec::new()));
+            assert!(matches!(result, Some(Err(_))));
+        }
+        assert!(exporter.is_built());
+        assert_eq!(builds.load(Ordering::SeqCst), 1);
+    }
+}
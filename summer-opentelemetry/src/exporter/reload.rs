@@ -0,0 +1,354 @@
+//! Replacement of the OTLP exporters of the running providers, e.g. to point them at another
+//! collector without restarting the application.
+
+use super::{
+    build_otlp_exporter, DiagnosticsExporter, LazyExporter, RetryExporter, Signal, SplitExporter,
+};
+use crate::builder::PluginOptions;
+use crate::config::{ExporterKind, OpenTelemetryConfig};
+use crate::OpenTelemetryPlugin;
+use opentelemetry_otlp::{LogExporter, MetricExporter, SpanExporter};
+use opentelemetry_sdk::error::OTelSdkResult;
+use opentelemetry_sdk::logs::LogBatch;
+use opentelemetry_sdk::metrics::data::ResourceMetrics;
+use opentelemetry_sdk::metrics::exporter::PushMetricExporter;
+use opentelemetry_sdk::metrics::Temporality;
+use opentelemetry_sdk::trace::SpanData;
+use opentelemetry_sdk::Resource;
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
+use std::time::Duration;
+use summer::error::Result;
+
+/// The OTLP exporters built by `build_otlp_exporter`.
+pub(crate) type OtlpExporter<E> = SplitExporter<DiagnosticsExporter<RetryExporter<E>>>;
+
+/// Exporter delegating to an inner exporter that can be replaced while the processor or the
+/// periodic reader owning it runs. The exports in flight complete with the previous exporter.
+#[derive(Debug)]
+pub(crate) struct ReloadableExporter<E> {
+    exporter: Arc<RwLock<Arc<E>>>,
+    /// The resource set by the provider, set on the replacing exporters too.
+    resource: Arc<Mutex<Option<Resource>>>,
+}
+
+impl<E> Clone for ReloadableExporter<E> {
+    fn clone(&self) -> Self {
+        Self {
+            exporter: self.exporter.clone(),
+            resource: self.resource.clone(),
+        }
+    }
+}
+
+impl<E> ReloadableExporter<E> {
+    pub(crate) fn new(exporter: E) -> Self {
+        Self {
+            exporter: Arc::new(RwLock::new(Arc::new(exporter))),
+            resource: Arc::default(),
+        }
+    }
+
+    fn current(&self) -> Arc<E> {
+        self.exporter
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    fn resource(&self) -> Option<Resource> {
+        self.resource
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Replaces the exporter, returning the previous one unless an export in flight uses it.
+    fn replace(&self, exporter: E) -> Option<E> {
+        let mut current = self
+            .exporter
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        let previous = std::mem::replace(&mut *current, Arc::new(exporter));
+        drop(current);
+        Arc::into_inner(previous)
+    }
+
+    /// Applies `f` to the exporter, unless an export in flight uses it.
+    fn with_exporter_mut(&self, f: impl FnOnce(&mut E) -> OTelSdkResult) -> OTelSdkResult {
+        let mut current = self
+            .exporter
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        match Arc::get_mut(&mut current) {
+            Some(exporter) => f(exporter),
+            None => Ok(()),
+        }
+    }
+
+    fn set_resource_with(&mut self, resource: &Resource, f: impl FnOnce(&mut E, &Resource)) {
+        *self.resource.lock().unwrap_or_else(PoisonError::into_inner) = Some(resource.clone());
+        let mut current = self
+            .exporter
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        if let Some(exporter) = Arc::get_mut(&mut current) {
+            f(exporter, resource);
+        }
+    }
+}
+
+impl<E: opentelemetry_sdk::trace::SpanExporter> opentelemetry_sdk::trace::SpanExporter
+    for ReloadableExporter<E>
+{
+    async fn export(&self, batch: Vec<SpanData>) -> OTelSdkResult {
+        let exporter = self.current();
+        exporter.export(batch).await
+    }
+
+    fn shutdown_with_timeout(&mut self, timeout: Duration) -> OTelSdkResult {
+        self.with_exporter_mut(|exporter| exporter.shutdown_with_timeout(timeout))
+    }
+
+    fn shutdown(&mut self) -> OTelSdkResult {
+        self.with_exporter_mut(|exporter| exporter.shutdown())
+    }
+
+    fn force_flush(&mut self) -> OTelSdkResult {
+        self.with_exporter_mut(|exporter| exporter.force_flush())
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.set_resource_with(resource, |exporter, resource| {
+            exporter.set_resource(resource)
+        })
+    }
+}
+
+impl<E: opentelemetry_sdk::logs::LogExporter> opentelemetry_sdk::logs::LogExporter
+    for ReloadableExporter<E>
+{
+    async fn export(&self, batch: LogBatch<'_>) -> OTelSdkResult {
+        let exporter = self.current();
+        exporter.export(batch).await
+    }
+
+    fn shutdown_with_timeout(&self, timeout: Duration) -> OTelSdkResult {
+        self.current().shutdown_with_timeout(timeout)
+    }
+
+    fn shutdown(&self) -> OTelSdkResult {
+        self.current().shutdown()
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.set_resource_with(resource, |exporter, resource| {
+            exporter.set_resource(resource)
+        })
+    }
+}
+
+impl<E: PushMetricExporter> PushMetricExporter for ReloadableExporter<E> {
+    async fn export(&self, metrics: &ResourceMetrics) -> OTelSdkResult {
+        let exporter = self.current();
+        exporter.export(metrics).await
+    }
+
+    fn force_flush(&self) -> OTelSdkResult {
+        self.current().force_flush()
+    }
+
+    fn shutdown_with_timeout(&self, timeout: Duration) -> OTelSdkResult {
+        self.current().shutdown_with_timeout(timeout)
+    }
+
+    fn shutdown(&self) -> OTelSdkResult {
+        self.current().shutdown()
+    }
+
+    fn temporality(&self) -> Temporality {
+        self.current().temporality()
+    }
+}
+
+type TraceExporter = ReloadableExporter<LazyExporter<OtlpExporter<SpanExporter>>>;
+type MetricsExporter = ReloadableExporter<LazyExporter<OtlpExporter<MetricExporter>>>;
+type LogsExporter = ReloadableExporter<LazyExporter<OtlpExporter<LogExporter>>>;
+
+/// The OTLP exporters of the providers, replaced by
+/// [`OpenTelemetryHandle::reload`](crate::OpenTelemetryHandle::reload).
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ReloadableExporters {
+    options: PluginOptions,
+    traces: Option<TraceExporter>,
+    metrics: Option<MetricsExporter>,
+    logs: Option<LogsExporter>,
+}
+
+impl ReloadableExporters {
+    /// The exporters built later with the options set in code.
+    pub(crate) fn new(options: &PluginOptions) -> Self {
+        Self {
+            options: options.clone(),
+            ..Self::default()
+        }
+    }
+
+    pub(crate) fn traces(
+        &mut self,
+        exporter: LazyExporter<OtlpExporter<SpanExporter>>,
+    ) -> TraceExporter {
+        self.traces
+            .insert(ReloadableExporter::new(exporter))
+            .clone()
+    }
+
+    pub(crate) fn metrics(
+        &mut self,
+        exporter: LazyExporter<OtlpExporter<MetricExporter>>,
+    ) -> MetricsExporter {
+        self.metrics
+            .insert(ReloadableExporter::new(exporter))
+            .clone()
+    }
+
+    pub(crate) fn logs(
+        &mut self,
+        exporter: LazyExporter<OtlpExporter<LogExporter>>,
+    ) -> LogsExporter {
+        self.logs.insert(ReloadableExporter::new(exporter)).clone()
+    }
+
+    /// Builds the exporters of `config`, calls `flush` so that the buffered telemetry is
+    /// exported by the previous exporters, then replaces and shuts them down.
+    ///
+    /// The exporters are all built before any is replaced, so that an invalid config doesn't
+    /// change any of them.
+    pub(crate) fn reload(&self, config: &OpenTelemetryConfig, flush: impl FnOnce()) -> Result<()> {
+        let options = &self.options;
+        for (signal, reloadable) in [
+            (Signal::Traces, self.traces.is_some()),
+            (Signal::Metrics, self.metrics.is_some()),
+            (Signal::Logs, self.logs.is_some()),
+        ] {
+            if reloadable && config.exporter(signal) != ExporterKind::Otlp {
+                return Err(anyhow::anyhow!(
+                    "the {signal} exporter can't be changed by a reload, only the OTLP exporter settings"
+                )
+                .into());
+            }
+        }
+        let traces = match &self.traces {
+            Some(_) => {
+                let config = &config.for_signal(Signal::Traces);
+                if OpenTelemetryPlugin::trace_endpoints(config, options).is_some() {
+                    return Err(anyhow::anyhow!("traces_endpoints can't be set by a reload").into());
+                }
+                let exporter =
+                    build_otlp_exporter!(Signal::Traces, SpanExporter::builder(), config, options)?;
+                Some(LazyExporter::ready(
+                    Signal::Traces,
+                    Temporality::default(),
+                    exporter,
+                ))
+            }
+            None => None,
+        };
+        let metrics = match &self.metrics {
+            Some(current) => {
+                // the periodic reader keeps the temporality of the exporter it was built with
+                let temporality = current.temporality();
+                let exporter = build_otlp_exporter!(
+                    Signal::Metrics,
+                    MetricExporter::builder().with_temporality(temporality),
+                    &config.for_signal(Signal::Metrics),
+                    options
+                )?;
+                Some(LazyExporter::ready(Signal::Metrics, temporality, exporter))
+            }
+            None => None,
+        };
+        let logs = match &self.logs {
+            Some(_) => {
+                let exporter = build_otlp_exporter!(
+                    Signal::Logs,
+                    LogExporter::builder(),
+                    &config.for_signal(Signal::Logs),
+                    options
+                )?;
+                Some(LazyExporter::ready(
+                    Signal::Logs,
+                    Temporality::default(),
+                    exporter,
+                ))
+            }
+            None => None,
+        };
+
+        flush();
+        let timeout = Duration::from_millis(config.shutdown_timeout_ms);
+        if let (Some(current), Some(mut exporter)) = (&self.traces, traces) {
+            use opentelemetry_sdk::trace::SpanExporter as _;
+            if let Some(resource) = current.resource() {
+                exporter.set_resource(&resource);
+            }
+            if let Some(mut previous) = current.replace(exporter) {
+                let _ = previous.shutdown_with_timeout(timeout);
+            }
+        }
+        if let (Some(current), Some(exporter)) = (&self.metrics, metrics) {
+            if let Some(previous) = current.replace(exporter) {
+                let _ = previous.shutdown_with_timeout(timeout);
+            }
+        }
+        if let (Some(current), Some(mut exporter)) = (&self.logs, logs) {
+            use opentelemetry_sdk::logs::LogExporter as _;
+            if let Some(resource) = current.resource() {
+                exporter.set_resource(&resource);
+            }
+            if let Some(previous) = current.replace(exporter) {
+                let _ = previous.shutdown_with_timeout(timeout);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry::trace::{Tracer, TracerProvider};
+    use opentelemetry::KeyValue;
+    use opentelemetry_sdk::trace::{InMemorySpanExporter, SdkTracerProvider};
+
+    #[test]
+    fn test_replace_exporter() {
+        let previous = InMemorySpanExporter::default();
+        let exporter = ReloadableExporter::new(previous.clone());
+        let provider = SdkTracerProvider::builder()
+            .with_resource(
+                Resource::builder_empty()
+                    .with_attribute(KeyValue::new("service.name", "payments"))
+                    .build(),
+            )
+            .with_simple_exporter(exporter.clone())
+            .build();
+        let tracer = provider.tracer("test");
+        tracer.in_span("before", |_| {});
+        let resource = exporter.resource().expect("resource not set");
+
+        let next = InMemorySpanExporter::default();
+        assert!(exporter.replace(next.clone()).is_some());
+        tracer.in_span("after", |_| {});
+
+        let names = |exporter: &InMemorySpanExporter| {
+            let spans = exporter.get_finished_spans().unwrap();
+            spans.into_iter().map(|span| span.name).collect::<Vec<_>>()
+        };
+        assert_eq!(names(&previous), ["before"]);
+        assert_eq!(names(&next), ["after"]);
+        assert_eq!(
+            resource.get(&opentelemetry::Key::new("service.name")),
+            Some("payments".into())
+        );
+    }
+}
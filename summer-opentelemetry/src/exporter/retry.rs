@@ -0,0 +1,399 @@
+//! Retry of failed exports with an exponential backoff.
+
+use opentelemetry_sdk::error::{OTelSdkError, OTelSdkResult};
+use opentelemetry_sdk::logs::{LogBatch, LogExporter};
+use opentelemetry_sdk::metrics::data::ResourceMetrics;
+use opentelemetry_sdk::metrics::exporter::PushMetricExporter;
+use opentelemetry_sdk::metrics::Temporality;
+use opentelemetry_sdk::trace::{SpanData, SpanExporter};
+use opentelemetry_sdk::Resource;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Set when the application shuts down, the final exports of the batch processors aren't
+/// retried so that the shutdown isn't delayed by the backoff.
+static RETRIES_STOPPED: AtomicBool = AtomicBool::new(false);
+
+/// Stops retrying the failed exports of all exporters, before the providers shut down.
+pub(crate) fn stop_retries() {
+    RETRIES_STOPPED.store(true, Ordering::Relaxed);
+}
+
+/// How often and how long to wait before retrying a failed export.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct RetryPolicy {
+    pub(crate) max_retries: u32,
+    pub(crate) initial_backoff: Duration,
+    pub(crate) stopped: &'static AtomicBool,
+}
+
+impl RetryPolicy {
+    pub(crate) fn new(max_retries: u32, initial_backoff: Duration) -> Self {
+        Self {
+            max_retries,
+            initial_backoff,
+            stopped: &RETRIES_STOPPED,
+        }
+    }
+
+    /// Runs `export` until it succeeds, fails with an error that isn't transient,
+    /// or `max_retries` is exhausted. The backoff doubles after each retry.
+    ///
+    /// The failures of the HTTP exporters are classified by their client in `failure`.
+    async fn retry<F, Fut>(
+        &self,
+        failure: Option<&TransientFailure>,
+        mut export: F,
+    ) -> OTelSdkResult
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = OTelSdkResult>,
+    {
+        let mut backoff = self.initial_backoff;
+        let mut retries = 0;
+        loop {
+            // the failure of a previous request
+            let _ = failure.map(TransientFailure::take);
+            match export().await {
+                Err(e)
+                    if retries < self.max_retries
+                        && is_transient(&e, failure)
+                        && !self.stopped.load(Ordering::Relaxed) =>
+                {
+                    retries += 1;
+                    // Only the batch processors and the periodic reader, which export on
+                    // dedicated threads, retry, so the backoff doesn't block the application.
+                    std::thread::sleep(backoff);
+                    backoff = backoff.saturating_mul(2);
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// Whether the last request of an HTTP exporter failed with a transient error, recorded by its
+/// [`StatusHttpClient`] before the exporter formats the failure into the message of the error.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct TransientFailure(Arc<AtomicBool>);
+
+impl TransientFailure {
+    #[cfg(any(feature = "http", feature = "http-json"))]
+    fn set(&self, transient: bool) {
+        self.0.store(transient, Ordering::Relaxed);
+    }
+
+    fn take(&self) -> bool {
+        self.0.swap(false, Ordering::Relaxed)
+    }
+}
+
+/// Whether a failed export may succeed when retried: timeouts, connection failures and the
+/// retryable gRPC and HTTP statuses according to the OTLP specification, e.g. rejected or
+/// malformed payloads are permanent.
+fn is_transient(error: &OTelSdkError, failure: Option<&TransientFailure>) -> bool {
+    match error {
+        OTelSdkError::Timeout(_) => true,
+        OTelSdkError::InternalFailure(message) => match failure {
+            Some(failure) => failure.take(),
+            None => grpc_code(message).is_some_and(is_retryable_code),
+        },
+        _ => false,
+    }
+}
+
+/// The code of the status a tonic exporter failed with. The exporter only reports the status
+/// formatted as `code: '<description>', message: ...`.
+fn grpc_code(message: &str) -> Option<tonic::Code> {
+    let (description, _) = message.strip_prefix("code: '")?.split_once('\'')?;
+    (0..=16)
+        .map(tonic::Code::from_i32)
+        .find(|code| code.description() == description)
+}
+
+fn is_retryable_code(code: tonic::Code) -> bool {
+    use tonic::Code;
+
+    matches!(
+        code,
+        Code::Cancelled
+            | Code::DeadlineExceeded
+            | Code::ResourceExhausted
+            | Code::Aborted
+            | Code::OutOfRange
+            | Code::Unavailable
+            | Code::DataLoss
+    )
+}
+
+#[cfg(any(feature = "http", feature = "http-json"))]
+fn is_retryable_status(status: http::StatusCode) -> bool {
+    use http::StatusCode;
+
+    matches!(
+        status,
+        StatusCode::TOO_MANY_REQUESTS
+            | StatusCode::BAD_GATEWAY
+            | StatusCode::SERVICE_UNAVAILABLE
+            | StatusCode::GATEWAY_TIMEOUT
+    )
+}
+
+/// An HTTP client recording in a [`TransientFailure`] whether each request failed with a
+/// retryable status, a timeout or a connection failure.
+#[cfg(any(feature = "http", feature = "http-json"))]
+#[derive(Debug)]
+pub(crate) struct StatusHttpClient {
+    inner: Arc<dyn opentelemetry_http::HttpClient>,
+    failure: TransientFailure,
+}
+
+#[cfg(any(feature = "http", feature = "http-json"))]
+impl StatusHttpClient {
+    pub(crate) fn new(
+        inner: Arc<dyn opentelemetry_http::HttpClient>,
+        failure: TransientFailure,
+    ) -> Self {
+        Self { inner, failure }
+    }
+}
+
+#[cfg(any(feature = "http", feature = "http-json"))]
+#[async_trait::async_trait]
+impl opentelemetry_http::HttpClient for StatusHttpClient {
+    async fn send_bytes(
+        &self,
+        request: http::Request<opentelemetry_http::Bytes>,
+    ) -> Result<http::Response<opentelemetry_http::Bytes>, opentelemetry_http::HttpError> {
+        let result = self.inner.send_bytes(request).await;
+        let transient = match &result {
+            Ok(response) => is_retryable_status(response.status()),
+            // reqwest reports the error statuses as errors
+            Err(e) => e.downcast_ref::<reqwest::Error>().is_some_and(|e| {
+                e.status()
+                    .map_or(e.is_connect() || e.is_timeout(), is_retryable_status)
+            }),
+        };
+        self.failure.set(transient);
+        result
+    }
+}
+
+/// Exporter retrying the failed exports of the inner exporter with a [`RetryPolicy`].
+#[derive(Debug)]
+pub(crate) struct RetryExporter<E> {
+    inner: E,
+    policy: RetryPolicy,
+    /// The failures classified by the client of an HTTP exporter.
+    failure: Option<TransientFailure>,
+}
+
+impl<E> RetryExporter<E> {
+    pub(crate) fn new(inner: E, policy: RetryPolicy, failure: Option<TransientFailure>) -> Self {
+        Self {
+            inner,
+            policy,
+            failure,
+        }
+    }
+}
+
+impl<E: SpanExporter> SpanExporter for RetryExporter<E> {
+    async fn export(&self, batch: Vec<SpanData>) -> OTelSdkResult {
+        if self.policy.max_retries == 0 {
+            return self.inner.export(batch).await;
+        }
+        self.policy
+            .retry(self.failure.as_ref(), || self.inner.export(batch.clone()))
+            .await
+    }
+
+    fn shutdown_with_timeout(&mut self, timeout: Duration) -> OTelSdkResult {
+        self.inner.shutdown_with_timeout(timeout)
+    }
+
+    fn shutdown(&mut self) -> OTelSdkResult {
+        self.inner.shutdown()
+    }
+
+    fn force_flush(&mut self) -> OTelSdkResult {
+        self.inner.force_flush()
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.inner.set_resource(resource)
+    }
+}
+
+impl<E: LogExporter> LogExporter for RetryExporter<E> {
+    async fn export(&self, batch: LogBatch<'_>) -> OTelSdkResult {
+        if self.policy.max_retries == 0 {
+            return self.inner.export(batch).await;
+        }
+        let records = batch.iter().collect::<Vec<_>>();
+        self.policy
+            .retry(self.failure.as_ref(), || {
+                self.inner.export(LogBatch::new(&records))
+            })
+            .await
+    }
+
+    fn shutdown_with_timeout(&self, timeout: Duration) -> OTelSdkResult {
+        self.inner.shutdown_with_timeout(timeout)
+    }
+
+    fn shutdown(&self) -> OTelSdkResult {
+        self.inner.shutdown()
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.inner.set_resource(resource)
+    }
+}
+
+impl<E: PushMetricExporter> PushMetricExporter for RetryExporter<E> {
+    async fn export(&self, metrics: &ResourceMetrics) -> OTelSdkResult {
+        self.policy
+            .retry(self.failure.as_ref(), || self.inner.export(metrics))
+            .await
+    }
+
+    fn force_flush(&self) -> OTelSdkResult {
+        self.inner.force_flush()
+    }
+
+    fn shutdown_with_timeout(&self, timeout: Duration) -> OTelSdkResult {
+        self.inner.shutdown_with_timeout(timeout)
+    }
+
+    fn shutdown(&self) -> OTelSdkResult {
+        self.inner.shutdown()
+    }
+
+    fn temporality(&self) -> Temporality {
+        self.inner.temporality()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::FutureExt;
+    use std::sync::atomic::AtomicU32;
+
+    fn policy(max_retries: u32) -> RetryPolicy {
+        RetryPolicy {
+            initial_backoff: Duration::from_millis(1),
+            stopped: Box::leak(Box::new(AtomicBool::new(false))),
+            ..RetryPolicy::new(max_retries, Duration::ZERO)
+        }
+    }
+
+    fn unavailable() -> OTelSdkError {
+        let status = tonic::Status::unavailable("connection refused");
+        OTelSdkError::InternalFailure(status.to_string())
+    }
+
+    async fn failing(attempts: &AtomicU32, failures: u32) -> OTelSdkResult {
+        if attempts.fetch_add(1, Ordering::SeqCst) < failures {
+            Err(unavailable())
+        } else {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_retry_until_success() {
+        let attempts = AtomicU32::new(0);
+        let result = policy(3)
+            .retry(None, || failing(&attempts, 2))
+            .now_or_never();
+        assert!(matches!(result, Some(Ok(()))));
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn test_retry_exhausted() {
+        let attempts = AtomicU32::new(0);
+        let result = policy(2)
+            .retry(None, || failing(&attempts, 5))
+            .now_or_never();
+        assert!(matches!(result, Some(Err(_))));
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn test_retry_stopped() {
+        let attempts = AtomicU32::new(0);
+        let policy = policy(3);
+        policy.stopped.store(true, Ordering::Relaxed);
+        let result = policy.retry(None, || failing(&attempts, 5)).now_or_never();
+        assert!(matches!(result, Some(Err(_))));
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_is_transient() {
+        assert!(is_transient(&unavailable(), None));
+        let timeout = OTelSdkError::Timeout(Duration::from_secs(1));
+        assert!(is_transient(&timeout, None));
+        let status = tonic::Status::invalid_argument("malformed payload");
+        let invalid = OTelSdkError::InternalFailure(status.to_string());
+        assert!(!is_transient(&invalid, None));
+        let serialization = OTelSdkError::InternalFailure("Failed to serialize metrics".into());
+        assert!(!is_transient(&serialization, None));
+        assert_eq!(
+            grpc_code(&status.to_string()),
+            Some(tonic::Code::InvalidArgument)
+        );
+    }
+
+    #[cfg(any(feature = "http", feature = "http-json"))]
+    #[test]
+    fn test_http_failures() {
+        use opentelemetry_http::{Bytes, HttpClient, HttpError, Request, Response};
+
+        /// Responds with the status of the `x-status` header.
+        #[derive(Debug)]
+        struct StatusClient;
+
+        #[async_trait::async_trait]
+        impl HttpClient for StatusClient {
+            async fn send_bytes(
+                &self,
+                request: Request<Bytes>,
+            ) -> Result<Response<Bytes>, HttpError> {
+                let status: u16 = request.headers()["x-status"].to_str()?.parse()?;
+                Ok(Response::builder().status(status).body(Bytes::new())?)
+            }
+        }
+
+        let failure = TransientFailure::default();
+        let client = StatusHttpClient::new(Arc::new(StatusClient), failure.clone());
+        let send = |status: &str| {
+            let request = Request::builder()
+                .header("x-status", status)
+                .body(Bytes::new());
+            let _ = client.send_bytes(request.unwrap()).now_or_never();
+            let error = OTelSdkError::InternalFailure(format!("Status Code: {status}"));
+            is_transient(&error, Some(&failure))
+        };
+        assert!(send("503"));
+        assert!(send("429"));
+        assert!(!send("400"));
+        assert!(!send("401"));
+        // not sent
+        assert!(!send("200"));
+
+        // reqwest reports the error statuses and the connection failures as errors
+        let reqwest = reqwest::blocking::Client::new();
+        let client = StatusHttpClient::new(Arc::new(reqwest), failure.clone());
+        let request = Request::post("http://127.0.0.1:1/v1/traces").body(Bytes::new());
+        let result = client.send_bytes(request.unwrap()).now_or_never();
+        assert!(matches!(result, Some(Err(_))));
+        let error = OTelSdkError::InternalFailure("connection refused".into());
+        assert!(is_transient(&error, Some(&failure)));
+    }
+}
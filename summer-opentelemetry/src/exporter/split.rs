@@ -0,0 +1,292 @@
+//! Split of the span and log batches whose encoded size would exceed `max_export_batch_bytes`,
+//! e.g. the 4 MiB limit of the gRPC messages received by the collector.
+//!
+//! The sizes are estimated from the data of the records, slightly above their protobuf
+//! encoding, rather than encoding each batch twice.
+
+use opentelemetry::logs::AnyValue;
+use opentelemetry::trace::Status;
+use opentelemetry::{KeyValue, Value};
+use opentelemetry_sdk::error::OTelSdkResult;
+use opentelemetry_sdk::logs::{LogBatch, LogExporter, SdkLogRecord};
+use opentelemetry_sdk::metrics::data::ResourceMetrics;
+use opentelemetry_sdk::metrics::exporter::PushMetricExporter;
+use opentelemetry_sdk::metrics::Temporality;
+use opentelemetry_sdk::trace::{SpanData, SpanExporter};
+use opentelemetry_sdk::Resource;
+use std::time::Duration;
+
+/// Upper bound of the tags, lengths and fixed-size fields of a record.
+const RECORD_OVERHEAD: usize = 96;
+
+/// Upper bound of the tags and lengths of a field.
+const FIELD_OVERHEAD: usize = 8;
+
+/// Exporter splitting the span and log batches into exports of at most `max_bytes`, the
+/// metrics are exported as is. A record larger than `max_bytes` is exported alone, so that it
+/// doesn't take the records of its batch down with it.
+#[derive(Debug)]
+pub(crate) struct SplitExporter<E> {
+    inner: E,
+    max_bytes: Option<usize>,
+    /// The encoded size of the resource, sent with each export.
+    resource_bytes: usize,
+}
+
+impl<E> SplitExporter<E> {
+    pub(crate) fn new(inner: E, max_bytes: Option<usize>) -> Self {
+        Self {
+            inner,
+            max_bytes,
+            resource_bytes: 0,
+        }
+    }
+
+    fn set_resource_bytes(&mut self, resource: &Resource) {
+        self.resource_bytes = resource
+            .iter()
+            .map(|(key, value)| key.as_str().len() + value_bytes(value) + FIELD_OVERHEAD)
+            .sum();
+    }
+
+    /// The ranges of the exports of the records of `sizes`, in order.
+    fn chunks(
+        &self,
+        max_bytes: usize,
+        sizes: impl IntoIterator<Item = usize>,
+    ) -> Vec<std::ops::Range<usize>> {
+        let budget = max_bytes.saturating_sub(self.resource_bytes);
+        let mut chunks = Vec::new();
+        let (mut start, mut bytes, mut len) = (0, 0, 0);
+        for (i, size) in sizes.into_iter().enumerate() {
+            if i > start && bytes + size > budget {
+                chunks.push(start..i);
+                (start, bytes) = (i, 0);
+            }
+            bytes += size;
+            len = i + 1;
+        }
+        if len > start {
+            chunks.push(start..len);
+        }
+        chunks
+    }
+}
+
+impl<E: SpanExporter> SpanExporter for SplitExporter<E> {
+    async fn export(&self, mut batch: Vec<SpanData>) -> OTelSdkResult {
+        let Some(max_bytes) = self.max_bytes else {
+            return self.inner.export(batch).await;
+        };
+        let chunks = self.chunks(max_bytes, batch.iter().map(span_bytes));
+        if chunks.len() <= 1 {
+            return self.inner.export(batch).await;
+        }
+        let mut result = Ok(());
+        for chunk in chunks {
+            let spans = batch.drain(..chunk.len()).collect();
+            let chunk_result = self.inner.export(spans).await;
+            result = result.and(chunk_result);
+        }
+        result
+    }
+
+    fn shutdown_with_timeout(&mut self, timeout: Duration) -> OTelSdkResult {
+        self.inner.shutdown_with_timeout(timeout)
+    }
+
+    fn shutdown(&mut self) -> OTelSdkResult {
+        self.inner.shutdown()
+    }
+
+    fn force_flush(&mut self) -> OTelSdkResult {
+        self.inner.force_flush()
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.set_resource_bytes(resource);
+        self.inner.set_resource(resource)
+    }
+}
+
+impl<E: LogExporter> LogExporter for SplitExporter<E> {
+    async fn export(&self, batch: LogBatch<'_>) -> OTelSdkResult {
+        let Some(max_bytes) = self.max_bytes else {
+            return self.inner.export(batch).await;
+        };
+        let records: Vec<_> = batch.iter().collect();
+        let chunks = self.chunks(
+            max_bytes,
+            records.iter().map(|(record, _)| log_bytes(record)),
+        );
+        if chunks.len() <= 1 {
+            return self.inner.export(batch).await;
+        }
+        let mut result = Ok(());
+        for chunk in chunks {
+            let chunk_result = self.inner.export(LogBatch::new(&records[chunk])).await;
+            result = result.and(chunk_result);
+        }
+        result
+    }
+
+    fn shutdown_with_timeout(&self, timeout: Duration) -> OTelSdkResult {
+        self.inner.shutdown_with_timeout(timeout)
+    }
+
+    fn shutdown(&self) -> OTelSdkResult {
+        self.inner.shutdown()
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.set_resource_bytes(resource);
+        self.inner.set_resource(resource)
+    }
+}
+
+impl<E: PushMetricExporter> PushMetricExporter for SplitExporter<E> {
+    async fn export(&self, metrics: &ResourceMetrics) -> OTelSdkResult {
+        self.inner.export(metrics).await
+    }
+
+    fn force_flush(&self) -> OTelSdkResult {
+        self.inner.force_flush()
+    }
+
+    fn shutdown_with_timeout(&self, timeout: Duration) -> OTelSdkResult {
+        self.inner.shutdown_with_timeout(timeout)
+    }
+
+    fn shutdown(&self) -> OTelSdkResult {
+        self.inner.shutdown()
+    }
+
+    fn temporality(&self) -> Temporality {
+        self.inner.temporality()
+    }
+}
+
+fn value_bytes(value: &Value) -> usize {
+    match value {
+        Value::String(value) => value.as_str().len(),
+        Value::Array(array) => array.to_string().len() + FIELD_OVERHEAD,
+        _ => FIELD_OVERHEAD,
+    }
+}
+
+fn attributes_bytes<'a>(attributes: impl IntoIterator<Item = &'a KeyValue>) -> usize {
+    attributes
+        .into_iter()
+        .map(|kv| kv.key.as_str().len() + value_bytes(&kv.value) + FIELD_OVERHEAD)
+        .sum()
+}
+
+fn span_bytes(span: &SpanData) -> usize {
+    let status = match &span.status {
+        Status::Error { description } => description.len(),
+        _ => 0,
+    };
+    let events: usize = span
+        .events
+        .iter()
+        .map(|event| event.name.len() + attributes_bytes(&event.attributes) + RECORD_OVERHEAD)
+        .sum();
+    let links: usize = span
+        .links
+        .iter()
+        .map(|link| {
+            link.span_context.trace_state().header().len()
+                + attributes_bytes(&link.attributes)
+                + RECORD_OVERHEAD
+        })
+        .sum();
+    let scope = span.instrumentation_scope.name().len()
+        + span.instrumentation_scope.version().map_or(0, str::len);
+    RECORD_OVERHEAD
+        + span.name.len()
+        + span.span_context.trace_state().header().len()
+        + attributes_bytes(&span.attributes)
+        + status
+        + events
+        + links
+        + scope
+}
+
+fn any_value_bytes(value: &AnyValue) -> usize {
+    match value {
+        AnyValue::String(value) => value.as_str().len() + FIELD_OVERHEAD,
+        AnyValue::Bytes(bytes) => bytes.len() + FIELD_OVERHEAD,
+        AnyValue::ListAny(values) => {
+            values.iter().map(any_value_bytes).sum::<usize>() + FIELD_OVERHEAD
+        }
+        AnyValue::Map(map) => {
+            map.iter()
+                .map(|(key, value)| key.as_str().len() + any_value_bytes(value) + FIELD_OVERHEAD)
+                .sum::<usize>()
+                + FIELD_OVERHEAD
+        }
+        _ => FIELD_OVERHEAD,
+    }
+}
+
+fn log_bytes(record: &SdkLogRecord) -> usize {
+    let attributes: usize = record
+        .attributes_iter()
+        .map(|(key, value)| key.as_str().len() + any_value_bytes(value) + FIELD_OVERHEAD)
+        .sum();
+    RECORD_OVERHEAD
+        + record.body().map_or(0, any_value_bytes)
+        + attributes
+        + record.severity_text().map_or(0, str::len)
+        + record.event_name().map_or(0, str::len)
+        + record.target().map_or(0, |target| target.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::FutureExt;
+    use opentelemetry::trace::{Span, Tracer, TracerProvider};
+    use opentelemetry_sdk::trace::InMemorySpanExporter;
+    use std::sync::{Arc, Mutex};
+
+    /// Records the span names of each exported batch.
+    #[derive(Debug, Default, Clone)]
+    struct BatchesExporter(Arc<Mutex<Vec<Vec<String>>>>);
+
+    impl SpanExporter for BatchesExporter {
+        async fn export(&self, batch: Vec<SpanData>) -> OTelSdkResult {
+            let names = batch.into_iter().map(|span| span.name.into_owned());
+            self.0.lock().unwrap().push(names.collect());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_split_span_batches() {
+        let spans = InMemorySpanExporter::default();
+        let provider = opentelemetry_sdk::trace::SdkTracerProvider::builder()
+            .with_simple_exporter(spans.clone())
+            .build();
+        let tracer = provider.tracer("test");
+        for (name, payload) in [("a", 300), ("b", 300), ("c", 5000), ("d", 10), ("e", 10)] {
+            let mut span = tracer.start(name);
+            span.set_attribute(KeyValue::new("payload", "x".repeat(payload)));
+            span.end();
+        }
+        let batch = spans.get_finished_spans().unwrap();
+        assert!(batch.iter().all(|span| span_bytes(span) > 100));
+
+        let batches = BatchesExporter::default();
+        let exporter = SplitExporter::new(batches.clone(), Some(1000));
+        let result = exporter.export(batch.clone()).now_or_never();
+        assert!(matches!(result, Some(Ok(()))));
+        let exported = std::mem::take(&mut *batches.0.lock().unwrap());
+        assert_eq!(exported, [vec!["a", "b"], vec!["c"], vec!["d", "e"]]);
+
+        let exporter = SplitExporter::new(batches.clone(), None);
+        let result = exporter.export(batch).now_or_never();
+        assert!(matches!(result, Some(Ok(()))));
+        assert_eq!(batches.0.lock().unwrap().len(), 1);
+    }
+}
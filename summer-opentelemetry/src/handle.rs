@@ -0,0 +1,444 @@
+//! Handle of the providers installed by the plugin, registered as a component.
+
+use crate::config::OpenTelemetryConfig;
+use crate::exporter::ReloadableExporters;
+use anyhow::Context;
+use opentelemetry_sdk::logs::SdkLoggerProvider;
+use opentelemetry_sdk::metrics::SdkMeterProvider;
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex, OnceLock, PoisonError};
+use std::time::{Duration, Instant};
+use summer::error::Result;
+use tokio::task::AbortHandle;
+
+/// Handle of the tracer, meter and logger providers of the plugin.
+///
+/// The providers of disabled signals are `None`. The handle is cheap to clone and can be
+/// injected with `Component<OpenTelemetryHandle>`, e.g. to flush the telemetry at the end
+/// of each invocation in serverless runtimes where the process may be frozen afterwards.
+#[derive(Debug, Clone, Default)]
+pub struct OpenTelemetryHandle {
+    tracer_provider: Option<SdkTracerProvider>,
+    meter_provider: Option<SdkMeterProvider>,
+    logger_provider: Option<SdkLoggerProvider>,
+    tasks: Vec<AbortHandle>,
+    flush_worker: Arc<FlushWorker>,
+    exporters: ReloadableExporters,
+    /// Whether [`drain`](Self::drain) has been called, shared by the clones.
+    draining: Arc<AtomicBool>,
+}
+
+/// A flush request, answered unless its deadline passed before the worker picked it up.
+type FlushRequest = (Instant, mpsc::Sender<Result<()>>);
+
+/// The long-lived thread flushing the providers, started by the first flush, so that calls
+/// timing out against a hung collector queue up on one thread instead of leaking one each.
+#[derive(Debug, Default)]
+struct FlushWorker(OnceLock<mpsc::Sender<FlushRequest>>);
+
+impl OpenTelemetryHandle {
+    pub(crate) fn new(
+        tracer_provider: Option<SdkTracerProvider>,
+        meter_provider: Option<SdkMeterProvider>,
+        logger_provider: Option<SdkLoggerProvider>,
+    ) -> Self {
+        Self {
+            tracer_provider,
+            meter_provider,
+            logger_provider,
+            tasks: Vec::new(),
+            flush_worker: Arc::default(),
+            exporters: ReloadableExporters::default(),
+            draining: Arc::default(),
+        }
+    }
+
+    /// Shares the flag set by [`drain`](Self::drain) with the sampler of the tracer provider.
+    pub(crate) fn with_draining(mut self, draining: Arc<AtomicBool>) -> Self {
+        self.draining = draining;
+        self
+    }
+
+    /// Sets the OTLP exporters replaced by [`reload`](Self::reload).
+    pub(crate) fn with_exporters(mut self, exporters: ReloadableExporters) -> Self {
+        self.exporters = exporters;
+        self
+    }
+
+    /// Adds a background task of the plugin, aborted on shutdown.
+    pub(crate) fn with_task(mut self, task: AbortHandle) -> Self {
+        self.tasks.push(task);
+        self
+    }
+
+    /// The tracer provider, `None` if traces are disabled.
+    pub fn tracer_provider(&self) -> Option<&SdkTracerProvider> {
+        self.tracer_provider.as_ref()
+    }
+
+    /// The meter provider, `None` if metrics are disabled.
+    pub fn meter_provider(&self) -> Option<&SdkMeterProvider> {
+        self.meter_provider.as_ref()
+    }
+
+    /// The logger provider, `None` if logs are disabled.
+    pub fn logger_provider(&self) -> Option<&SdkLoggerProvider> {
+        self.logger_provider.as_ref()
+    }
+
+    /// Exports all buffered spans, metrics and log records, blocking the current thread
+    /// until they are exported or the `timeout` elapses. Use [`force_flush_async`] in async
+    /// code, so that the runtime worker isn't blocked.
+    ///
+    /// All providers are flushed even if one of them fails, the first error is returned.
+    ///
+    /// [`force_flush_async`]: Self::force_flush_async
+    pub fn force_flush(&self, timeout: Duration) -> Result<()> {
+        let deadline = Instant::now() + timeout;
+        let (tx, rx) = mpsc::channel();
+        self.flush_requests()
+            .send((deadline, tx))
+            .map_err(|_| anyhow::anyhow!("force flush thread is not running"))?;
+        rx.recv_timeout(timeout)
+            .map_err(|_| anyhow::anyhow!("force flush timed out after {timeout:?}"))?
+    }
+
+    /// Like [`force_flush`](Self::force_flush), waiting for the flush on a blocking thread of
+    /// the Tokio runtime.
+    pub async fn force_flush_async(&self, timeout: Duration) -> Result<()> {
+        let handle = self.clone();
+        tokio::task::spawn_blocking(move || handle.force_flush(timeout))
+            .await
+            .context("force flush failed")?
+    }
+
+    /// Marks the application as draining, e.g. when its pod receives `SIGTERM` during a rolling
+    /// deploy, and exports the buffered telemetry within `timeout`, without shutting the
+    /// providers down.
+    ///
+    /// From then on, the spans whose parent isn't sampled, e.g. the root spans of new requests,
+    /// are dropped. The requests in flight keep recording telemetry, exported with the next
+    /// batches or when the providers are shut down with the app, so a short `timeout` gets the
+    /// telemetry of the last seconds out before the pod is killed.
+    /// [`is_draining`](Self::is_draining) tells e.g. a readiness probe to fail.
+    pub async fn drain(&self, timeout: Duration) -> Result<()> {
+        self.draining.store(true, Ordering::Relaxed);
+        self.force_flush_async(timeout).await
+    }
+
+    /// Whether [`drain`](Self::drain) has been called.
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::Relaxed)
+    }
+
+    /// Replaces the OTLP exporters of the providers with the ones of `config`, e.g. to point the
+    /// application at another collector without a restart. Blocks the current thread like
+    /// [`force_flush`](Self::force_flush).
+    ///
+    /// The exporters are all built first, so that an invalid config changes nothing. The
+    /// buffered telemetry is then flushed to the previous exporters within the
+    /// `shutdown_timeout_ms` of `config`, and the previous exporters are shut down once
+    /// replaced. The exports in flight complete with them, so no telemetry is lost in between.
+    ///
+    /// Only the settings of the OTLP exporters apply: the endpoints, headers, protocol,
+    /// compression, TLS, timeout and retries. The providers are kept, since the layers and
+    /// the instruments created by the application use them, together with their sampler,
+    /// resource, processors and global registration. The traces exported to
+    /// `traces_endpoints` aren't reloaded, and the exporter of a signal can't be changed.
+    pub fn reload(&self, config: &OpenTelemetryConfig) -> Result<()> {
+        self.exporters.reload(config, || {
+            let timeout = Duration::from_millis(config.shutdown_timeout_ms);
+            if let Err(e) = self.force_flush(timeout) {
+                tracing::warn!("flush before the reload of the exporters failed: {e:?}");
+            }
+        })
+    }
+
+    fn flush_requests(&self) -> &mpsc::Sender<FlushRequest> {
+        self.flush_worker.0.get_or_init(|| {
+            let (requests, receiver) = mpsc::channel::<FlushRequest>();
+            // the worker doesn't hold the sender, so it stops once all handles are dropped
+            let providers = Self {
+                flush_worker: Arc::default(),
+                ..self.clone()
+            };
+            let _ = std::thread::Builder::new()
+                .name("opentelemetry-flush".into())
+                .spawn(move || {
+                    for (deadline, reply) in receiver {
+                        if Instant::now() < deadline {
+                            let _ = reply.send(providers.force_flush_all());
+                        }
+                    }
+                });
+            requests
+        })
+    }
+
+    fn force_flush_all(&self) -> Result<()> {
+        let mut results = Vec::with_capacity(3);
+        if let Some(tracer_provider) = &self.tracer_provider {
+            results.push(
+                tracer_provider
+                    .force_flush()
+                    .context("force flush tracer provider failed"),
+            );
+        }
+        if let Some(meter_provider) = &self.meter_provider {
+            results.push(
+                meter_provider
+                    .force_flush()
+                    .context("force flush meter provider failed"),
+            );
+        }
+        if let Some(logger_provider) = &self.logger_provider {
+            results.push(
+                logger_provider
+                    .force_flush()
+                    .context("force flush logger provider failed"),
+            );
+        }
+        results.into_iter().collect::<anyhow::Result<Vec<_>>>()?;
+        Ok(())
+    }
+
+    /// Shuts down the providers on a blocking thread, sharing the `timeout` between them so
+    /// that an unreachable collector doesn't stall the graceful shutdown of the application.
+    ///
+    /// All providers are shut down even if one of them fails, the first error is returned.
+    /// The periodic reader of the SDK ignores the timeout, so the shutdown stops waiting once
+    /// `timeout` elapses and warns about the providers not shut down yet.
+    pub(crate) async fn shutdown(self, timeout: Duration) -> Result<String> {
+        self.tasks.iter().for_each(AbortHandle::abort);
+        let pending = Arc::new(Mutex::new(Vec::with_capacity(3)));
+        let shutdown = tokio::task::spawn_blocking({
+            let pending = pending.clone();
+            move || self.shutdown_all(timeout, &pending)
+        });
+        match tokio::time::timeout(timeout, shutdown).await {
+            Ok(result) => result.context("shutdown OpenTelemetry providers failed")?,
+            Err(_) => {
+                let pending = pending.lock().unwrap_or_else(PoisonError::into_inner);
+                tracing::warn!(
+                    "OpenTelemetry shutdown timed out after {timeout:?}, the {} providers weren't shut down",
+                    pending.join(", ")
+                );
+                Ok("OpenTelemetry shutdown timed out".into())
+            }
+        }
+    }
+
+    /// Shuts down the providers in turn, `pending` holding the signals of the providers not
+    /// shut down yet.
+    fn shutdown_all(self, timeout: Duration, pending: &Mutex<Vec<&'static str>>) -> Result<String> {
+        let deadline = Instant::now() + timeout;
+        let remaining = || deadline.saturating_duration_since(Instant::now());
+        let shut_down = |signal: &str| {
+            let mut pending = pending.lock().unwrap_or_else(PoisonError::into_inner);
+            pending.retain(|pending| *pending != signal);
+        };
+        {
+            let mut pending = pending.lock().unwrap_or_else(PoisonError::into_inner);
+            let providers = [
+                ("tracer", self.tracer_provider.is_some()),
+                ("meter", self.meter_provider.is_some()),
+                ("logger", self.logger_provider.is_some()),
+            ];
+            pending.extend(
+                providers
+                    .into_iter()
+                    .filter_map(|(signal, set)| set.then_some(signal)),
+            );
+        }
+        let mut results = Vec::with_capacity(3);
+        if let Some(tracer_provider) = self.tracer_provider {
+            results.push(
+                tracer_provider
+                    .shutdown_with_timeout(remaining())
+                    .context("shutdown tracer provider failed"),
+            );
+            shut_down("tracer");
+        }
+        if let Some(meter_provider) = self.meter_provider {
+            results.push(
+                meter_provider
+                    .shutdown_with_timeout(remaining())
+                    .context("shutdown meter provider failed"),
+            );
+            shut_down("meter");
+        }
+        if let Some(logger_provider) = self.logger_provider {
+            results.push(
+                logger_provider
+                    .shutdown_with_timeout(remaining())
+                    .context("shutdown log provider failed"),
+            );
+            shut_down("logger");
+        }
+        results.into_iter().collect::<anyhow::Result<Vec<_>>>()?;
+        Ok("OpenTelemetry shutdown successful".into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shutdown_all_providers_despite_errors() {
+        let tracer_provider = SdkTracerProvider::builder().build();
+        let meter_provider = SdkMeterProvider::builder().build();
+        let logger_provider = SdkLoggerProvider::builder().build();
+        // shutting down twice fails
+        tracer_provider
+            .shutdown()
+            .expect("shutdown tracer provider failed");
+
+        let handle = OpenTelemetryHandle::new(
+            Some(tracer_provider),
+            Some(meter_provider.clone()),
+            Some(logger_provider.clone()),
+        );
+        let pending = Mutex::default();
+        assert!(handle
+            .shutdown_all(Duration::from_secs(1), &pending)
+            .is_err());
+        assert!(pending.lock().unwrap().is_empty());
+        assert!(meter_provider.shutdown().is_err());
+        assert!(logger_provider.shutdown().is_err());
+    }
+
+    #[test]
+    fn test_force_flush() {
+        let handle = OpenTelemetryHandle::new(
+            Some(SdkTracerProvider::builder().build()),
+            Some(SdkMeterProvider::builder().build()),
+            Some(SdkLoggerProvider::builder().build()),
+        );
+        assert!(handle.force_flush(Duration::from_secs(1)).is_ok());
+        // later calls reuse the worker
+        assert!(handle.clone().force_flush(Duration::from_secs(1)).is_ok());
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .expect("build runtime failed");
+        let result = runtime.block_on(handle.force_flush_async(Duration::from_secs(1)));
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_drain() {
+        use crate::sampler::DrainingSampler;
+        use opentelemetry::trace::{Span, TraceContextExt, Tracer, TracerProvider};
+        use opentelemetry_sdk::trace::{
+            BatchConfigBuilder, BatchSpanProcessor, InMemorySpanExporter, Sampler,
+        };
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .expect("build runtime failed");
+        let exporter = InMemorySpanExporter::default();
+        // the batches are only exported when flushed
+        let processor = BatchSpanProcessor::builder(exporter.clone())
+            .with_batch_config(
+                BatchConfigBuilder::default()
+                    .with_scheduled_delay(Duration::from_secs(3600))
+                    .build(),
+            )
+            .build();
+        let draining = Arc::new(AtomicBool::new(false));
+        let tracer_provider = SdkTracerProvider::builder()
+            .with_sampler(DrainingSampler::new(draining.clone(), Sampler::AlwaysOn))
+            .with_span_processor(processor)
+            .build();
+        let handle = OpenTelemetryHandle::new(Some(tracer_provider.clone()), None, None)
+            .with_draining(draining);
+        let tracer = tracer_provider.tracer("test");
+        tracer.in_span("before", |_| {});
+        let request = opentelemetry::Context::new().with_span(tracer.start("request"));
+        assert!(!handle.is_draining());
+
+        let result = runtime.block_on(handle.drain(Duration::from_secs(1)));
+        assert!(result.is_ok());
+        assert!(handle.is_draining());
+        assert_eq!(exporter.get_finished_spans().unwrap().len(), 1);
+
+        // the providers still record and export the telemetry of the requests in flight, the
+        // new requests aren't traced anymore
+        tracer.start_with_context("in_flight", &request).end();
+        request.span().end();
+        tracer.in_span("after", |_| {});
+        let result = runtime.block_on(handle.force_flush_async(Duration::from_secs(1)));
+        assert!(result.is_ok());
+        let names: Vec<_> = exporter
+            .get_finished_spans()
+            .unwrap()
+            .into_iter()
+            .map(|span| span.name)
+            .collect();
+        assert_eq!(names, ["before", "in_flight", "request"]);
+    }
+
+    #[test]
+    fn test_shutdown_without_providers() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .expect("build runtime failed");
+        let handle = OpenTelemetryHandle::default();
+        let result = runtime.block_on(handle.shutdown(Duration::from_secs(1)));
+        assert_eq!(
+            result.ok().as_deref(),
+            Some("OpenTelemetry shutdown successful")
+        );
+    }
+
+    #[test]
+    fn test_shutdown_timeout() {
+        use opentelemetry_sdk::error::OTelSdkResult;
+        use opentelemetry_sdk::trace::{Span, SpanData, SpanProcessor};
+
+        /// Blocks its shutdown until the test is done.
+        #[derive(Debug)]
+        struct HangingProcessor(Mutex<mpsc::Receiver<()>>);
+
+        impl SpanProcessor for HangingProcessor {
+            fn on_start(&self, _span: &mut Span, _cx: &opentelemetry::Context) {}
+
+            fn on_end(&self, _span: SpanData) {}
+
+            fn force_flush(&self) -> OTelSdkResult {
+                Ok(())
+            }
+
+            fn shutdown_with_timeout(&self, _timeout: Duration) -> OTelSdkResult {
+                let _ = self.0.lock().unwrap().recv();
+                Ok(())
+            }
+        }
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .expect("build runtime failed");
+        let (release, hang) = mpsc::channel();
+        let tracer_provider = SdkTracerProvider::builder()
+            .with_span_processor(HangingProcessor(Mutex::new(hang)))
+            .build();
+        let handle = OpenTelemetryHandle::new(
+            Some(tracer_provider),
+            Some(SdkMeterProvider::builder().build()),
+            None,
+        );
+
+        let start = Instant::now();
+        let result = runtime.block_on(handle.shutdown(Duration::from_millis(100)));
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert_eq!(
+            result.ok().as_deref(),
+            Some("OpenTelemetry shutdown timed out")
+        );
+        release.send(()).unwrap();
+    }
+}
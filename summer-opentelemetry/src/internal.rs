@@ -0,0 +1,104 @@
+//! Filtering of the internal events of the OpenTelemetry crates, e.g. the export errors, which
+//! the SDK emits as `tracing` events with the name of the crate as target.
+
+use std::collections::HashMap;
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+use tracing::callsite::Identifier;
+use tracing::{Event, Subscriber};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::Layer;
+
+/// The window of the rate limit of the internal events.
+const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(60);
+
+/// Drops the internal events below `level` and, with a rate limit, the events of a callsite
+/// beyond `rate_limit` per minute, e.g. the export error repeated on every batch while the
+/// collector is down. The events are disabled for all the layers of the subscriber.
+#[derive(Debug)]
+pub(crate) struct InternalLogsLayer {
+    level: LevelFilter,
+    rate_limit: Option<u32>,
+    /// The start of the current window and the number of events in it, by callsite.
+    windows: Mutex<HashMap<Identifier, (Instant, u32)>>,
+}
+
+impl InternalLogsLayer {
+    pub(crate) fn new(level: LevelFilter, rate_limit: Option<u32>) -> Self {
+        Self {
+            level,
+            rate_limit,
+            windows: Mutex::default(),
+        }
+    }
+
+    /// Whether one more event of `callsite` fits in the rate limit at `now`.
+    fn allow(&self, callsite: Identifier, limit: u32, now: Instant) -> bool {
+        let mut windows = self.windows.lock().unwrap_or_else(PoisonError::into_inner);
+        let (start, count) = windows.entry(callsite).or_insert((now, 0));
+        if now.saturating_duration_since(*start) >= RATE_LIMIT_WINDOW {
+            (*start, *count) = (now, 0);
+        }
+        *count = count.saturating_add(1);
+        *count <= limit
+    }
+}
+
+/// Whether the target is one of the OpenTelemetry crates, e.g. `opentelemetry_sdk` or
+/// `opentelemetry-otlp`.
+fn is_internal(target: &str) -> bool {
+    target.starts_with("opentelemetry")
+}
+
+impl<S: Subscriber> Layer<S> for InternalLogsLayer {
+    fn event_enabled(&self, event: &Event<'_>, _ctx: Context<'_, S>) -> bool {
+        let metadata = event.metadata();
+        if !is_internal(metadata.target()) {
+            return true;
+        }
+        if *metadata.level() > self.level {
+            return false;
+        }
+        match self.rate_limit {
+            Some(limit) => self.allow(metadata.callsite(), limit, Instant::now()),
+            None => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    /// Counts the events it receives.
+    #[derive(Debug, Default, Clone)]
+    struct CountingLayer(Arc<AtomicUsize>);
+
+    impl<S: Subscriber> Layer<S> for CountingLayer {
+        fn on_event(&self, _event: &Event<'_>, _ctx: Context<'_, S>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn test_internal_logs_filtered() {
+        let counting = CountingLayer::default();
+        let subscriber = tracing_subscriber::registry()
+            .with(InternalLogsLayer::new(LevelFilter::WARN, Some(2)))
+            .with(counting.clone());
+
+        tracing::subscriber::with_default(subscriber, || {
+            for _ in 0..5 {
+                tracing::error!(target: "opentelemetry_sdk", name = "BatchSpanProcessor.ExportError");
+                tracing::debug!(target: "opentelemetry-otlp", name = "TonicTracesClient.ExportStarted");
+                tracing::error!(target: "app", "payment failed");
+            }
+        });
+        // 2 export errors and the application errors
+        assert_eq!(counting.0.load(Ordering::SeqCst), 7);
+    }
+}
@@ -2,9 +2,28 @@
 #![doc(html_favicon_url = "https://summer-rs.github.io/favicon.ico")]
 #![doc(html_logo_url = "https://summer-rs.github.io/logo.svg")]
 
+pub mod builder;
+pub mod config;
+mod console;
+pub mod detector;
+mod exporter;
+pub mod handle;
+mod internal;
 pub mod metrics;
+mod processor;
+pub mod propagation;
+mod sampler;
+pub mod task;
+#[cfg(feature = "testing")]
+pub mod testing;
 pub mod trace;
 pub mod util;
+mod view;
+
+#[cfg(not(any(feature = "http", feature = "http-json", feature = "grpc")))]
+compile_error!(
+    "You must enable one of the 'http', 'http-json' or 'grpc' features for the exporters."
+);
 
 use opentelemetry_otlp::{LogExporter, MetricExporter, SpanExporter};
 #[rustfmt::skip]
@@ -29,70 +48,100 @@ pub use opentelemetry_otlp::{
     OTEL_EXPORTER_OTLP_TRACES_HEADERS,
     OTEL_EXPORTER_OTLP_TRACES_TIMEOUT,
 };
+pub use config::{
+    OTEL_SDK_DISABLED, OTEL_SERVICE_NAME, OTEL_TRACES_SAMPLER, OTEL_TRACES_SAMPLER_ARG,
+};
+pub use handle::OpenTelemetryHandle;
 pub use opentelemetry::{global, KeyValue};
+pub use opentelemetry_sdk::logs::SdkLoggerProvider;
+pub use opentelemetry_sdk::metrics::SdkMeterProvider;
+pub use opentelemetry_sdk::trace::SdkTracerProvider;
 pub use opentelemetry_sdk::Resource;
 pub use opentelemetry_semantic_conventions::resource::*;
+pub use propagation::{current_context, extract_context, extract_links, inject_context};
+pub use task::{spawn_with_context, with_request_attributes};
 
+use crate::builder::{ConfiguredOpenTelemetryPlugin, OpenTelemetryPluginBuilder, PluginOptions};
+use crate::config::{
+    EndpointsMode, ExporterKind, OpenTelemetryConfig, PropagatorKind, SpanProcessorKind,
+};
+use crate::detector::TimeoutDetector;
+use crate::exporter::{
+    build_otlp_exporter, unsupported_exporter, FailoverExporter, LazyExporter, Signal,
+};
+use crate::metrics::layer::AttributesMeterProvider;
+use crate::metrics::spans::{SpanMetrics, SpanMetricsProcessor};
+use crate::processor::{
+    AttributeRenameProcessor, AttributeValueLengthProcessor, BaggageAttributesProcessor,
+    ErrorSpanProcessor, FilterLogProcessor, HttpStatusProcessor, RedactionProcessor,
+    RequestAttributesProcessor, ServiceAttributesProcessor, SeverityProcessor, SpanNameProcessor,
+};
+use crate::sampler::{
+    DrainingSampler, ExcludedSpansSampler, RecordUnsampledSampler, RouteSampler,
+    SampledParentSampler,
+};
 use anyhow::Context;
-use opentelemetry::propagation::TextMapCompositePropagator;
+use opentelemetry::metrics::MeterProvider;
+use opentelemetry::propagation::{TextMapCompositePropagator, TextMapPropagator};
 use opentelemetry::trace::TracerProvider;
 use opentelemetry_appender_tracing::layer::OpenTelemetryTracingBridge;
-use opentelemetry_sdk::logs::SdkLoggerProvider;
-use opentelemetry_sdk::metrics::SdkMeterProvider;
+use opentelemetry_sdk::logs::{BatchLogProcessor, LogProcessor, LoggerProviderBuilder};
+use opentelemetry_sdk::metrics::exporter::PushMetricExporter;
+use opentelemetry_sdk::metrics::PeriodicReader;
 use opentelemetry_sdk::propagation::{BaggagePropagator, TraceContextPropagator};
-use opentelemetry_sdk::trace::SdkTracerProvider;
+use opentelemetry_sdk::resource::{
+    ResourceDetector, SdkProvidedResourceDetector, TelemetryResourceDetector,
+};
+use opentelemetry_sdk::trace::{BatchSpanProcessor, Sampler, SpanProcessor, TracerProviderBuilder};
 use opentelemetry_semantic_conventions::attribute;
-use schemars::JsonSchema;
-use serde::Deserialize;
-use summer::config::{ConfigRegistry, Configurable};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use summer::config::env::Env;
+use summer::config::ConfigRegistry;
 use summer::plugin::component::ComponentRef;
 use summer::plugin::{ComponentRegistry, MutableComponentRegistry};
 use summer::{app::AppBuilder, error::Result, plugin::Plugin};
 use tracing_opentelemetry::{MetricsLayer, OpenTelemetryLayer};
-
-summer::submit_config_schema!("opentelemetry", OpenTelemetryConfig);
-
-#[derive(Debug, Configurable, Clone, JsonSchema, Deserialize)]
-#[config_prefix = "opentelemetry"]
-struct OpenTelemetryConfig {
-    #[serde(default)]
-    enable: bool,
-}
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::Layer;
 
 /// Routers collection
 pub type KeyValues = Vec<KeyValue>;
 
 pub struct OpenTelemetryPlugin;
 
+/// The providers installed by [`OpenTelemetryPlugin::install_globals`], reused by the plugin.
+static INSTALLED_PROVIDERS: OnceLock<Providers> = OnceLock::new();
+
+/// The providers of the enabled signals.
+#[derive(Debug, Clone, Default)]
+struct Providers {
+    tracer_provider: Option<SdkTracerProvider>,
+    meter_provider: Option<SdkMeterProvider>,
+    logger_provider: Option<SdkLoggerProvider>,
+    span_queues: Vec<Arc<metrics::batch::SpanQueue>>,
+    exporters: exporter::ReloadableExporters,
+    /// Set by [`OpenTelemetryHandle::drain`], shared with the sampler.
+    draining: Arc<std::sync::atomic::AtomicBool>,
+    #[cfg(feature = "prometheus")]
+    prometheus_registry: Option<metrics::PrometheusRegistry>,
+    #[cfg(feature = "testing")]
+    in_memory: Option<testing::InMemoryTelemetry>,
+}
+
+impl Providers {
+    /// The telemetry captured by the `in_memory` exporters, shared by the signals.
+    #[cfg(feature = "testing")]
+    fn in_memory(&mut self) -> &mut testing::InMemoryTelemetry {
+        self.in_memory.get_or_insert_with(Default::default)
+    }
+}
+
 impl Plugin for OpenTelemetryPlugin {
     fn immediately_build(&self, app: &mut AppBuilder) {
-        let config = app
-            .get_config::<OpenTelemetryConfig>()
-            .expect("opentelemetry plugin config load failed");
-        if !config.enable {
-            return;
-        }
-        let resource = Self::build_resource(app);
-        let log_provider = Self::init_logs(resource.clone());
-        let meter_provider = Self::init_metrics(resource.clone());
-        let tracer_provider = Self::init_tracer(resource);
-
-        let tracer = tracer_provider.tracer(env!("CARGO_PKG_NAME"));
-
-        let log_layer = OpenTelemetryTracingBridge::new(&log_provider);
-        let metric_layer = MetricsLayer::new(meter_provider.clone());
-        let trace_layer = OpenTelemetryLayer::new(tracer);
-
-        app.add_layer(trace_layer)
-            .add_layer(log_layer)
-            .add_layer(metric_layer)
-            .add_shutdown_hook(move |_| {
-                Box::new(Self::shutdown(
-                    tracer_provider,
-                    meter_provider,
-                    log_provider,
-                ))
-            });
+        if let Err(e) = Self::try_build(app) {
+            panic!("opentelemetry plugin build failed: {e:?}");
+        }
     }
 
     fn immediately(&self) -> bool {
@@ -101,148 +150,1006 @@ impl Plugin for OpenTelemetryPlugin {
 }
 
 impl OpenTelemetryPlugin {
-    fn init_logs(resource: Resource) -> SdkLoggerProvider {
-        let exporter = {
-            #[cfg(feature = "http")]
-            {
-                LogExporter::builder()
-                    .with_http()
-                    .build()
-                    .expect("build http log exporter failed")
+    /// Creates a builder to customize the plugin in code, e.g. to share the service name
+    /// and sampler of an application that already sets up OpenTelemetry.
+    pub fn builder() -> OpenTelemetryPluginBuilder {
+        OpenTelemetryPluginBuilder::default()
+    }
+
+    /// The plugin setting up the metrics only, with a single exporter: the traces and logs
+    /// providers aren't built and the propagators aren't installed, whatever the config.
+    ///
+    /// ```rust,ignore
+    /// App::new().add_plugin(OpenTelemetryPlugin::metrics_only()).run().await;
+    /// ```
+    pub fn metrics_only() -> ConfiguredOpenTelemetryPlugin {
+        Self::builder().metrics_only().build()
+    }
+
+    /// Builds the providers and installs the layers, returning an error instead of panicking
+    /// when the configuration can't be loaded or an exporter can't be built.
+    pub fn try_build(app: &mut AppBuilder) -> Result<()> {
+        Self::build_with(app, &PluginOptions::default())
+    }
+
+    pub(crate) fn build_with(app: &mut AppBuilder, options: &PluginOptions) -> Result<()> {
+        Self::build_with_providers(app, options, INSTALLED_PROVIDERS.get().cloned())
+    }
+
+    fn build_with_providers(
+        app: &mut AppBuilder,
+        options: &PluginOptions,
+        installed: Option<Providers>,
+    ) -> Result<()> {
+        let mut config = app
+            .get_config::<OpenTelemetryConfig>()
+            .context("opentelemetry plugin config load failed")?;
+        if !config.enabled() {
+            tracing::debug!("opentelemetry plugin is disabled");
+            // the providers installed by install_globals are still shut down with the app
+            if let Some(providers) = installed {
+                let handle = OpenTelemetryHandle::new(
+                    providers.tracer_provider,
+                    providers.meter_provider,
+                    providers.logger_provider,
+                )
+                .with_exporters(providers.exporters)
+                .with_draining(providers.draining);
+                Self::add_handle(app, handle, &config);
             }
+            return Ok(());
+        }
+        if let Some(service_name) = &options.service_name {
+            config.service_name = Some(service_name.clone());
+        }
+        if options.metrics_only {
+            config.enable_traces = false;
+            config.enable_logs = false;
+        }
+        if config.dev_console {
+            app.add_layer(console::ConsoleLayer);
+        }
+        if config.internal_logs_level.is_some() || config.internal_logs_rate_limit.is_some() {
+            let level = config
+                .internal_logs_level
+                .map_or(LevelFilter::TRACE, Into::into);
+            let rate_limit = config
+                .internal_logs_rate_limit
+                .map(std::num::NonZeroU32::get);
+            app.add_layer(internal::InternalLogsLayer::new(level, rate_limit));
+        }
+        let providers = match installed {
+            Some(providers) => {
+                // the subscriber isn't installed yet
+                if !options.is_empty() || app.get_component::<KeyValues>().is_some() {
+                    eprintln!(
+                        "opentelemetry: the builder options and resource attributes of the app \
+                         don't apply to the providers installed by install_globals"
+                    );
+                }
+                providers
+            }
+            None => {
+                let resource = Self::build_resource(app, &config, options);
+                Self::init_providers(resource, &config, options)?
+            }
+        };
+        #[cfg(feature = "prometheus")]
+        if let Some(registry) = &providers.prometheus_registry {
+            app.add_component(registry.clone());
+        }
+        #[cfg(feature = "testing")]
+        if let Some(telemetry) = &providers.in_memory {
+            testing::set_current(telemetry.clone());
+            app.add_component(telemetry.clone());
+        }
+        let Providers {
+            tracer_provider,
+            meter_provider,
+            logger_provider: log_provider,
+            exporters,
+            draining,
+            ..
+        } = providers;
 
-            #[cfg(all(not(feature = "http"), feature = "grpc"))]
-            {
-                LogExporter::builder()
-                    .with_tonic()
-                    .build()
-                    .expect("build grpc log exporter failed")
+        if let Some(tracer_provider) = &tracer_provider {
+            app.add_component(tracer_provider.clone());
+            app.add_component(trace::HistoricalTracer::new(
+                tracer_provider,
+                config.instrumentation_scope(),
+            ));
+            let tracer = tracer_provider.tracer_with_scope(config.instrumentation_scope());
+            app.add_layer(OpenTelemetryLayer::new(tracer));
+        }
+        if let Some(log_provider) = &log_provider {
+            // log records get the trace id and span id of the span recorded by the trace layer
+            app.add_component(log_provider.clone());
+            let layer = OpenTelemetryTracingBridge::new(log_provider);
+            match config.logs_level {
+                Some(level) => app.add_layer(layer.with_filter(LevelFilter::from(level))),
+                None => app.add_layer(layer),
+            };
+        }
+        if let Some(meter_provider) = &meter_provider {
+            app.add_component(meter_provider.clone());
+            app.add_component(metrics::Metrics::new(
+                meter_provider,
+                config.instrumentation_scope(),
+            ));
+            if config.metrics_layer_attributes.is_empty() {
+                app.add_layer(MetricsLayer::new(meter_provider.clone()));
+            } else {
+                let attributes = config
+                    .metrics_layer_attributes
+                    .iter()
+                    .map(|(key, value)| KeyValue::new(key.clone(), value.clone()))
+                    .collect();
+                app.add_layer(MetricsLayer::new(AttributesMeterProvider::new(
+                    meter_provider.clone(),
+                    attributes,
+                )));
             }
+        }
 
-            #[cfg(not(any(feature = "http", feature = "grpc")))]
-            compile_error!(
-                "You must enable either the 'http' or 'grpc' feature for the log exporter."
-            );
-        };
-        SdkLoggerProvider::builder()
-            .with_resource(resource)
-            .with_batch_exporter(exporter)
-            .build()
+        let mut handle =
+            OpenTelemetryHandle::new(tracer_provider, meter_provider.clone(), log_provider)
+                .with_exporters(exporters)
+                .with_draining(draining);
+        if let Some(meter_provider) = meter_provider.filter(|_| config.process_metrics) {
+            let meter = meter_provider.meter(env!("CARGO_PKG_NAME"));
+            let interval = Duration::from_millis(config.process_metrics_interval_ms.max(1));
+            handle = handle.with_task(metrics::process::start(&meter, interval));
+        }
+        if config.exporter_diagnostics {
+            let exported = [
+                (Signal::Traces, handle.tracer_provider().is_some()),
+                (Signal::Metrics, handle.meter_provider().is_some()),
+                (Signal::Logs, handle.logger_provider().is_some()),
+            ];
+            let signals = exported
+                .into_iter()
+                .filter(|(signal, exported)| {
+                    *exported && config.exporter(*signal) == ExporterKind::Otlp
+                })
+                .map(|(signal, _)| signal)
+                .collect::<Vec<_>>();
+            if !signals.is_empty() {
+                let interval =
+                    Duration::from_millis(config.exporter_diagnostics_interval_ms.max(1));
+                handle = handle.with_task(exporter::start_diagnostics(signals, interval));
+            }
+        }
+        Self::add_handle(app, handle, &config);
+        Ok(())
     }
 
-    fn init_metrics(resource: Resource) -> SdkMeterProvider {
-        let exporter = {
-            #[cfg(feature = "http")]
-            {
-                MetricExporter::builder()
-                    .with_http()
-                    .build()
-                    .expect("build http metric exporter failed")
+    /// Registers the handle as a component and shuts its providers down with the app.
+    fn add_handle(app: &mut AppBuilder, handle: OpenTelemetryHandle, config: &OpenTelemetryConfig) {
+        app.add_component(handle.clone());
+        let shutdown_timeout = Duration::from_millis(config.shutdown_timeout_ms);
+        app.add_shutdown_hook(move |_| {
+            exporter::stop_retries();
+            Box::new(handle.shutdown(shutdown_timeout))
+        });
+    }
+
+    /// Builds the providers from `config` and installs them as the global providers right away,
+    /// decoupled from the registration of the layers and components of the plugin.
+    ///
+    /// The plugin installs the global providers when it's added to the app, so the spans and
+    /// metrics recorded with the global APIs before that use no-op providers. Calling this first,
+    /// e.g. at the start of `main`, lets the code running earlier record them too.
+    ///
+    /// The plugin then reuses these providers and shuts them down with the app, even when the
+    /// app config disables it. The exporters, sampler and resource come from `config` only: the
+    /// builder options and the [`KeyValues`] component don't apply to them, and the app config
+    /// only sets up the layers and components, e.g. `logs_level` and `process_metrics`.
+    /// The `deployment.environment.name` is derived from `SUMMER_ENV`.
+    pub fn install_globals(config: &OpenTelemetryConfig) -> Result<()> {
+        if !config.enabled() {
+            tracing::debug!("opentelemetry plugin is disabled");
+            return Ok(());
+        }
+        let already_installed = || anyhow::anyhow!("opentelemetry providers are already installed");
+        if INSTALLED_PROVIDERS.get().is_some() {
+            return Err(already_installed().into());
+        }
+        let options = PluginOptions::default();
+        let resource = Self::resource(KeyValues::default(), Env::from_env(), config, &options);
+        let providers = Self::init_providers(resource, config, &options)?;
+        INSTALLED_PROVIDERS
+            .set(providers)
+            .map_err(|_| already_installed())?;
+        Ok(())
+    }
+
+    fn init_providers(
+        resource: Resource,
+        config: &OpenTelemetryConfig,
+        options: &PluginOptions,
+    ) -> Result<Providers> {
+        // the propagators are used by the integrations and `inject_context` regardless of
+        // whether the spans are exported, unless the app only records metrics
+        if !options.metrics_only {
+            Self::init_propagator(config)?;
+        }
+        let mut providers = Providers {
+            exporters: exporter::ReloadableExporters::new(options),
+            ..Providers::default()
+        };
+        if config.enable_logs {
+            let config = &config.for_signal(Signal::Logs);
+            let resource = Self::signal_resource(&resource, Signal::Logs, config);
+            let logger_provider = Self::init_logs(&mut providers, resource, config, options);
+            providers.logger_provider =
+                Self::tolerate_init_error(logger_provider, Signal::Logs, config)?;
+        }
+        if config.enable_metrics {
+            let config = &config.for_signal(Signal::Metrics);
+            let resource = Self::signal_resource(&resource, Signal::Metrics, config);
+            let meter_provider = Self::init_metrics(&mut providers, resource, config, options);
+            providers.meter_provider =
+                Self::tolerate_init_error(meter_provider, Signal::Metrics, config)?;
+        }
+        if config.enable_traces {
+            let config = &config.for_signal(Signal::Traces);
+            let resource = Self::signal_resource(&resource, Signal::Traces, config);
+            let tracer_provider = Self::init_tracer(&mut providers, resource, config, options);
+            providers.tracer_provider =
+                Self::tolerate_init_error(tracer_provider, Signal::Traces, config)?;
+        }
+        #[cfg(feature = "testing")]
+        if config.exporter(Signal::Metrics) == ExporterKind::InMemory {
+            // the captured metrics are collected on demand
+            let meter_provider = providers.meter_provider.clone();
+            providers.in_memory().meter_provider = meter_provider;
+        }
+        if let Some(meter_provider) = &providers.meter_provider {
+            if !providers.span_queues.is_empty() {
+                let meter = meter_provider.meter(env!("CARGO_PKG_NAME"));
+                metrics::batch::register(&meter, providers.span_queues.clone());
             }
+        }
+        Ok(providers)
+    }
 
-            #[cfg(all(not(feature = "http"), feature = "grpc"))]
-            {
-                MetricExporter::builder()
-                    .with_tonic()
-                    .build()
-                    .expect("build grpc metric exporter failed")
+    /// Lets the app start without the provider of the signal when its exporter can't be built,
+    /// unless `fail_on_export_error` is set. Invalid configs, e.g. a compression or propagator
+    /// whose feature isn't enabled, always fail the build.
+    fn tolerate_init_error<P>(
+        provider: Result<P>,
+        signal: Signal,
+        config: &OpenTelemetryConfig,
+    ) -> Result<Option<P>> {
+        match provider {
+            Ok(provider) => Ok(Some(provider)),
+            Err(e) if !config.fail_on_export_error && exporter::is_transport_error(&e) => {
+                // the plugin is built before the log subscriber is installed
+                eprintln!("{signal} are not exported, the exporter build failed: {e:?}");
+                Ok(None)
             }
+            Err(e) => Err(e),
+        }
+    }
 
-            #[cfg(not(any(feature = "http", feature = "grpc")))]
-            compile_error!(
-                "You must enable either the 'http' or 'grpc' feature for the log exporter."
-            );
+    fn init_logs(
+        providers: &mut Providers,
+        resource: Resource,
+        config: &OpenTelemetryConfig,
+        options: &PluginOptions,
+    ) -> Result<SdkLoggerProvider> {
+        let mut builder = SdkLoggerProvider::builder().with_resource(resource.clone());
+        if let Some(mapping) = &config.logs_severity {
+            builder = builder.with_log_processor(SeverityProcessor::new(mapping));
+        }
+        if config.logs_include_service_attributes {
+            builder = builder.with_log_processor(ServiceAttributesProcessor::new(&resource));
+        }
+        builder = builder.with_log_processor(RequestAttributesProcessor);
+        for processor in options.log_processors.take() {
+            builder = builder.with_log_processor(processor);
+        }
+        let builder = match config.exporter(Signal::Logs) {
+            ExporterKind::Otlp => {
+                let exporter = Self::otlp_exporter(Signal::Logs, config, {
+                    let (config, options) = (config.clone(), options.clone());
+                    move || {
+                        build_otlp_exporter!(
+                            Signal::Logs,
+                            LogExporter::builder(),
+                            &config,
+                            &options
+                        )
+                    }
+                })?;
+                let exporter = providers.exporters.logs(exporter);
+                let processor = BatchLogProcessor::builder(exporter)
+                    .with_batch_config(config.log_batch_config())
+                    .build();
+                Self::with_log_processor(builder, processor, options)
+            }
+            #[cfg(feature = "stdout")]
+            ExporterKind::Stdout => {
+                let exporter = opentelemetry_stdout::LogExporter::default();
+                let processor = opentelemetry_sdk::logs::SimpleLogProcessor::new(exporter);
+                Self::with_log_processor(builder, processor, options)
+            }
+            ExporterKind::Logfmt => {
+                let processor =
+                    opentelemetry_sdk::logs::SimpleLogProcessor::new(console::LogfmtExporter);
+                Self::with_log_processor(builder, processor, options)
+            }
+            #[cfg(feature = "testing")]
+            ExporterKind::InMemory => {
+                let processor = opentelemetry_sdk::logs::SimpleLogProcessor::new(
+                    providers.in_memory().log_exporter(),
+                );
+                Self::with_log_processor(builder, processor, options)
+            }
+            #[allow(unreachable_patterns)]
+            exporter => return Err(unsupported_exporter(Signal::Logs, exporter).into()),
         };
+        Ok(builder.build())
+    }
 
-        let provider = SdkMeterProvider::builder()
-            .with_resource(resource)
-            .with_periodic_exporter(exporter)
-            .build();
+    /// Adds the processor exporting the log records, behind the log filter set in code.
+    fn with_log_processor<P: LogProcessor + 'static>(
+        builder: LoggerProviderBuilder,
+        processor: P,
+        options: &PluginOptions,
+    ) -> LoggerProviderBuilder {
+        match &options.log_filter {
+            Some(filter) => {
+                builder.with_log_processor(FilterLogProcessor::new(filter.clone(), processor))
+            }
+            None => builder.with_log_processor(processor),
+        }
+    }
+
+    fn init_metrics(
+        providers: &mut Providers,
+        resource: Resource,
+        config: &OpenTelemetryConfig,
+        options: &PluginOptions,
+    ) -> Result<SdkMeterProvider> {
+        let mut builder = SdkMeterProvider::builder().with_resource(resource);
+        if let Some(view) = view::build_view(
+            &config.metrics_views,
+            config.metrics_cardinality_limit,
+            config.metrics_namespace.as_deref(),
+        )? {
+            builder = builder.with_view(view);
+        }
+        let builder = match config.exporter(Signal::Metrics) {
+            ExporterKind::Otlp => {
+                let exporter = Self::otlp_exporter(Signal::Metrics, config, {
+                    let (config, options) = (config.clone(), options.clone());
+                    move || {
+                        build_otlp_exporter!(
+                            Signal::Metrics,
+                            MetricExporter::builder()
+                                .with_temporality(config.metrics_temporality.into()),
+                            &config,
+                            &options
+                        )
+                    }
+                })?;
+                let exporter = providers.exporters.metrics(exporter);
+                builder.with_reader(Self::periodic_reader(exporter, config))
+            }
+            #[cfg(feature = "stdout")]
+            ExporterKind::Stdout => {
+                let exporter = opentelemetry_stdout::MetricExporter::builder()
+                    .with_temporality(config.metrics_temporality.into())
+                    .build();
+                builder.with_reader(Self::periodic_reader(exporter, config))
+            }
+            #[cfg(feature = "prometheus")]
+            ExporterKind::Prometheus => {
+                if config.metrics_temporality != crate::config::MetricsTemporality::Cumulative {
+                    return Err(anyhow::anyhow!(
+                        "the prometheus exporter only exports cumulative metrics, \
+                         metrics_temporality must be `cumulative`"
+                    )
+                    .into());
+                }
+                let registry = prometheus::Registry::new();
+                let exporter = opentelemetry_prometheus::exporter()
+                    .with_registry(registry.clone())
+                    .build()
+                    .context("build metrics exporter failed (exporter: prometheus)")?;
+                providers.prometheus_registry = Some(metrics::PrometheusRegistry::new(registry));
+                builder.with_reader(exporter)
+            }
+            #[cfg(feature = "testing")]
+            ExporterKind::InMemory => {
+                let temporality = config.metrics_temporality.into();
+                let exporter = providers.in_memory().metric_exporter(temporality);
+                builder.with_reader(Self::periodic_reader(exporter, config))
+            }
+            #[allow(unreachable_patterns)]
+            exporter => return Err(unsupported_exporter(Signal::Metrics, exporter).into()),
+        };
+        let provider = builder.build();
 
         global::set_meter_provider(provider.clone());
         tracing::debug!("metrics provider installed");
 
-        provider
+        Ok(provider)
     }
 
-    fn init_tracer(resource: Resource) -> SdkTracerProvider {
-        let exporter = {
-            #[cfg(feature = "http")]
-            {
-                SpanExporter::builder()
-                    .with_http()
-                    .build()
-                    .expect("build http span exporter failed")
-            }
+    /// The periodic reader of a push exporter, exporting every `metrics_export_interval_ms`.
+    fn periodic_reader<E: PushMetricExporter>(
+        exporter: E,
+        config: &OpenTelemetryConfig,
+    ) -> PeriodicReader<E> {
+        let builder = PeriodicReader::builder(exporter);
+        match config.metrics_export_interval_ms {
+            Some(interval_ms) => builder
+                .with_interval(Duration::from_millis(interval_ms))
+                .build(),
+            None => builder.build(),
+        }
+    }
 
-            #[cfg(all(not(feature = "http"), feature = "grpc"))]
-            {
-                SpanExporter::builder()
-                    .with_tonic()
-                    .build()
-                    .expect("build grpc span exporter failed")
+    fn init_tracer(
+        providers: &mut Providers,
+        resource: Resource,
+        config: &OpenTelemetryConfig,
+        options: &PluginOptions,
+    ) -> Result<SdkTracerProvider> {
+        let service_name = resource
+            .get(&opentelemetry::Key::from_static_str(
+                attribute::SERVICE_NAME,
+            ))
+            .map(|name| name.to_string());
+        let mut builder = SdkTracerProvider::builder()
+            .with_resource(resource)
+            .with_span_limits(config.span_limits())
+            .with_span_processor(RequestAttributesProcessor);
+        if !config.baggage_to_span_attributes.is_empty() {
+            builder = builder.with_span_processor(BaggageAttributesProcessor::new(
+                config.baggage_to_span_attributes.clone(),
+            ));
+        }
+        let mut span_metrics = None;
+        if let Some(span_metrics_config) = &config.span_metrics {
+            if !config.enable_metrics {
+                return Err(
+                    anyhow::anyhow!("`span_metrics` requires the metrics to be enabled").into(),
+                );
             }
+            // the metrics aren't recorded when their exporter failed to build
+            if let Some(meter_provider) = &providers.meter_provider {
+                let meter = meter_provider.meter(env!("CARGO_PKG_NAME"));
+                span_metrics = Some(SpanMetrics::new(&meter, &span_metrics_config.dimensions));
+            }
+        }
+        let builder = options
+            .span_processors
+            .take()
+            .into_iter()
+            .fold(builder, |builder, processor| {
+                builder.with_span_processor(processor)
+            });
+        let mut builder = match config.exporter(Signal::Traces) {
+            ExporterKind::Otlp => match Self::trace_endpoints(config, options) {
+                Some(endpoints) => {
+                    let exporters = endpoints
+                        .iter()
+                        .map(|endpoint| {
+                            let config = config.with_traces_endpoint(endpoint);
+                            Self::otlp_exporter(Signal::Traces, &config.clone(), {
+                                let options = options.clone();
+                                move || {
+                                    build_otlp_exporter!(
+                                        Signal::Traces,
+                                        SpanExporter::builder(),
+                                        &config,
+                                        &options
+                                    )
+                                }
+                            })
+                        })
+                        .collect::<anyhow::Result<Vec<_>>>()?;
+                    match config.traces_endpoints_mode {
+                        EndpointsMode::Mirror => {
+                            exporters.into_iter().fold(builder, |builder, exporter| {
+                                Self::with_exporter_span_processor(
+                                    builder,
+                                    exporter,
+                                    config,
+                                    &mut providers.span_queues,
+                                    &mut span_metrics,
+                                )
+                            })
+                        }
+                        EndpointsMode::Failover => Self::with_exporter_span_processor(
+                            builder,
+                            FailoverExporter::new(exporters),
+                            config,
+                            &mut providers.span_queues,
+                            &mut span_metrics,
+                        ),
+                    }
+                }
+                None => {
+                    let exporter = Self::otlp_exporter(Signal::Traces, config, {
+                        let (config, options) = (config.clone(), options.clone());
+                        move || {
+                            build_otlp_exporter!(
+                                Signal::Traces,
+                                SpanExporter::builder(),
+                                &config,
+                                &options
+                            )
+                        }
+                    })?;
+                    let exporter = providers.exporters.traces(exporter);
+                    Self::with_exporter_span_processor(
+                        builder,
+                        exporter,
+                        config,
+                        &mut providers.span_queues,
+                        &mut span_metrics,
+                    )
+                }
+            },
+            #[cfg(feature = "stdout")]
+            ExporterKind::Stdout => {
+                let exporter = opentelemetry_stdout::SpanExporter::default();
+                let processor = opentelemetry_sdk::trace::SimpleSpanProcessor::new(exporter);
+                Self::with_span_processor(builder, processor, config, &mut span_metrics)
+            }
+            #[cfg(feature = "testing")]
+            ExporterKind::InMemory => {
+                let exporter = providers.in_memory().span_exporter();
+                let processor = opentelemetry_sdk::trace::SimpleSpanProcessor::new(exporter);
+                Self::with_span_processor(builder, processor, config, &mut span_metrics)
+            }
+            #[allow(unreachable_patterns)]
+            exporter => return Err(unsupported_exporter(Signal::Traces, exporter).into()),
+        };
 
-            #[cfg(not(any(feature = "http", feature = "grpc")))]
-            compile_error!(
-                "You must enable either the 'http' or 'grpc' feature for the log exporter."
-            );
+        let sampler = match &options.sampler {
+            Some(sampler) => sampler.clone(),
+            None => Self::build_sampler(config, service_name)?,
+        };
+        // the upstream sampling decision is respected whatever the configured sampler
+        let sampler = SampledParentSampler::new(sampler);
+        let sampler = DrainingSampler::new(providers.draining.clone(), sampler);
+        let excluded_paths = config.excluded_span_paths.clone().unwrap_or_default();
+        let excluded_names = config.excluded_span_names.clone();
+        let excluded = !excluded_paths.is_empty() || !excluded_names.is_empty();
+        builder = match (excluded, config.keep_error_spans) {
+            (true, true) => builder.with_sampler(ExcludedSpansSampler::new(
+                excluded_paths,
+                excluded_names,
+                RecordUnsampledSampler::new(sampler),
+            )),
+            (true, false) => builder.with_sampler(ExcludedSpansSampler::new(
+                excluded_paths,
+                excluded_names,
+                sampler,
+            )),
+            (false, true) => builder.with_sampler(RecordUnsampledSampler::new(sampler)),
+            (false, false) => builder.with_sampler(sampler),
         };
+        let provider = builder.build();
 
-        global::set_text_map_propagator(TextMapCompositePropagator::new(vec![
-            Box::new(BaggagePropagator::new()),
-            Box::new(TraceContextPropagator::new()),
-        ]));
-        #[cfg(feature = "jaeger")]
-        global::set_text_map_propagator(opentelemetry_jaeger_propagator::Propagator::new());
-        #[cfg(feature = "zipkin")]
-        global::set_text_map_propagator(opentelemetry_zipkin::Propagator::new());
+        global::set_tracer_provider(provider.clone());
+        tracing::debug!("tracer provider installed");
 
-        let provider = SdkTracerProvider::builder()
-            .with_resource(resource)
-            .with_batch_exporter(exporter)
+        Ok(provider)
+    }
+
+    /// Adds the `span_processor` of the exporter, the batch span processor counting the spans
+    /// dropped when its queue is full for the `batch_metrics`.
+    fn with_exporter_span_processor<E: opentelemetry_sdk::trace::SpanExporter + 'static>(
+        builder: TracerProviderBuilder,
+        exporter: E,
+        config: &OpenTelemetryConfig,
+        span_queues: &mut Vec<Arc<metrics::batch::SpanQueue>>,
+        span_metrics: &mut Option<SpanMetrics>,
+    ) -> TracerProviderBuilder {
+        if config.span_processor == SpanProcessorKind::Simple {
+            let processor = opentelemetry_sdk::trace::SimpleSpanProcessor::new(exporter);
+            return Self::with_span_processor(builder, processor, config, span_metrics);
+        }
+        if !(config.batch_metrics && config.enable_metrics) {
+            let processor = BatchSpanProcessor::builder(exporter)
+                .with_batch_config(config.trace_batch_config())
+                .build();
+            return Self::with_span_processor(builder, processor, config, span_metrics);
+        }
+        let queue = Arc::new(metrics::batch::SpanQueue::default());
+        span_queues.push(queue.clone());
+        let exporter = metrics::batch::QueueExporter::new(queue.clone(), exporter);
+        let processor = BatchSpanProcessor::builder(exporter)
+            .with_batch_config(config.trace_batch_config())
             .build();
+        let processor =
+            metrics::batch::QueueProcessor::new(config.max_queue_size(), queue, processor);
+        Self::with_span_processor(builder, processor, config, span_metrics)
+    }
 
-        global::set_tracer_provider(provider.clone());
-        tracing::debug!("tracer provider installed");
+    /// Adds a span processor, truncating the attribute values to `max_attribute_value_length`
+    /// and normalizing the span names to the `span_name_templates` when configured.
+    ///
+    /// The `span_metrics` are recorded right before the processor, once all the other
+    /// processors changed the spans, and taken by the first processor so that the spans
+    /// mirrored to several endpoints are only counted once.
+    fn with_span_processor<P: SpanProcessor + 'static>(
+        builder: TracerProviderBuilder,
+        processor: P,
+        config: &OpenTelemetryConfig,
+        span_metrics: &mut Option<SpanMetrics>,
+    ) -> TracerProviderBuilder {
+        match span_metrics.take() {
+            Some(metrics) => Self::with_attribute_length_processor(
+                builder,
+                SpanMetricsProcessor::new(metrics, processor),
+                config,
+            ),
+            None => Self::with_attribute_length_processor(builder, processor, config),
+        }
+    }
+
+    fn with_attribute_length_processor<P: SpanProcessor + 'static>(
+        builder: TracerProviderBuilder,
+        processor: P,
+        config: &OpenTelemetryConfig,
+    ) -> TracerProviderBuilder {
+        match config.max_attribute_value_length {
+            Some(max_length) => Self::with_span_name_processor(
+                builder,
+                AttributeValueLengthProcessor::new(max_length, processor),
+                config,
+            ),
+            None => Self::with_span_name_processor(builder, processor, config),
+        }
+    }
+
+    fn with_span_name_processor<P: SpanProcessor + 'static>(
+        builder: TracerProviderBuilder,
+        processor: P,
+        config: &OpenTelemetryConfig,
+    ) -> TracerProviderBuilder {
+        match config.span_name_templates.clone().filter(|t| !t.is_empty()) {
+            Some(templates) => Self::with_rename_processor(
+                builder,
+                SpanNameProcessor::new(templates, processor),
+                config,
+            ),
+            None => Self::with_rename_processor(builder, processor, config),
+        }
+    }
+
+    /// Renames the attributes of the `rename_attributes`, after their redaction.
+    fn with_rename_processor<P: SpanProcessor + 'static>(
+        builder: TracerProviderBuilder,
+        processor: P,
+        config: &OpenTelemetryConfig,
+    ) -> TracerProviderBuilder {
+        if config.rename_attributes.is_empty() {
+            Self::with_redaction_processor(builder, processor, config)
+        } else {
+            let renames = config.rename_attributes.clone();
+            let processor = AttributeRenameProcessor::new(renames, processor);
+            Self::with_redaction_processor(builder, processor, config)
+        }
+    }
+
+    fn with_redaction_processor<P: SpanProcessor + 'static>(
+        builder: TracerProviderBuilder,
+        processor: P,
+        config: &OpenTelemetryConfig,
+    ) -> TracerProviderBuilder {
+        match config.redact_attributes.clone().filter(|k| !k.is_empty()) {
+            Some(keys) => Self::with_error_span_processor(
+                builder,
+                RedactionProcessor::new(keys, processor),
+                config,
+            ),
+            None => Self::with_error_span_processor(builder, processor, config),
+        }
+    }
+
+    /// Drops the unsampled spans recorded for `keep_error_spans` unless they ended with an
+    /// error, before the other processors handle them.
+    fn with_error_span_processor<P: SpanProcessor + 'static>(
+        builder: TracerProviderBuilder,
+        processor: P,
+        config: &OpenTelemetryConfig,
+    ) -> TracerProviderBuilder {
+        if config.keep_error_spans {
+            Self::with_http_status_processor(builder, ErrorSpanProcessor::new(processor), config)
+        } else {
+            Self::with_http_status_processor(builder, processor, config)
+        }
+    }
+
+    /// Marks the spans of the `http_error_status` responses as errors, before `keep_error_spans`
+    /// checks their status.
+    fn with_http_status_processor<P: SpanProcessor + 'static>(
+        builder: TracerProviderBuilder,
+        processor: P,
+        config: &OpenTelemetryConfig,
+    ) -> TracerProviderBuilder {
+        match config.http_error_status {
+            Some(status) => builder.with_span_processor(HttpStatusProcessor::new(
+                status.min_status_code(),
+                processor,
+            )),
+            None => builder.with_span_processor(processor),
+        }
+    }
+
+    /// The OTLP exporter of `signal` built by `build`, on its first export with `lazy_init`.
+    fn otlp_exporter<E>(
+        signal: Signal,
+        config: &OpenTelemetryConfig,
+        build: impl FnOnce() -> anyhow::Result<E> + Send + 'static,
+    ) -> anyhow::Result<LazyExporter<E>> {
+        let temporality = config.metrics_temporality.into();
+        if config.lazy_init {
+            Ok(LazyExporter::new(signal, temporality, build))
+        } else {
+            Ok(LazyExporter::ready(signal, temporality, build()?))
+        }
+    }
+
+    /// The sampler of the root spans built from `OTEL_TRACES_SAMPLER`, or `sampler_ratio`, the
+    /// `jaeger_remote_sampler` and `sampling_rules`, child spans follow the sampling decision
+    /// of their parent. The remote sampler polls the strategy of `service_name` by default.
+    fn build_sampler(
+        config: &OpenTelemetryConfig,
+        service_name: Option<String>,
+    ) -> Result<Sampler> {
+        let mut default = match (config.env_traces_sampler(), config.sampler_ratio()) {
+            (Some(sampler), _) => sampler,
+            (None, Some(ratio)) => Sampler::TraceIdRatioBased(ratio),
+            (None, None) => Sampler::AlwaysOn,
+        };
+        if let Some(remote) = &config.jaeger_remote_sampler {
+            let service_name = remote.service_name.clone().or(service_name);
+            let service_name = service_name.unwrap_or_default();
+            default = Self::jaeger_remote_sampler(remote, &service_name, default)?;
+        }
+        let rules = config.sampling_rules();
+        if rules.is_empty() {
+            Ok(Sampler::ParentBased(Box::new(default)))
+        } else {
+            Ok(Sampler::ParentBased(Box::new(RouteSampler::new(
+                rules, default,
+            ))))
+        }
+    }
+
+    #[cfg(feature = "jaeger-remote-sampler")]
+    fn jaeger_remote_sampler(
+        remote: &crate::config::JaegerRemoteSamplerConfig,
+        service_name: &str,
+        default: Sampler,
+    ) -> Result<Sampler> {
+        Ok(sampler::jaeger_remote_sampler(
+            remote,
+            service_name,
+            default,
+        )?)
+    }
 
-        provider
+    #[cfg(not(feature = "jaeger-remote-sampler"))]
+    fn jaeger_remote_sampler(
+        _remote: &crate::config::JaegerRemoteSamplerConfig,
+        _service_name: &str,
+        _default: Sampler,
+    ) -> Result<Sampler> {
+        Err(
+            anyhow::anyhow!("jaeger_remote_sampler requires the `jaeger-remote-sampler` feature")
+                .into(),
+        )
     }
 
-    fn build_resource(app: &AppBuilder) -> Resource {
-        let mut key_values = app.get_component::<KeyValues>().unwrap_or_default();
+    /// The `traces_endpoints` of the config, unless the trace endpoint is set in code or by the env vars.
+    fn trace_endpoints<'a>(
+        config: &'a OpenTelemetryConfig,
+        options: &PluginOptions,
+    ) -> Option<&'a [String]> {
+        if options.exporter_endpoint.is_some() || Signal::Traces.endpoint_from_env() {
+            return None;
+        }
+        config
+            .traces_endpoints
+            .as_deref()
+            .filter(|endpoints| !endpoints.is_empty())
+    }
+
+    fn init_propagator(config: &OpenTelemetryConfig) -> Result<()> {
+        let propagator = Self::build_text_map_propagator(config)?;
+        if config.set_global_propagator {
+            #[cfg(test)]
+            let _lock = tests::lock_global_propagator();
+            global::set_text_map_propagator(propagator);
+        } else {
+            tracing::debug!("the global propagator is left as is");
+        }
+        Ok(())
+    }
+
+    fn build_text_map_propagator(
+        config: &OpenTelemetryConfig,
+    ) -> Result<TextMapCompositePropagator> {
+        let propagators = config
+            .propagators()
+            .into_iter()
+            .map(Self::build_propagator)
+            .collect::<Result<Vec<_>>>()?;
+        Ok(TextMapCompositePropagator::new(propagators))
+    }
+
+    fn build_propagator(
+        propagator: PropagatorKind,
+    ) -> Result<Box<dyn TextMapPropagator + Send + Sync>> {
+        Ok(match propagator {
+            PropagatorKind::TraceContext => Box::new(TraceContextPropagator::new()),
+            PropagatorKind::Baggage => Box::new(BaggagePropagator::new()),
+            #[cfg(any(feature = "b3", feature = "zipkin"))]
+            PropagatorKind::B3 => Box::new(opentelemetry_zipkin::Propagator::with_encoding(
+                opentelemetry_zipkin::B3Encoding::SingleHeader,
+            )),
+            #[cfg(any(feature = "b3", feature = "zipkin"))]
+            PropagatorKind::B3Multi => Box::new(opentelemetry_zipkin::Propagator::with_encoding(
+                opentelemetry_zipkin::B3Encoding::MultipleHeader,
+            )),
+            #[cfg(feature = "jaeger")]
+            PropagatorKind::Jaeger => Box::new(opentelemetry_jaeger_propagator::Propagator::new()),
+            #[cfg(feature = "datadog")]
+            PropagatorKind::Datadog => Box::new(propagation::DatadogPropagator),
+            #[allow(unreachable_patterns)]
+            propagator => {
+                return Err(anyhow::anyhow!(
+                    "propagator `{propagator}` requires the corresponding feature"
+                )
+                .into())
+            }
+        })
+    }
+
+    fn build_resource(
+        app: &AppBuilder,
+        config: &OpenTelemetryConfig,
+        options: &PluginOptions,
+    ) -> Resource {
+        let key_values = app.get_component::<KeyValues>().unwrap_or_default();
+        Self::resource(key_values, app.get_env(), config, options)
+    }
+
+    fn resource(
+        mut key_values: KeyValues,
+        env: Env,
+        config: &OpenTelemetryConfig,
+        options: &PluginOptions,
+    ) -> Resource {
+        if let Some(resource_attributes) = &config.resource_attributes {
+            key_values.extend(
+                resource_attributes
+                    .iter()
+                    .map(|(key, value)| KeyValue::new(key.clone(), value.clone())),
+            );
+        }
+        key_values.extend(options.resource_attributes.iter().cloned());
+        // the SDK lets the `service.name` of OTEL_RESOURCE_ATTRIBUTES override OTEL_SERVICE_NAME
+        if let Some(service_name) = config.service_name() {
+            key_values.push(KeyValue::new(attribute::SERVICE_NAME, service_name));
+        }
+        if let Some(service_version) = &config.service_version {
+            key_values.push(KeyValue::new(
+                attribute::SERVICE_VERSION,
+                service_version.clone(),
+            ));
+        }
+        if let Some(commit_sha) = config.commit_sha() {
+            key_values.push(KeyValue::new(attribute::VCS_REF_HEAD_REVISION, commit_sha));
+        }
+        let deployment_environment = config
+            .deployment_environment
+            .clone()
+            .or_else(|| {
+                let var = config.deployment_environment_env_var.as_deref()?;
+                std::env::var(var).ok().filter(|value| !value.is_empty())
+            })
+            .unwrap_or_else(|| Self::deployment_environment_name(env).to_string());
         key_values.push(KeyValue::new(
             attribute::DEPLOYMENT_ENVIRONMENT_NAME,
-            format!("{:?}", app.get_env()),
+            deployment_environment,
         ));
-        let mut builder = Resource::builder();
+        #[allow(unused_mut)]
+        let mut detectors: Vec<Arc<dyn ResourceDetector + Send + Sync>> = vec![
+            #[cfg(feature = "k8s")]
+            Arc::new(detector::K8sResourceDetector),
+            #[cfg(feature = "aws")]
+            Arc::new(detector::AwsResourceDetector),
+            #[cfg(feature = "gcp")]
+            Arc::new(detector::GcpResourceDetector),
+        ];
         #[cfg(feature = "more-resource")]
         {
-            builder = builder.with_detectors(&[
-                Box::new(opentelemetry_resource_detectors::HostResourceDetector::default()),
-                Box::new(opentelemetry_resource_detectors::OsResourceDetector),
-                Box::new(opentelemetry_resource_detectors::ProcessResourceDetector),
-            ]);
+            use opentelemetry_resource_detectors::{
+                HostResourceDetector, OsResourceDetector, ProcessResourceDetector,
+            };
+            let infra_detectors: [(bool, Arc<dyn ResourceDetector + Send + Sync>); 3] = [
+                (
+                    config.detect_host,
+                    Arc::new(HostResourceDetector::default()),
+                ),
+                (config.detect_os, Arc::new(OsResourceDetector)),
+                (config.detect_process, Arc::new(ProcessResourceDetector)),
+            ];
+            let infra_detectors = infra_detectors
+                .into_iter()
+                .filter(|(enabled, _)| *enabled)
+                .map(|(_, detector)| detector);
+            detectors.splice(0..0, infra_detectors);
         }
-        builder = builder.with_attributes(key_values);
+        detectors.extend(options.resource_detectors.iter().cloned());
+        let mut builder = if config.detect_env {
+            Resource::builder()
+        } else {
+            Resource::builder_empty().with_detectors(&[
+                Box::new(SdkProvidedResourceDetector),
+                Box::new(TelemetryResourceDetector),
+            ])
+        };
+        if !detectors.is_empty() {
+            let timeout = Duration::from_millis(config.resource_detection_timeout_ms);
+            builder = builder.with_detector(Box::new(TimeoutDetector::new(detectors, timeout)));
+        }
+        let resource = builder.with_attributes(key_values).build();
+        if config.resource_schema_url.is_empty() {
+            return resource;
+        }
+        // the builder merges the attributes given with a schema URL under the detected ones
+        let attributes = resource
+            .iter()
+            .map(|(key, value)| KeyValue::new(key.clone(), value.clone()));
+        Resource::builder_empty()
+            .with_schema_url(attributes, config.resource_schema_url.clone())
+            .build()
+    }
+
+    /// The resource shared by all signals, extended with the resource attributes of `signal`.
+    fn signal_resource(
+        resource: &Resource,
+        signal: Signal,
+        config: &OpenTelemetryConfig,
+    ) -> Resource {
+        let Some(signal_attributes) = config.signal_resource_attributes(signal) else {
+            return resource.clone();
+        };
+        let attributes = resource
+            .iter()
+            .map(|(key, value)| KeyValue::new(key.clone(), value.clone()))
+            .chain(
+                signal_attributes
+                    .iter()
+                    .map(|(key, value)| KeyValue::new(key.clone(), value.clone())),
+            );
+        let builder = Resource::builder_empty();
+        let builder = match resource.schema_url() {
+            Some(schema_url) => builder.with_schema_url(attributes, schema_url.to_string()),
+            None => builder.with_attributes(attributes),
+        };
         builder.build()
     }
 
-    async fn shutdown(
-        tracer_provider: SdkTracerProvider,
-        meter_provider: SdkMeterProvider,
-        log_provider: SdkLoggerProvider,
-    ) -> Result<String> {
-        tracer_provider
-            .shutdown()
-            .context("shutdown tracer provider failed")?;
-        meter_provider
-            .shutdown()
-            .context("shutdown meter provider failed")?;
-        log_provider
-            .shutdown()
-            .context("shutdown log provider failed")?;
-        Ok("OpenTelemetry shutdown successful".into())
+    /// The canonical `deployment.environment.name` of an application environment.
+    fn deployment_environment_name(env: Env) -> &'static str {
+        match env {
+            Env::Dev => "development",
+            Env::Test => "test",
+            Env::Prod => "production",
+        }
     }
 }
 
@@ -271,3 +1178,751 @@ impl ResourceConfigurator for AppBuilder {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry::baggage::BaggageExt;
+    use serde::Deserialize;
+    use std::cell::Cell;
+    use std::collections::HashMap;
+    use std::sync::{Mutex, MutexGuard, PoisonError};
+
+    /// Held while the global propagator is set, and by the tests using it until they are done.
+    static GLOBAL_PROPAGATOR: Mutex<()> = Mutex::new(());
+
+    thread_local! {
+        static HOLDS_GLOBAL_PROPAGATOR: Cell<bool> = const { Cell::new(false) };
+    }
+
+    /// The lock of the global propagator, a no-op when the thread already holds it, e.g. when
+    /// a test holding it builds the plugin.
+    pub(crate) struct GlobalPropagatorGuard(Option<MutexGuard<'static, ()>>);
+
+    impl Drop for GlobalPropagatorGuard {
+        fn drop(&mut self) {
+            if self.0.is_some() {
+                HOLDS_GLOBAL_PROPAGATOR.set(false);
+            }
+        }
+    }
+
+    pub(crate) fn lock_global_propagator() -> GlobalPropagatorGuard {
+        if HOLDS_GLOBAL_PROPAGATOR.get() {
+            return GlobalPropagatorGuard(None);
+        }
+        let guard = GLOBAL_PROPAGATOR
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        HOLDS_GLOBAL_PROPAGATOR.set(true);
+        GlobalPropagatorGuard(Some(guard))
+    }
+
+    /// Installs the propagators of `config` as the global propagator, which is left as is by the
+    /// other tests until the guard is dropped.
+    pub(crate) fn install_propagator(config: &OpenTelemetryConfig) -> GlobalPropagatorGuard {
+        let guard = lock_global_propagator();
+        OpenTelemetryPlugin::init_propagator(config).expect("init propagator failed");
+        guard
+    }
+
+    #[test]
+    fn test_resource_attributes_precedence() {
+        let mut app = AppBuilder::default();
+        app.opentelemetry_attrs([
+            KeyValue::new("team", "checkout"),
+            KeyValue::new("host.role", "api"),
+        ]);
+        let config = OpenTelemetryConfig::deserialize(toml::toml! {
+            service_name = "payments"
+            resource_attributes = { team = "payments", "service.name" = "ignored" }
+        })
+        .expect("config deserialize failed");
+
+        let resource =
+            OpenTelemetryPlugin::build_resource(&app, &config, &PluginOptions::default());
+        let get = |key: &'static str| resource.get(&opentelemetry::Key::new(key));
+        assert_eq!(get("team"), Some("payments".into()));
+        assert_eq!(get("host.role"), Some("api".into()));
+        assert_eq!(get(attribute::SERVICE_NAME), Some("payments".into()));
+        assert_eq!(
+            get(attribute::DEPLOYMENT_ENVIRONMENT_NAME),
+            Some("development".into())
+        );
+    }
+
+    #[test]
+    fn test_custom_resource_detector() {
+        struct CatalogDetector;
+
+        impl ResourceDetector for CatalogDetector {
+            fn detect(&self) -> Resource {
+                Resource::builder_empty()
+                    .with_attributes([
+                        KeyValue::new("service.owner", "payments-team"),
+                        KeyValue::new("team", "catalog"),
+                    ])
+                    .build()
+            }
+        }
+
+        let config = OpenTelemetryConfig::deserialize(toml::toml! {
+            resource_attributes = { team = "payments" }
+        })
+        .expect("config deserialize failed");
+        let mut options = PluginOptions::default();
+        options.resource_detectors.push(CatalogDetector);
+        let resource =
+            OpenTelemetryPlugin::resource(KeyValues::default(), Env::Prod, &config, &options);
+        let get = |key: &'static str| resource.get(&opentelemetry::Key::new(key));
+        assert_eq!(get("service.owner"), Some("payments-team".into()));
+        assert_eq!(get("team"), Some("payments".into()));
+    }
+
+    #[test]
+    fn test_otel_service_name_precedence() {
+        std::env::set_var(OTEL_SERVICE_NAME, "checkout");
+        let service_name = |config: toml::Table, key_values: KeyValues| {
+            let config =
+                OpenTelemetryConfig::deserialize(config).expect("config deserialize failed");
+            let resource = OpenTelemetryPlugin::resource(
+                key_values,
+                Env::Prod,
+                &config,
+                &PluginOptions::default(),
+            );
+            resource.get(&opentelemetry::Key::new(attribute::SERVICE_NAME))
+        };
+        let key_values = vec![KeyValue::new(
+            attribute::SERVICE_NAME,
+            "from-resource-attributes",
+        )];
+
+        // OTEL_SERVICE_NAME overrides the `service.name` resource attribute
+        assert_eq!(
+            service_name(toml::toml! { enable = true }, key_values.clone()),
+            Some("checkout".into())
+        );
+        // the configured service name overrides OTEL_SERVICE_NAME
+        assert_eq!(
+            service_name(toml::toml! { service_name = "payments" }, key_values),
+            Some("payments".into())
+        );
+        std::env::remove_var(OTEL_SERVICE_NAME);
+    }
+
+    #[test]
+    fn test_detect_env() {
+        std::env::set_var("OTEL_RESOURCE_ATTRIBUTES", "summer.test.detect_env=true");
+        let detected = |config: toml::Table| {
+            let config =
+                OpenTelemetryConfig::deserialize(config).expect("config deserialize failed");
+            let resource = OpenTelemetryPlugin::resource(
+                KeyValues::default(),
+                Env::Prod,
+                &config,
+                &PluginOptions::default(),
+            );
+            let telemetry_sdk =
+                resource.get(&opentelemetry::Key::new(attribute::TELEMETRY_SDK_NAME));
+            assert_eq!(telemetry_sdk, Some("opentelemetry".into()));
+            resource
+                .get(&opentelemetry::Key::new("summer.test.detect_env"))
+                .is_some()
+        };
+        assert!(detected(toml::toml! { enable = true }));
+        assert!(!detected(toml::toml! { detect_env = false }));
+        std::env::remove_var("OTEL_RESOURCE_ATTRIBUTES");
+    }
+
+    #[test]
+    fn test_resource_schema_url() {
+        let schema_url = |config: toml::Table| {
+            let config =
+                OpenTelemetryConfig::deserialize(config).expect("config deserialize failed");
+            let resource = OpenTelemetryPlugin::resource(
+                KeyValues::default(),
+                Env::Prod,
+                &config,
+                &PluginOptions::default(),
+            );
+            resource.schema_url().map(str::to_string)
+        };
+        assert_eq!(
+            schema_url(toml::toml! { enable = true }).as_deref(),
+            Some(opentelemetry_semantic_conventions::SCHEMA_URL)
+        );
+        assert_eq!(
+            schema_url(
+                toml::toml! { resource_schema_url = "https://opentelemetry.io/schemas/1.26.0" }
+            )
+            .as_deref(),
+            Some("https://opentelemetry.io/schemas/1.26.0")
+        );
+        assert_eq!(schema_url(toml::toml! { resource_schema_url = "" }), None);
+    }
+
+    #[cfg(feature = "more-resource")]
+    #[test]
+    fn test_detect_process() {
+        let config = OpenTelemetryConfig::deserialize(toml::toml! { detect_process = false })
+            .expect("config deserialize failed");
+        let resource = OpenTelemetryPlugin::resource(
+            KeyValues::default(),
+            Env::Prod,
+            &config,
+            &PluginOptions::default(),
+        );
+        assert!(resource
+            .get(&opentelemetry::Key::new(attribute::OS_TYPE))
+            .is_some());
+        assert!(resource
+            .get(&opentelemetry::Key::new(attribute::PROCESS_PID))
+            .is_none());
+    }
+
+    #[test]
+    fn test_simple_span_processor() {
+        use opentelemetry::trace::Tracer;
+        use opentelemetry_sdk::trace::InMemorySpanExporter;
+
+        let config = OpenTelemetryConfig::deserialize(toml::toml! {
+            span_processor = "simple"
+            batch_metrics = true
+        })
+        .expect("config deserialize failed");
+        let exporter = InMemorySpanExporter::default();
+        let mut span_queues = Vec::new();
+        let provider = OpenTelemetryPlugin::with_exporter_span_processor(
+            SdkTracerProvider::builder(),
+            exporter.clone(),
+            &config,
+            &mut span_queues,
+            &mut None,
+        )
+        .build();
+        let tracer = provider.tracer("test");
+        for _ in 0..3 {
+            tracer.in_span("work", |_| {});
+        }
+        // exported when they end, without a flush
+        assert_eq!(exporter.get_finished_spans().unwrap().len(), 3);
+        assert!(span_queues.is_empty());
+    }
+
+    #[test]
+    fn test_span_metrics_of_http_errors() {
+        use opentelemetry::metrics::MeterProvider;
+        use opentelemetry::trace::{Span, Tracer};
+        use opentelemetry_sdk::metrics::data::{AggregatedMetrics, MetricData};
+        use opentelemetry_sdk::metrics::{InMemoryMetricExporter, PeriodicReader};
+        use opentelemetry_sdk::trace::InMemorySpanExporter;
+
+        let config = OpenTelemetryConfig::deserialize(toml::toml! {
+            span_processor = "simple"
+            http_error_status = "5xx"
+            span_name_templates = ["/users/{id}"]
+        })
+        .expect("config deserialize failed");
+        let metrics = InMemoryMetricExporter::default();
+        let meter_provider = SdkMeterProvider::builder()
+            .with_reader(PeriodicReader::builder(metrics.clone()).build())
+            .build();
+        let dimensions = ["span.name", "status.code"].map(String::from);
+        let mut span_metrics = Some(SpanMetrics::new(&meter_provider.meter("test"), &dimensions));
+        let provider = OpenTelemetryPlugin::with_exporter_span_processor(
+            SdkTracerProvider::builder(),
+            InMemorySpanExporter::default(),
+            &config,
+            &mut Vec::new(),
+            &mut span_metrics,
+        )
+        .build();
+        assert!(span_metrics.is_none());
+        let tracer = provider.tracer("test");
+        for status_code in [200, 500] {
+            let mut span = tracer.start("GET /users/42");
+            span.set_attribute(KeyValue::new("http.response.status_code", status_code));
+            span.end();
+        }
+        meter_provider.force_flush().expect("flush failed");
+
+        let metrics = metrics.get_finished_metrics().unwrap();
+        let calls = metrics[0]
+            .scope_metrics()
+            .flat_map(|scope| scope.metrics())
+            .find(|metric| metric.name() == "traces.span.metrics.calls")
+            .expect("calls not recorded");
+        let AggregatedMetrics::U64(MetricData::Sum(calls)) = calls.data() else {
+            panic!("calls is not a u64 sum");
+        };
+        let mut points: Vec<_> = calls
+            .data_points()
+            .map(|point| {
+                let mut attributes: Vec<_> = point.attributes().cloned().collect();
+                attributes.sort_by(|a, b| a.key.cmp(&b.key));
+                (attributes, point.value())
+            })
+            .collect();
+        points.sort_by_key(|(attributes, _)| attributes[1].value.to_string());
+        let point = |status_code: &'static str| {
+            let attributes = vec![
+                KeyValue::new("span.name", "GET /users/{id}"),
+                KeyValue::new("status.code", status_code),
+            ];
+            (attributes, 1)
+        };
+        assert_eq!(
+            points,
+            [point("STATUS_CODE_ERROR"), point("STATUS_CODE_UNSET")]
+        );
+    }
+
+    #[test]
+    fn test_commit_sha_env_var() {
+        std::env::set_var("SUMMER_OTEL_TEST_COMMIT", "4f2a9c1e\n");
+        let config = OpenTelemetryConfig::deserialize(toml::toml! {
+            commit_sha_env_var = "SUMMER_OTEL_TEST_COMMIT"
+        })
+        .expect("config deserialize failed");
+        let resource = OpenTelemetryPlugin::resource(
+            KeyValues::default(),
+            Env::Prod,
+            &config,
+            &PluginOptions::default(),
+        );
+        let revision = opentelemetry::Key::new(attribute::VCS_REF_HEAD_REVISION);
+        assert_eq!(resource.get(&revision), Some("4f2a9c1e".into()));
+
+        let config = OpenTelemetryConfig::deserialize(toml::toml! {
+            commit_sha_env_var = "SUMMER_OTEL_TEST_UNSET_COMMIT"
+        })
+        .expect("config deserialize failed");
+        let resource = OpenTelemetryPlugin::resource(
+            KeyValues::default(),
+            Env::Prod,
+            &config,
+            &PluginOptions::default(),
+        );
+        assert_eq!(resource.get(&revision), None);
+    }
+
+    #[test]
+    fn test_deployment_environment_env_var() {
+        std::env::set_var("SUMMER_OTEL_TEST_APP_ENV", "staging-eu");
+        let config = OpenTelemetryConfig::deserialize(toml::toml! {
+            deployment_environment_env_var = "SUMMER_OTEL_TEST_APP_ENV"
+        })
+        .expect("config deserialize failed");
+        let resource = OpenTelemetryPlugin::resource(
+            KeyValues::default(),
+            Env::Prod,
+            &config,
+            &PluginOptions::default(),
+        );
+        assert_eq!(
+            resource.get(&opentelemetry::Key::new(
+                attribute::DEPLOYMENT_ENVIRONMENT_NAME
+            )),
+            Some("staging-eu".into())
+        );
+
+        // without the env var, the active env is used
+        let config = OpenTelemetryConfig::deserialize(toml::toml! {
+            deployment_environment_env_var = "SUMMER_OTEL_TEST_UNSET_ENV"
+        })
+        .expect("config deserialize failed");
+        let resource = OpenTelemetryPlugin::resource(
+            KeyValues::default(),
+            Env::Prod,
+            &config,
+            &PluginOptions::default(),
+        );
+        assert_eq!(
+            resource.get(&opentelemetry::Key::new(
+                attribute::DEPLOYMENT_ENVIRONMENT_NAME
+            )),
+            Some("production".into())
+        );
+    }
+
+    #[test]
+    fn test_signal_resource_attributes() {
+        let config = OpenTelemetryConfig::deserialize(toml::toml! {
+            service_name = "payments"
+            logs_resource_attributes = { "log.source" = "app" }
+        })
+        .expect("config deserialize failed");
+        let resource = OpenTelemetryPlugin::resource(
+            KeyValues::default(),
+            Env::Dev,
+            &config,
+            &PluginOptions::default(),
+        );
+        let log_source = opentelemetry::Key::new("log.source");
+
+        let logs_resource = OpenTelemetryPlugin::signal_resource(&resource, Signal::Logs, &config);
+        assert_eq!(logs_resource.get(&log_source), Some("app".into()));
+        assert_eq!(
+            logs_resource.get(&opentelemetry::Key::new(attribute::SERVICE_NAME)),
+            Some("payments".into())
+        );
+        let traces_resource =
+            OpenTelemetryPlugin::signal_resource(&resource, Signal::Traces, &config);
+        assert_eq!(traces_resource.get(&log_source), None);
+    }
+
+    #[test]
+    fn test_builder_options_override_config() {
+        let app = AppBuilder::default();
+        let config = OpenTelemetryConfig::deserialize(toml::toml! {
+            resource_attributes = { team = "payments" }
+        })
+        .expect("config deserialize failed");
+        let options = PluginOptions {
+            exporter_endpoint: Some("http://collector:4318/".into()),
+            resource_attributes: vec![KeyValue::new("team", "checkout")],
+            ..Default::default()
+        };
+
+        let resource = OpenTelemetryPlugin::build_resource(&app, &config, &options);
+        assert_eq!(
+            resource.get(&opentelemetry::Key::new("team")),
+            Some("checkout".into())
+        );
+        assert_eq!(
+            Signal::Traces.provided_endpoint(config::OtlpProtocol::HttpProtobuf, &config, &options),
+            Some("http://collector:4318/v1/traces".into())
+        );
+        assert_eq!(
+            Signal::Traces.provided_endpoint(config::OtlpProtocol::Grpc, &config, &options),
+            Some("http://collector:4318/".into())
+        );
+    }
+
+    #[test]
+    fn test_metrics_only() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("build runtime failed");
+        let _guard = runtime.enter();
+
+        let mut app = AppBuilder::default();
+        app.use_config_str("[opentelemetry]\nenable = true\nenable_traces = true");
+        OpenTelemetryPlugin::metrics_only()
+            .try_build(&mut app)
+            .expect("plugin build failed");
+        assert!(app.get_component::<SdkMeterProvider>().is_some());
+        assert!(app.get_component::<SdkTracerProvider>().is_none());
+        assert!(app.get_component::<SdkLoggerProvider>().is_none());
+        let handle = app.get_component::<OpenTelemetryHandle>().unwrap();
+        assert!(handle.tracer_provider().is_none());
+    }
+
+    #[test]
+    fn test_config_endpoint() {
+        let config = OpenTelemetryConfig::deserialize(toml::toml! {
+            endpoint = "http://collector:4318"
+        })
+        .expect("config deserialize failed");
+        let options = PluginOptions::default();
+
+        assert_eq!(
+            Signal::Logs.provided_endpoint(config::OtlpProtocol::HttpJson, &config, &options),
+            Some("http://collector:4318/v1/logs".into())
+        );
+        assert_eq!(
+            Signal::Logs.provided_endpoint(config::OtlpProtocol::Grpc, &config, &options),
+            Some("http://collector:4318".into())
+        );
+
+        let config = OpenTelemetryConfig::deserialize(toml::toml! {
+            endpoint = "http://collector:4318"
+            metrics_endpoint = "https://metrics.example.com/otlp/metrics"
+        })
+        .expect("config deserialize failed");
+        assert_eq!(
+            Signal::Metrics.provided_endpoint(
+                config::OtlpProtocol::HttpProtobuf,
+                &config,
+                &options
+            ),
+            Some("https://metrics.example.com/otlp/metrics".into())
+        );
+        assert_eq!(
+            Signal::Traces.provided_endpoint(config::OtlpProtocol::HttpProtobuf, &config, &options),
+            Some("http://collector:4318/v1/traces".into())
+        );
+
+        // the entries of `traces_endpoints` are full endpoints like `traces_endpoint`
+        let options = PluginOptions::default();
+        assert_eq!(
+            Signal::Traces.provided_endpoint(
+                config::OtlpProtocol::HttpProtobuf,
+                &config.with_traces_endpoint("https://backup.example.com/otlp/traces"),
+                &options
+            ),
+            Some("https://backup.example.com/otlp/traces".into())
+        );
+    }
+
+    #[test]
+    fn test_log_records_carry_span_context() {
+        use opentelemetry::logs::AnyValue;
+        use opentelemetry_sdk::logs::InMemoryLogExporter;
+        use opentelemetry_sdk::trace::InMemorySpanExporter;
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let log_exporter = InMemoryLogExporter::default();
+        let logger_provider = SdkLoggerProvider::builder()
+            .with_simple_exporter(log_exporter.clone())
+            .build();
+        let span_exporter = InMemorySpanExporter::default();
+        let tracer_provider = SdkTracerProvider::builder()
+            .with_simple_exporter(span_exporter.clone())
+            .build();
+        let subscriber = tracing_subscriber::registry()
+            .with(OpenTelemetryLayer::new(tracer_provider.tracer("test")))
+            .with(OpenTelemetryTracingBridge::new(&logger_provider));
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!("outside");
+            tracing::info_span!("request").in_scope(|| tracing::info!("inside"));
+        });
+
+        let spans = span_exporter
+            .get_finished_spans()
+            .expect("get spans failed");
+        let logs = log_exporter.get_emitted_logs().expect("get logs failed");
+        let trace_context = |body: &'static str| {
+            logs.iter()
+                .find(|log| log.record.body() == Some(&AnyValue::from(body)))
+                .and_then(|log| log.record.trace_context())
+                .map(|cx| (cx.trace_id, cx.span_id))
+        };
+        assert_eq!(trace_context("outside"), None);
+        assert_eq!(
+            trace_context("inside"),
+            Some((
+                spans[0].span_context.trace_id(),
+                spans[0].span_context.span_id()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_logs_level() {
+        use opentelemetry::logs::AnyValue;
+        use opentelemetry_sdk::logs::InMemoryLogExporter;
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let config = OpenTelemetryConfig::deserialize(toml::toml! { logs_level = "info" })
+            .expect("config deserialize failed");
+        let log_exporter = InMemoryLogExporter::default();
+        let logger_provider = SdkLoggerProvider::builder()
+            .with_simple_exporter(log_exporter.clone())
+            .build();
+        let level = LevelFilter::from(config.logs_level.expect("logs_level not set"));
+        let subscriber = tracing_subscriber::registry()
+            .with(OpenTelemetryTracingBridge::new(&logger_provider).with_filter(level));
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::debug!("debug");
+            tracing::info!("info");
+            tracing::warn!("warn");
+        });
+
+        let logs = log_exporter.get_emitted_logs().expect("get logs failed");
+        let bodies = logs
+            .iter()
+            .filter_map(|log| log.record.body().cloned())
+            .collect::<Vec<_>>();
+        assert_eq!(bodies, [AnyValue::from("info"), AnyValue::from("warn")]);
+    }
+
+    #[test]
+    fn test_tolerate_init_error() {
+        let transport_error = || {
+            let error = opentelemetry_otlp::ExporterBuildError::ThreadSpawnFailed;
+            Err::<(), _>(
+                anyhow::Error::new(error)
+                    .context("build traces exporter failed")
+                    .into(),
+            )
+        };
+        let config_error = || {
+            Err::<(), _>(anyhow::anyhow!("compression `zstd` requires the `zstd` feature").into())
+        };
+        let config = OpenTelemetryConfig::deserialize(toml::toml! { enable = true })
+            .expect("config deserialize failed");
+        assert!(matches!(
+            OpenTelemetryPlugin::tolerate_init_error(transport_error(), Signal::Traces, &config),
+            Ok(None)
+        ));
+        assert!(
+            OpenTelemetryPlugin::tolerate_init_error(config_error(), Signal::Traces, &config)
+                .is_err()
+        );
+
+        let config = OpenTelemetryConfig::deserialize(toml::toml! { fail_on_export_error = true })
+            .expect("config deserialize failed");
+        assert!(OpenTelemetryPlugin::tolerate_init_error(
+            transport_error(),
+            Signal::Traces,
+            &config
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_propagator_installed_without_traces() {
+        let config = OpenTelemetryConfig::deserialize(toml::toml! {
+            enable_traces = false
+            enable_metrics = false
+            enable_logs = false
+        })
+        .expect("config deserialize failed");
+        let _propagator = lock_global_propagator();
+        let providers = OpenTelemetryPlugin::init_providers(
+            Resource::builder_empty().build(),
+            &config,
+            &PluginOptions::default(),
+        )
+        .expect("init providers failed");
+        assert!(providers.tracer_provider.is_none());
+
+        let cx = opentelemetry::Context::new().with_baggage([KeyValue::new("tenant.id", "acme")]);
+        let mut headers = HashMap::new();
+        global::get_text_map_propagator(|propagator| propagator.inject_context(&cx, &mut headers));
+        assert_eq!(
+            headers.get("baggage").map(String::as_str),
+            Some("tenant.id=acme")
+        );
+    }
+
+    #[test]
+    fn test_baggage_round_trip() {
+        let config = OpenTelemetryConfig::deserialize(toml::toml! { enable = true })
+            .expect("config deserialize failed");
+        let propagator = OpenTelemetryPlugin::build_text_map_propagator(&config)
+            .expect("build propagator failed");
+
+        let incoming = HashMap::from([
+            (
+                "traceparent".to_string(),
+                "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01".to_string(),
+            ),
+            ("baggage".to_string(), "tenant.id=acme".to_string()),
+        ]);
+        let cx = propagator.extract(&incoming);
+        assert_eq!(
+            cx.baggage().get("tenant.id").map(|v| v.to_string()),
+            Some("acme".to_string())
+        );
+
+        let mut outgoing = HashMap::new();
+        propagator.inject_context(&cx, &mut outgoing);
+        assert_eq!(
+            outgoing.get("baggage").map(String::as_str),
+            Some("tenant.id=acme")
+        );
+    }
+
+    #[test]
+    fn test_build_with_installed_providers() {
+        let tracer_provider = SdkTracerProvider::builder().build();
+        let installed = Providers {
+            tracer_provider: Some(tracer_provider),
+            ..Default::default()
+        };
+
+        let mut app = AppBuilder::default();
+        app.use_config_str("[opentelemetry]\nenable = true");
+        OpenTelemetryPlugin::build_with_providers(
+            &mut app,
+            &PluginOptions::default(),
+            Some(installed.clone()),
+        )
+        .expect("plugin build failed");
+        assert!(app.get_component::<SdkTracerProvider>().is_some());
+        assert!(app.get_component::<SdkMeterProvider>().is_none());
+
+        // disabled in the app config, the installed providers are still shut down with the app
+        let mut app = AppBuilder::default();
+        app.use_config_str("[opentelemetry]\nenable = false");
+        OpenTelemetryPlugin::build_with_providers(
+            &mut app,
+            &PluginOptions::default(),
+            Some(installed),
+        )
+        .expect("plugin build failed");
+        assert!(app.get_component::<SdkTracerProvider>().is_none());
+        let handle = app
+            .get_component::<OpenTelemetryHandle>()
+            .expect("handle not registered");
+        assert!(handle.tracer_provider().is_some());
+    }
+
+    #[test]
+    fn test_build_with_disabled_metrics() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("build runtime failed");
+        let _guard = runtime.enter();
+
+        let mut app = AppBuilder::default();
+        app.use_config_str("[opentelemetry]\nenable = true\nenable_metrics = false");
+        OpenTelemetryPlugin::build_with_providers(&mut app, &PluginOptions::default(), None)
+            .expect("plugin build failed");
+        assert!(app.get_component::<SdkTracerProvider>().is_some());
+        assert!(app.get_component::<SdkLoggerProvider>().is_some());
+        assert!(app.get_component::<SdkMeterProvider>().is_none());
+        assert!(app.get_component::<metrics::Metrics>().is_none());
+
+        // the shutdown hook only shuts down the created providers
+        let handle = app
+            .get_component::<OpenTelemetryHandle>()
+            .expect("handle not registered");
+        assert!(handle.meter_provider().is_none());
+        let result = runtime.block_on(handle.shutdown(Duration::from_secs(1)));
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_reload_exporters() {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .expect("build runtime failed");
+        let _guard = runtime.enter();
+
+        let mut app = AppBuilder::default();
+        app.use_config_str(
+            "[opentelemetry]\nenable = true\nendpoint = \"http://127.0.0.1:1\"\nshutdown_timeout_ms = 100",
+        );
+        OpenTelemetryPlugin::build_with_providers(&mut app, &PluginOptions::default(), None)
+            .expect("plugin build failed");
+        let handle = app
+            .get_component::<OpenTelemetryHandle>()
+            .expect("handle not registered");
+
+        let reload = |config: toml::Table| {
+            let config =
+                OpenTelemetryConfig::deserialize(config).expect("config deserialize failed");
+            handle.reload(&config)
+        };
+        let result = reload(toml::toml! {
+            endpoint = "http://127.0.0.1:2"
+            shutdown_timeout_ms = 100
+        });
+        assert!(result.is_ok(), "{result:?}");
+
+        let result = reload(toml::toml! { traces_exporter = "stdout" });
+        let error = result.expect_err("the exporter can't be changed");
+        assert!(error
+            .to_string()
+            .contains("traces exporter can't be changed"));
+    }
+}
@@ -1,6 +1,18 @@
 //! Middleware that adds metrics to a [`Service`] that handles HTTP requests.
 //! refs: https://opentelemetry.io/docs/specs/semconv/http/http-metrics/
 
+pub(crate) mod batch;
+mod instruments;
+pub(crate) mod layer;
+pub(crate) mod process;
+#[cfg(feature = "prometheus")]
+mod prometheus;
+pub(crate) mod spans;
+
+pub use crate::metrics::instruments::Metrics;
+#[cfg(feature = "prometheus")]
+pub use crate::metrics::prometheus::PrometheusRegistry;
+
 use crate::util::http as http_util;
 use http::{Request, Response};
 use http_body::Body;
@@ -310,3 +322,150 @@ impl ResponseMetricState {
         &self.attributes[..self.active_requests_attributes]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::OpenTelemetryConfig;
+    use crate::sampler::SampledParentSampler;
+    use crate::OpenTelemetryPlugin;
+    use futures_util::FutureExt;
+    use opentelemetry::metrics::MeterProvider;
+    use opentelemetry::trace::TracerProvider;
+    use opentelemetry_sdk::metrics::data::{AggregatedMetrics, MetricData};
+    use opentelemetry_sdk::metrics::{InMemoryMetricExporter, PeriodicReader, SdkMeterProvider};
+    use opentelemetry_sdk::trace::{InMemorySpanExporter, SdkTracerProvider};
+    use serde::Deserialize;
+    use std::convert::Infallible;
+    use tower_layer::Layer;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    /// Responds `200 OK` to every request, counting them with the `MetricsLayer`.
+    #[derive(Debug, Clone)]
+    struct Ok200;
+
+    impl Service<Request<String>> for Ok200 {
+        type Response = Response<String>;
+        type Error = Infallible;
+        type Future = std::future::Ready<Result<Self::Response, Self::Error>>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, _req: Request<String>) -> Self::Future {
+            tracing::info!(monotonic_counter.orders.listed = 1_u64);
+            std::future::ready(Ok(Response::new("ok".to_string())))
+        }
+    }
+
+    #[test]
+    fn test_metrics_independent_of_trace_sampling() {
+        let config = OpenTelemetryConfig::deserialize(toml::toml! { sampler_ratio = 0.01 })
+            .expect("config deserialize failed");
+        let span_exporter = InMemorySpanExporter::default();
+        let tracer_provider = SdkTracerProvider::builder()
+            .with_sampler(SampledParentSampler::new(
+                OpenTelemetryPlugin::build_sampler(&config, None).expect("build sampler failed"),
+            ))
+            .with_simple_exporter(span_exporter.clone())
+            .build();
+        let metric_exporter = InMemoryMetricExporter::default();
+        let meter_provider = SdkMeterProvider::builder()
+            .with_reader(PeriodicReader::builder(metric_exporter.clone()).build())
+            .build();
+
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(tracer_provider.tracer("test")))
+            .with(tracing_opentelemetry::MetricsLayer::new(
+                meter_provider.clone(),
+            ));
+        let meter = meter_provider.meter("test");
+        let mut service = crate::trace::HttpLayer::server(tracing::Level::INFO)
+            .layer(HttpLayer::server(&meter).layer(Ok200));
+        tracing::subscriber::with_default(subscriber, || {
+            for _ in 0..1000 {
+                let request = Request::get("/orders").body(String::new()).unwrap();
+                let response = service.call(request).now_or_never();
+                assert!(matches!(response, Some(Ok(_))));
+            }
+        });
+        meter_provider.force_flush().expect("flush failed");
+
+        // about 1% of the requests are traced, all of them are counted
+        let traced = span_exporter.get_finished_spans().unwrap().len();
+        assert!(traced < 100, "{traced} requests traced");
+        let metrics = metric_exporter.get_finished_metrics().unwrap();
+        let metric = |name: &str| {
+            metrics[0]
+                .scope_metrics()
+                .flat_map(|scope| scope.metrics())
+                .find(|metric| metric.name() == name)
+                .unwrap_or_else(|| panic!("{name} not recorded"))
+        };
+        let AggregatedMetrics::F64(MetricData::Histogram(histogram)) =
+            metric(HTTP_SERVER_REQUEST_DURATION).data()
+        else {
+            panic!("request duration is not a f64 histogram");
+        };
+        let requests: u64 = histogram.data_points().map(|point| point.count()).sum();
+        assert_eq!(requests, 1000);
+        let AggregatedMetrics::U64(MetricData::Sum(sum)) = metric("orders.listed").data() else {
+            panic!("orders.listed is not a u64 sum");
+        };
+        assert_eq!(
+            sum.data_points().map(|point| point.value()).sum::<u64>(),
+            1000
+        );
+    }
+
+    #[test]
+    fn test_delta_temporality() {
+        use crate::config::MetricsTemporality;
+        use opentelemetry_sdk::metrics::InMemoryMetricExporterBuilder;
+        use std::sync::atomic::{AtomicU64, Ordering};
+        use std::sync::Arc;
+
+        let exporter = InMemoryMetricExporterBuilder::new()
+            .with_temporality(MetricsTemporality::Delta.into())
+            .build();
+        let meter_provider = SdkMeterProvider::builder()
+            .with_reader(PeriodicReader::builder(exporter.clone()).build())
+            .build();
+        let meter = meter_provider.meter("test");
+        let counter = meter.u64_counter("orders.created").build();
+        // observed as a total since the start, like `process.cpu.time`
+        let total = Arc::new(AtomicU64::new(0));
+        let observed = total.clone();
+        let _observable = meter
+            .u64_observable_counter("orders.shipped")
+            .with_callback(move |observer| observer.observe(observed.load(Ordering::SeqCst), &[]))
+            .build();
+
+        for (created, shipped) in [(5, 10), (3, 25), (0, 25)] {
+            counter.add(created, &[]);
+            total.store(shipped, Ordering::SeqCst);
+            meter_provider.force_flush().expect("flush failed");
+        }
+
+        let metrics = exporter.get_finished_metrics().unwrap();
+        let values = |name: &str| {
+            let values = metrics.iter().map(|metrics| {
+                let metric = metrics
+                    .scope_metrics()
+                    .flat_map(|scope| scope.metrics())
+                    .find(|metric| metric.name() == name);
+                match metric.map(|metric| metric.data()) {
+                    Some(AggregatedMetrics::U64(MetricData::Sum(sum))) => {
+                        sum.data_points().map(|point| point.value()).sum()
+                    }
+                    _ => 0,
+                }
+            });
+            values.collect::<Vec<u64>>()
+        };
+        // each export reports the increase since the previous one
+        assert_eq!(values("orders.created"), [5, 3, 0]);
+        assert_eq!(values("orders.shipped"), [10, 15, 0]);
+    }
+}
@@ -0,0 +1,190 @@
+//! Metrics of the queues of the batch span processors, reporting the spans dropped when a
+//! queue is full.
+
+use opentelemetry::metrics::Meter;
+use opentelemetry::Context;
+use opentelemetry_sdk::error::OTelSdkResult;
+use opentelemetry_sdk::trace::{Span, SpanData, SpanExporter, SpanProcessor};
+use opentelemetry_sdk::Resource;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Spans queued by a batch span processor and dropped because its queue was full.
+#[derive(Debug, Default)]
+pub(crate) struct SpanQueue {
+    /// The spans waiting in the queue or being exported.
+    queued: AtomicUsize,
+    dropped: AtomicU64,
+}
+
+/// Bounds the spans handed to a batch span processor to `max_queue_size`, counting the ones
+/// dropped instead of letting the processor drop them silently.
+///
+/// The spans being exported count too, so the processor's own queue never overflows.
+#[derive(Debug)]
+pub(crate) struct QueueProcessor<P> {
+    max_queue_size: usize,
+    queue: Arc<SpanQueue>,
+    inner: P,
+}
+
+impl<P> QueueProcessor<P> {
+    pub(crate) fn new(max_queue_size: usize, queue: Arc<SpanQueue>, inner: P) -> Self {
+        Self {
+            max_queue_size,
+            queue,
+            inner,
+        }
+    }
+}
+
+impl<P: SpanProcessor> SpanProcessor for QueueProcessor<P> {
+    fn on_start(&self, span: &mut Span, cx: &Context) {
+        self.inner.on_start(span, cx)
+    }
+
+    fn on_end(&self, span: SpanData) {
+        let queued = self.queue.queued.fetch_add(1, Ordering::Relaxed);
+        if queued >= self.max_queue_size {
+            self.queue.queued.fetch_sub(1, Ordering::Relaxed);
+            self.queue.dropped.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        self.inner.on_end(span)
+    }
+
+    fn force_flush(&self) -> OTelSdkResult {
+        self.inner.force_flush()
+    }
+
+    fn shutdown_with_timeout(&self, timeout: Duration) -> OTelSdkResult {
+        self.inner.shutdown_with_timeout(timeout)
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.inner.set_resource(resource)
+    }
+}
+
+/// Removes the exported spans from the [`SpanQueue`] of the processor, whether or not the
+/// export succeeded.
+#[derive(Debug)]
+pub(crate) struct QueueExporter<E> {
+    queue: Arc<SpanQueue>,
+    inner: E,
+}
+
+impl<E> QueueExporter<E> {
+    pub(crate) fn new(queue: Arc<SpanQueue>, inner: E) -> Self {
+        Self { queue, inner }
+    }
+}
+
+impl<E: SpanExporter> SpanExporter for QueueExporter<E> {
+    async fn export(&self, batch: Vec<SpanData>) -> OTelSdkResult {
+        let len = batch.len();
+        let result = self.inner.export(batch).await;
+        self.queue.queued.fetch_sub(len, Ordering::Relaxed);
+        result
+    }
+
+    fn shutdown_with_timeout(&mut self, timeout: Duration) -> OTelSdkResult {
+        self.inner.shutdown_with_timeout(timeout)
+    }
+
+    fn shutdown(&mut self) -> OTelSdkResult {
+        self.inner.shutdown()
+    }
+
+    fn force_flush(&mut self) -> OTelSdkResult {
+        self.inner.force_flush()
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.inner.set_resource(resource)
+    }
+}
+
+/// Registers the `otel.batch.dropped_spans` and `otel.batch.queue_size` instruments,
+/// summed over the queues of all batch span processors.
+pub(crate) fn register(meter: &Meter, queues: Vec<Arc<SpanQueue>>) {
+    let queues = Arc::new(queues);
+    let dropped_queues = queues.clone();
+    meter
+        .u64_observable_counter("otel.batch.dropped_spans")
+        .with_description("The spans dropped because the queue of the batch processor was full.")
+        .with_unit("{span}")
+        .with_callback(move |observer| {
+            let dropped = dropped_queues
+                .iter()
+                .map(|queue| queue.dropped.load(Ordering::Relaxed))
+                .sum();
+            observer.observe(dropped, &[]);
+        })
+        .build();
+    meter
+        .u64_observable_gauge("otel.batch.queue_size")
+        .with_description(
+            "The spans waiting in the queue of the batch processor or being exported.",
+        )
+        .with_unit("{span}")
+        .with_callback(move |observer| {
+            let queued = queues
+                .iter()
+                .map(|queue| queue.queued.load(Ordering::Relaxed) as u64)
+                .sum();
+            observer.observe(queued, &[]);
+        })
+        .build();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::FutureExt;
+    use opentelemetry::trace::{Tracer, TracerProvider};
+    use opentelemetry_sdk::trace::{InMemorySpanExporter, SdkTracerProvider};
+    use std::sync::Mutex;
+
+    /// Keeps the ended spans, like a batch processor that doesn't export yet.
+    #[derive(Debug, Default, Clone)]
+    struct PendingProcessor(Arc<Mutex<Vec<SpanData>>>);
+
+    impl SpanProcessor for PendingProcessor {
+        fn on_start(&self, _span: &mut Span, _cx: &Context) {}
+
+        fn on_end(&self, span: SpanData) {
+            self.0.lock().unwrap().push(span);
+        }
+
+        fn force_flush(&self) -> OTelSdkResult {
+            Ok(())
+        }
+
+        fn shutdown_with_timeout(&self, _timeout: Duration) -> OTelSdkResult {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_queue_counts_dropped_spans() {
+        let queue = Arc::new(SpanQueue::default());
+        let pending = PendingProcessor::default();
+        let provider = SdkTracerProvider::builder()
+            .with_span_processor(QueueProcessor::new(2, queue.clone(), pending.clone()))
+            .build();
+        let tracer = provider.tracer("test");
+        for _ in 0..3 {
+            tracer.in_span("work", |_| {});
+        }
+        assert_eq!(queue.queued.load(Ordering::Relaxed), 2);
+        assert_eq!(queue.dropped.load(Ordering::Relaxed), 1);
+
+        let exporter = QueueExporter::new(queue.clone(), InMemorySpanExporter::default());
+        let batch = std::mem::take(&mut *pending.0.lock().unwrap());
+        let result = exporter.export(batch).now_or_never();
+        assert!(matches!(result, Some(Ok(()))));
+        assert_eq!(queue.queued.load(Ordering::Relaxed), 0);
+    }
+}
@@ -0,0 +1,83 @@
+//! Instruments of the meter provider of the plugin, registered as a component.
+
+use opentelemetry::metrics::{Counter, Histogram, Meter, MeterProvider, UpDownCounter};
+use opentelemetry::InstrumentationScope;
+use opentelemetry_sdk::metrics::SdkMeterProvider;
+use std::borrow::Cow;
+
+/// Creates instruments backed by the [`SdkMeterProvider`] of the plugin, so they report the
+/// configured resource even when they are created before the global meter provider is set.
+///
+/// It is registered as a component when metrics are enabled and is cheap to clone:
+///
+/// ```rust,ignore
+/// #[get("/order")]
+/// async fn order(Component(metrics): Component<Metrics>) -> impl IntoResponse {
+///     metrics.counter("order.created").add(1, &[]);
+///     "ok"
+/// }
+/// ```
+///
+/// Instruments are meant to be created once and reused, use [`meter`](Self::meter) to set
+/// the description or unit of an instrument.
+#[derive(Debug, Clone)]
+pub struct Metrics {
+    meter: Meter,
+}
+
+impl Metrics {
+    pub(crate) fn new(provider: &SdkMeterProvider, scope: InstrumentationScope) -> Self {
+        Self {
+            meter: provider.meter_with_scope(scope),
+        }
+    }
+
+    /// The meter of the instrumentation scope of the application.
+    pub fn meter(&self) -> &Meter {
+        &self.meter
+    }
+
+    /// A monotonic counter, e.g. of the handled requests.
+    pub fn counter(&self, name: impl Into<Cow<'static, str>>) -> Counter<u64> {
+        self.meter.u64_counter(name).build()
+    }
+
+    /// A histogram of measurements, e.g. of durations in seconds.
+    pub fn histogram(&self, name: impl Into<Cow<'static, str>>) -> Histogram<f64> {
+        self.meter.f64_histogram(name).build()
+    }
+
+    /// A counter that can go up and down, e.g. of the active connections.
+    pub fn up_down_counter(&self, name: impl Into<Cow<'static, str>>) -> UpDownCounter<i64> {
+        self.meter.i64_up_down_counter(name).build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry_sdk::metrics::InMemoryMetricExporter;
+
+    #[test]
+    fn test_instruments_use_provider_and_scope() {
+        let exporter = InMemoryMetricExporter::default();
+        let provider = SdkMeterProvider::builder()
+            .with_periodic_exporter(exporter.clone())
+            .build();
+        let metrics = Metrics::new(&provider, InstrumentationScope::builder("payments").build());
+
+        metrics.counter("order.created").add(1, &[]);
+        provider.force_flush().expect("force flush failed");
+
+        let resource_metrics = exporter.get_finished_metrics().expect("get metrics failed");
+        let scope_metrics = resource_metrics[0]
+            .scope_metrics()
+            .next()
+            .expect("scope metrics");
+        assert_eq!(scope_metrics.scope().name(), "payments");
+        assert_eq!(
+            scope_metrics.metrics().next().map(|metric| metric.name()),
+            Some("order.created")
+        );
+    }
+}
@@ -0,0 +1,195 @@
+//! Attributes added to the metrics recorded from the `tracing` events by the `MetricsLayer`,
+//! e.g. `service.instance.id` for the backends that don't show the resource of the metrics.
+
+use opentelemetry::metrics::{
+    Counter, Gauge, Histogram, HistogramBuilder, InstrumentBuilder, InstrumentProvider, Meter,
+    MeterProvider, SyncInstrument, UpDownCounter,
+};
+use opentelemetry::{InstrumentationScope, KeyValue};
+use opentelemetry_sdk::metrics::SdkMeterProvider;
+use std::sync::Arc;
+
+/// Meter provider of the `MetricsLayer`, whose instruments add `attributes` to the attributes
+/// of the events. The attributes of an event take precedence over the ones with the same key.
+///
+/// Only the synchronous instruments created by the layer are supported, the other ones are
+/// no-ops.
+#[derive(Debug, Clone)]
+pub(crate) struct AttributesMeterProvider {
+    inner: SdkMeterProvider,
+    attributes: Arc<[KeyValue]>,
+}
+
+impl AttributesMeterProvider {
+    pub(crate) fn new(inner: SdkMeterProvider, attributes: Vec<KeyValue>) -> Self {
+        Self {
+            inner,
+            attributes: attributes.into(),
+        }
+    }
+}
+
+impl MeterProvider for AttributesMeterProvider {
+    fn meter_with_scope(&self, scope: InstrumentationScope) -> Meter {
+        Meter::new(Arc::new(AttributesInstrumentProvider {
+            meter: self.inner.meter_with_scope(scope),
+            attributes: self.attributes.clone(),
+        }))
+    }
+}
+
+struct AttributesInstrumentProvider {
+    meter: Meter,
+    attributes: Arc<[KeyValue]>,
+}
+
+impl AttributesInstrumentProvider {
+    fn wrap<I>(&self, inner: I) -> Arc<AttributesInstrument<I>> {
+        Arc::new(AttributesInstrument {
+            inner,
+            attributes: self.attributes.clone(),
+        })
+    }
+}
+
+/// Creates the instrument with the meter of the provider and wraps it to add the attributes.
+macro_rules! instruments {
+    ($($method:ident: $builder:ident<$instrument:ident<$value:ty>> $(, $boundaries:ident)?;)*) => {
+        impl InstrumentProvider for AttributesInstrumentProvider {
+            $(
+                fn $method(&self, builder: $builder<'_, $instrument<$value>>) -> $instrument<$value> {
+                    let mut inner = self.meter.$method(builder.name);
+                    if let Some(description) = builder.description {
+                        inner = inner.with_description(description);
+                    }
+                    if let Some(unit) = builder.unit {
+                        inner = inner.with_unit(unit);
+                    }
+                    $(
+                        if let Some(boundaries) = builder.$boundaries {
+                            inner = inner.with_boundaries(boundaries);
+                        }
+                    )?
+                    $instrument::new(self.wrap(inner.build()))
+                }
+            )*
+        }
+    };
+}
+
+instruments! {
+    u64_counter: InstrumentBuilder<Counter<u64>>;
+    f64_counter: InstrumentBuilder<Counter<f64>>;
+    i64_up_down_counter: InstrumentBuilder<UpDownCounter<i64>>;
+    f64_up_down_counter: InstrumentBuilder<UpDownCounter<f64>>;
+    u64_gauge: InstrumentBuilder<Gauge<u64>>;
+    i64_gauge: InstrumentBuilder<Gauge<i64>>;
+    f64_gauge: InstrumentBuilder<Gauge<f64>>;
+    u64_histogram: HistogramBuilder<Histogram<u64>>, boundaries;
+    f64_histogram: HistogramBuilder<Histogram<f64>>, boundaries;
+}
+
+/// Instrument adding the attributes to the measurements of the inner instrument.
+struct AttributesInstrument<I> {
+    inner: I,
+    attributes: Arc<[KeyValue]>,
+}
+
+impl<I> AttributesInstrument<I> {
+    fn with_attributes(&self, attributes: &[KeyValue]) -> Vec<KeyValue> {
+        let mut merged = attributes.to_vec();
+        let added = self.attributes.iter().filter(|added| {
+            !attributes
+                .iter()
+                .any(|attribute| attribute.key == added.key)
+        });
+        merged.extend(added.cloned());
+        merged
+    }
+}
+
+/// Records the measurements with `$record` of the inner instrument.
+macro_rules! sync_instrument {
+    ($($instrument:ident<$value:ty>: $record:ident;)*) => {
+        $(
+            impl SyncInstrument<$value> for AttributesInstrument<$instrument<$value>> {
+                fn measure(&self, measurement: $value, attributes: &[KeyValue]) {
+                    self.inner.$record(measurement, &self.with_attributes(attributes))
+                }
+            }
+        )*
+    };
+}
+
+sync_instrument! {
+    Counter<u64>: add;
+    Counter<f64>: add;
+    UpDownCounter<i64>: add;
+    UpDownCounter<f64>: add;
+    Gauge<u64>: record;
+    Gauge<i64>: record;
+    Gauge<f64>: record;
+    Histogram<u64>: record;
+    Histogram<f64>: record;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry_sdk::metrics::data::{AggregatedMetrics, MetricData};
+    use opentelemetry_sdk::metrics::{InMemoryMetricExporter, PeriodicReader};
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn test_layer_attributes() {
+        let exporter = InMemoryMetricExporter::default();
+        let meter_provider = SdkMeterProvider::builder()
+            .with_reader(PeriodicReader::builder(exporter.clone()).build())
+            .build();
+        let provider = AttributesMeterProvider::new(
+            meter_provider.clone(),
+            vec![
+                KeyValue::new("service.instance.id", "pod-1"),
+                KeyValue::new("region", "eu"),
+            ],
+        );
+        let subscriber =
+            tracing_subscriber::registry().with(tracing_opentelemetry::MetricsLayer::new(provider));
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(monotonic_counter.orders = 1_u64, region = "us");
+            tracing::info!(histogram.order_amount = 42.0);
+        });
+        meter_provider.force_flush().expect("flush failed");
+
+        let metrics = exporter.get_finished_metrics().unwrap();
+        let metric = |name: &str| {
+            metrics[0]
+                .scope_metrics()
+                .flat_map(|scope| scope.metrics())
+                .find(|metric| metric.name() == name)
+                .unwrap_or_else(|| panic!("{name} not recorded"))
+        };
+        let orders = metric("orders");
+        let AggregatedMetrics::U64(MetricData::Sum(sum)) = orders.data() else {
+            panic!("orders is not a u64 sum");
+        };
+        let mut attributes: Vec<_> = sum.data_points().next().unwrap().attributes().collect();
+        attributes.sort_by(|a, b| a.key.cmp(&b.key));
+        assert_eq!(
+            attributes,
+            [
+                &KeyValue::new("region", "us"),
+                &KeyValue::new("service.instance.id", "pod-1"),
+            ]
+        );
+        let amount = metric("order_amount");
+        let AggregatedMetrics::F64(MetricData::Histogram(histogram)) = amount.data() else {
+            panic!("order_amount is not a f64 histogram");
+        };
+        let point = histogram.data_points().next().unwrap();
+        assert!(point
+            .attributes()
+            .any(|attribute| *attribute == KeyValue::new("service.instance.id", "pod-1")));
+    }
+}
@@ -0,0 +1,123 @@
+//! Process metrics recorded when `process_metrics = true`.
+//! refs: https://opentelemetry.io/docs/specs/semconv/system/process-metrics/
+
+use opentelemetry::metrics::Meter;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::task::AbortHandle;
+
+/// The process metrics sampled by the background task, `None` where unsupported.
+#[derive(Debug, Default, Clone, Copy)]
+struct Sample {
+    cpu_time: Option<f64>,
+    memory_usage: Option<u64>,
+}
+
+impl Sample {
+    #[cfg(target_os = "linux")]
+    fn read() -> Self {
+        match procfs::process::Process::myself().and_then(|process| process.stat()) {
+            Ok(stat) => Self {
+                cpu_time: Some(
+                    (stat.utime + stat.stime) as f64 / procfs::ticks_per_second() as f64,
+                ),
+                memory_usage: Some(stat.rss * procfs::page_size()),
+            },
+            Err(e) => {
+                tracing::debug!("read process stat failed: {e}");
+                Self::default()
+            }
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn read() -> Self {
+        Self::default()
+    }
+}
+
+/// Registers the `process.uptime`, `process.cpu.time` and `process.memory.usage` instruments
+/// on the meter, and spawns the task sampling the process every `interval`.
+///
+/// The returned handle aborts the task, the CPU time and memory usage are only recorded on Linux.
+pub(crate) fn start(meter: &Meter, interval: Duration) -> AbortHandle {
+    let start = Instant::now();
+    let sample = Arc::new(Mutex::new(Sample::read()));
+
+    meter
+        .f64_observable_gauge("process.uptime")
+        .with_description("The time the process has been running.")
+        .with_unit("s")
+        .with_callback(move |observer| observer.observe(start.elapsed().as_secs_f64(), &[]))
+        .build();
+    let cpu_sample = sample.clone();
+    meter
+        .f64_observable_counter("process.cpu.time")
+        .with_description("Total CPU seconds of the process.")
+        .with_unit("s")
+        .with_callback(move |observer| {
+            if let Some(cpu_time) = cpu_sample.lock().ok().and_then(|s| s.cpu_time) {
+                observer.observe(cpu_time, &[]);
+            }
+        })
+        .build();
+    let memory_sample = sample.clone();
+    meter
+        .i64_observable_up_down_counter("process.memory.usage")
+        .with_description("The amount of physical memory in use.")
+        .with_unit("By")
+        .with_callback(move |observer| {
+            if let Some(memory_usage) = memory_sample.lock().ok().and_then(|s| s.memory_usage) {
+                observer.observe(memory_usage as i64, &[]);
+            }
+        })
+        .build();
+
+    let task = tokio::spawn(async move {
+        let mut interval = tokio::time::interval(interval);
+        loop {
+            interval.tick().await;
+            let next = Sample::read();
+            if let Ok(mut sample) = sample.lock() {
+                *sample = next;
+            }
+        }
+    });
+    task.abort_handle()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry::metrics::MeterProvider;
+    use opentelemetry_sdk::metrics::{InMemoryMetricExporter, SdkMeterProvider};
+
+    #[test]
+    fn test_process_metrics() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .expect("build runtime failed");
+        let exporter = InMemoryMetricExporter::default();
+        let provider = SdkMeterProvider::builder()
+            .with_periodic_exporter(exporter.clone())
+            .build();
+
+        let task =
+            runtime.block_on(async { start(&provider.meter("test"), Duration::from_secs(1)) });
+        provider.force_flush().expect("force flush failed");
+        task.abort();
+
+        let resource_metrics = exporter.get_finished_metrics().expect("get metrics failed");
+        let names = resource_metrics[0]
+            .scope_metrics()
+            .flat_map(|scope| scope.metrics())
+            .map(|metric| metric.name().to_string())
+            .collect::<Vec<_>>();
+        assert!(names.contains(&"process.uptime".to_string()));
+        if cfg!(target_os = "linux") {
+            assert!(names.contains(&"process.cpu.time".to_string()));
+            assert!(names.contains(&"process.memory.usage".to_string()));
+        }
+    }
+}
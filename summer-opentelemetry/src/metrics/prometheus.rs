@@ -0,0 +1,35 @@
+//! Registry of the Prometheus pull exporter.
+
+use anyhow::Context;
+use prometheus::{Encoder, Registry, TextEncoder};
+use summer::error::Result;
+
+/// Registry the metrics are collected into when `metrics_exporter = "prometheus"`,
+/// registered as a component so that a web handler can expose it for scraping.
+#[derive(Debug, Clone)]
+pub struct PrometheusRegistry(Registry);
+
+impl PrometheusRegistry {
+    pub(crate) fn new(registry: Registry) -> Self {
+        Self(registry)
+    }
+
+    /// The underlying [`Registry`], e.g. to register additional collectors.
+    pub fn registry(&self) -> &Registry {
+        &self.0
+    }
+
+    /// The `Content-Type` of the output of [`encode`](Self::encode).
+    pub fn content_type(&self) -> &'static str {
+        prometheus::TEXT_FORMAT
+    }
+
+    /// Gathers the collected metrics and encodes them in the Prometheus text format.
+    pub fn encode(&self) -> Result<String> {
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&self.0.gather(), &mut buffer)
+            .context("encode prometheus metrics failed")?;
+        Ok(String::from_utf8(buffer).context("prometheus metrics are not valid utf-8")?)
+    }
+}
@@ -0,0 +1,258 @@
+//! Request, error and duration metrics derived from the ended spans, like the span metrics
+//! connector of the collector but in process.
+//! refs: https://github.com/open-telemetry/opentelemetry-collector-contrib/tree/main/connector/spanmetricsconnector
+
+use opentelemetry::metrics::{Counter, Histogram, Meter};
+use opentelemetry::trace::{SpanKind, Status};
+use opentelemetry::{Context, Key, KeyValue};
+use opentelemetry_sdk::error::OTelSdkResult;
+use opentelemetry_sdk::trace::{Span, SpanData, SpanProcessor};
+use opentelemetry_sdk::Resource;
+use std::time::Duration;
+
+/// An attribute of the span metrics.
+#[derive(Debug)]
+enum Dimension {
+    SpanName,
+    SpanKind,
+    StatusCode,
+    /// An attribute of the span, omitted when the span doesn't have it.
+    Attribute(Key),
+}
+
+impl Dimension {
+    fn new(dimension: &str) -> Self {
+        match dimension {
+            "span.name" => Self::SpanName,
+            "span.kind" => Self::SpanKind,
+            "status.code" => Self::StatusCode,
+            key => Self::Attribute(Key::new(key.to_string())),
+        }
+    }
+
+    fn attribute(&self, span: &SpanData) -> Option<KeyValue> {
+        match self {
+            Self::SpanName => Some(KeyValue::new("span.name", span.name.clone())),
+            Self::SpanKind => Some(KeyValue::new("span.kind", span_kind(&span.span_kind))),
+            Self::StatusCode => Some(KeyValue::new("status.code", status_code(&span.status))),
+            Self::Attribute(key) => span
+                .attributes
+                .iter()
+                .find(|attribute| attribute.key == *key)
+                .cloned(),
+        }
+    }
+}
+
+fn span_kind(kind: &SpanKind) -> &'static str {
+    match kind {
+        SpanKind::Client => "SPAN_KIND_CLIENT",
+        SpanKind::Server => "SPAN_KIND_SERVER",
+        SpanKind::Producer => "SPAN_KIND_PRODUCER",
+        SpanKind::Consumer => "SPAN_KIND_CONSUMER",
+        SpanKind::Internal => "SPAN_KIND_INTERNAL",
+    }
+}
+
+fn status_code(status: &Status) -> &'static str {
+    match status {
+        Status::Unset => "STATUS_CODE_UNSET",
+        Status::Error { .. } => "STATUS_CODE_ERROR",
+        Status::Ok => "STATUS_CODE_OK",
+    }
+}
+
+/// The `traces.span.metrics.calls` counter and the `traces.span.metrics.duration` histogram
+/// of the ended spans, with the attributes of the `dimensions`. The errors are the calls whose
+/// `status.code` is `STATUS_CODE_ERROR`.
+#[derive(Debug)]
+pub(crate) struct SpanMetrics {
+    dimensions: Vec<Dimension>,
+    calls: Counter<u64>,
+    duration: Histogram<f64>,
+}
+
+impl SpanMetrics {
+    pub(crate) fn new(meter: &Meter, dimensions: &[String]) -> Self {
+        Self {
+            dimensions: dimensions.iter().map(|d| Dimension::new(d)).collect(),
+            calls: meter
+                .u64_counter("traces.span.metrics.calls")
+                .with_description("The spans ended, by the span metrics dimensions.")
+                .with_unit("{call}")
+                .build(),
+            duration: meter
+                .f64_histogram("traces.span.metrics.duration")
+                .with_description("The duration of the spans, by the span metrics dimensions.")
+                .with_unit("s")
+                .with_boundaries(vec![
+                    0.005, 0.01, 0.025, 0.05, 0.075, 0.1, 0.25, 0.5, 0.75, 1.0, 2.5, 5.0, 7.5, 10.0,
+                ])
+                .build(),
+        }
+    }
+
+    fn record(&self, span: &SpanData) {
+        let attributes: Vec<_> = self
+            .dimensions
+            .iter()
+            .filter_map(|dimension| dimension.attribute(span))
+            .collect();
+        let duration = span
+            .end_time
+            .duration_since(span.start_time)
+            .unwrap_or_default();
+        self.calls.add(1, &attributes);
+        self.duration.record(duration.as_secs_f64(), &attributes);
+    }
+}
+
+/// Records the [`SpanMetrics`] of the ended spans before handing them to the inner processor,
+/// so that the metrics see the spans as exported, e.g. renamed or marked as errors by the
+/// processors wrapping it.
+#[derive(Debug)]
+pub(crate) struct SpanMetricsProcessor<P> {
+    metrics: SpanMetrics,
+    inner: P,
+}
+
+impl<P> SpanMetricsProcessor<P> {
+    pub(crate) fn new(metrics: SpanMetrics, inner: P) -> Self {
+        Self { metrics, inner }
+    }
+}
+
+impl<P: SpanProcessor> SpanProcessor for SpanMetricsProcessor<P> {
+    fn on_start(&self, span: &mut Span, cx: &Context) {
+        self.inner.on_start(span, cx)
+    }
+
+    fn on_end(&self, span: SpanData) {
+        self.metrics.record(&span);
+        self.inner.on_end(span)
+    }
+
+    fn force_flush(&self) -> OTelSdkResult {
+        self.inner.force_flush()
+    }
+
+    fn shutdown_with_timeout(&self, timeout: Duration) -> OTelSdkResult {
+        self.inner.shutdown_with_timeout(timeout)
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.inner.set_resource(resource)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry::metrics::MeterProvider;
+    use opentelemetry::trace::{Span as _, Tracer, TracerProvider};
+    use opentelemetry_sdk::metrics::data::{AggregatedMetrics, MetricData};
+    use opentelemetry_sdk::metrics::{InMemoryMetricExporter, PeriodicReader, SdkMeterProvider};
+    use opentelemetry_sdk::trace::{InMemorySpanExporter, SdkTracerProvider, SimpleSpanProcessor};
+    use std::time::UNIX_EPOCH;
+
+    #[test]
+    fn test_span_metrics() {
+        let exporter = InMemoryMetricExporter::default();
+        let meter_provider = SdkMeterProvider::builder()
+            .with_reader(PeriodicReader::builder(exporter.clone()).build())
+            .build();
+        let dimensions = ["span.name", "status.code", "http.route"].map(String::from);
+        let spans = InMemorySpanExporter::default();
+        let tracer_provider = SdkTracerProvider::builder()
+            .with_span_processor(SpanMetricsProcessor::new(
+                SpanMetrics::new(&meter_provider.meter("test"), &dimensions),
+                SimpleSpanProcessor::new(spans.clone()),
+            ))
+            .build();
+        let tracer = tracer_provider.tracer("test");
+        let start = UNIX_EPOCH + Duration::from_secs(1_000);
+        for (route, millis, failed) in [
+            ("/orders", 100, false),
+            ("/orders", 300, true),
+            ("/users", 2_000, false),
+        ] {
+            let mut span = tracer
+                .span_builder("GET")
+                .with_start_time(start)
+                .with_attributes([KeyValue::new("http.route", route)])
+                .start(&tracer);
+            if failed {
+                span.set_status(Status::error("timeout"));
+            }
+            span.end_with_timestamp(start + Duration::from_millis(millis));
+        }
+        tracer.in_span("unrouted", |_| {});
+        meter_provider.force_flush().expect("flush failed");
+        assert_eq!(spans.get_finished_spans().unwrap().len(), 4);
+
+        let metrics = exporter.get_finished_metrics().unwrap();
+        let metric = |name: &str| {
+            metrics[0]
+                .scope_metrics()
+                .flat_map(|scope| scope.metrics())
+                .find(|metric| metric.name() == name)
+                .unwrap_or_else(|| panic!("{name} not recorded"))
+        };
+        let AggregatedMetrics::U64(MetricData::Sum(calls)) =
+            metric("traces.span.metrics.calls").data()
+        else {
+            panic!("calls is not a u64 sum");
+        };
+        let count = |attributes: [(&'static str, &'static str); 3]| {
+            let attributes = attributes.map(|(key, value)| KeyValue::new(key, value));
+            calls
+                .data_points()
+                .find(|point| {
+                    let mut found: Vec<_> = point.attributes().cloned().collect();
+                    found.sort_by(|a, b| a.key.cmp(&b.key));
+                    found == attributes
+                })
+                .map_or(0, |point| point.value())
+        };
+        let orders = ("http.route", "/orders");
+        let get = ("span.name", "GET");
+        assert_eq!(
+            count([orders, get, ("status.code", "STATUS_CODE_UNSET")]),
+            1
+        );
+        assert_eq!(
+            count([orders, get, ("status.code", "STATUS_CODE_ERROR")]),
+            1
+        );
+        assert_eq!(
+            count([
+                ("http.route", "/users"),
+                get,
+                ("status.code", "STATUS_CODE_UNSET")
+            ]),
+            1
+        );
+        // the spans without the attribute are counted without it
+        let unrouted = calls.data_points().find(|point| {
+            point
+                .attributes()
+                .any(|a| *a == KeyValue::new("span.name", "unrouted"))
+        });
+        assert_eq!(unrouted.map(|point| point.attributes().count()), Some(2));
+
+        let AggregatedMetrics::F64(MetricData::Histogram(duration)) =
+            metric("traces.span.metrics.duration").data()
+        else {
+            panic!("duration is not a f64 histogram");
+        };
+        let users = duration
+            .data_points()
+            .find(|point| {
+                point
+                    .attributes()
+                    .any(|a| *a == KeyValue::new("http.route", "/users"))
+            })
+            .expect("/users not recorded");
+        assert_eq!((users.count(), users.sum()), (1, 2.0));
+    }
+}
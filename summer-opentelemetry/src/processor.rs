@@ -0,0 +1,1125 @@
+//! Span processors wrapping the processors of the exporters, and log processors enriching the
+//! log records before they are exported.
+
+use crate::config::LogsSeverityMapping;
+use opentelemetry::baggage::BaggageExt;
+use opentelemetry::logs::{AnyValue, LogRecord as _, Severity};
+use opentelemetry::trace::{Span as _, SpanContext, Status};
+use opentelemetry::{Array, Context, InstrumentationScope, Key, KeyValue, StringValue, Value};
+use opentelemetry_sdk::error::OTelSdkResult;
+use opentelemetry_sdk::logs::{LogProcessor, SdkLogRecord};
+use opentelemetry_sdk::trace::{Span, SpanData, SpanProcessor};
+use opentelemetry_sdk::Resource;
+use opentelemetry_semantic_conventions::attribute::HTTP_RESPONSE_STATUS_CODE;
+use opentelemetry_semantic_conventions::resource::{SERVICE_NAME, SERVICE_VERSION};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Truncates the string attribute values of the ended spans, their events and links to
+/// `max_length` characters before handing them to the inner processor.
+#[derive(Debug)]
+pub(crate) struct AttributeValueLengthProcessor<P> {
+    max_length: usize,
+    inner: P,
+}
+
+impl<P> AttributeValueLengthProcessor<P> {
+    pub(crate) fn new(max_length: usize, inner: P) -> Self {
+        Self { max_length, inner }
+    }
+
+    fn truncate_all(&self, attributes: &mut [KeyValue]) {
+        for attribute in attributes {
+            truncate(&mut attribute.value, self.max_length);
+        }
+    }
+}
+
+fn truncate_str(value: &mut StringValue, max_length: usize) {
+    if let Some((index, _)) = value.as_str().char_indices().nth(max_length) {
+        *value = StringValue::from(value.as_str()[..index].to_string());
+    }
+}
+
+fn truncate(value: &mut Value, max_length: usize) {
+    match value {
+        Value::String(value) => truncate_str(value, max_length),
+        Value::Array(Array::String(values)) => values
+            .iter_mut()
+            .for_each(|value| truncate_str(value, max_length)),
+        _ => {}
+    }
+}
+
+impl<P: SpanProcessor> SpanProcessor for AttributeValueLengthProcessor<P> {
+    fn on_start(&self, span: &mut Span, cx: &Context) {
+        self.inner.on_start(span, cx)
+    }
+
+    fn on_end(&self, mut span: SpanData) {
+        self.truncate_all(&mut span.attributes);
+        for event in &mut span.events.events {
+            self.truncate_all(&mut event.attributes);
+        }
+        for link in &mut span.links.links {
+            self.truncate_all(&mut link.attributes);
+        }
+        self.inner.on_end(span)
+    }
+
+    fn force_flush(&self) -> OTelSdkResult {
+        self.inner.force_flush()
+    }
+
+    fn shutdown_with_timeout(&self, timeout: Duration) -> OTelSdkResult {
+        self.inner.shutdown_with_timeout(timeout)
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.inner.set_resource(resource)
+    }
+}
+
+/// Renames the ended spans whose name contains a path matching one of the route templates
+/// to the template, e.g. `GET /users/12345` to `GET /users/{id}`, before handing them to the
+/// inner processor.
+#[derive(Debug)]
+pub(crate) struct SpanNameProcessor<P> {
+    templates: Vec<String>,
+    inner: P,
+}
+
+impl<P> SpanNameProcessor<P> {
+    pub(crate) fn new(templates: Vec<String>, inner: P) -> Self {
+        Self { templates, inner }
+    }
+
+    fn normalize(&self, name: &str) -> Option<String> {
+        // the path follows the method, if any, and ends before the query
+        let start = name.find('/')?;
+        let (prefix, path) = name.split_at(start);
+        let path = path.split_once('?').map_or(path, |(path, _)| path);
+        let template = self
+            .templates
+            .iter()
+            .find(|template| matches_template(template, path))?;
+        Some(format!("{prefix}{template}"))
+    }
+}
+
+/// Whether the segments of `path` match those of `template`, a `{param}` segment matching
+/// any non-empty segment.
+fn matches_template(template: &str, path: &str) -> bool {
+    let mut path_segments = path.split('/');
+    let mut template_segments = template.split('/');
+    loop {
+        match (template_segments.next(), path_segments.next()) {
+            (None, None) => return true,
+            (Some(template), Some(segment)) => {
+                let is_param = template.starts_with('{') && template.ends_with('}');
+                if !((is_param && !segment.is_empty()) || template == segment) {
+                    return false;
+                }
+            }
+            _ => return false,
+        }
+    }
+}
+
+impl<P: SpanProcessor> SpanProcessor for SpanNameProcessor<P> {
+    fn on_start(&self, span: &mut Span, cx: &Context) {
+        self.inner.on_start(span, cx)
+    }
+
+    fn on_end(&self, mut span: SpanData) {
+        if let Some(name) = self.normalize(&span.name) {
+            span.name = name.into();
+        }
+        self.inner.on_end(span)
+    }
+
+    fn force_flush(&self) -> OTelSdkResult {
+        self.inner.force_flush()
+    }
+
+    fn shutdown_with_timeout(&self, timeout: Duration) -> OTelSdkResult {
+        self.inner.shutdown_with_timeout(timeout)
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.inner.set_resource(resource)
+    }
+}
+
+/// The value of the redacted attributes.
+const REDACTED: &str = "[REDACTED]";
+
+/// Replaces the values of the attributes of the ended spans, their events and links matching
+/// one of the keys with `"[REDACTED]"` before handing them to the inner processor.
+#[derive(Debug)]
+pub(crate) struct RedactionProcessor<P> {
+    keys: Vec<String>,
+    inner: P,
+}
+
+impl<P> RedactionProcessor<P> {
+    pub(crate) fn new(keys: Vec<String>, inner: P) -> Self {
+        Self { keys, inner }
+    }
+
+    fn is_redacted(&self, key: &str) -> bool {
+        self.keys
+            .iter()
+            .any(|redacted| match redacted.strip_suffix('*') {
+                Some(prefix) => key.starts_with(prefix),
+                None => key == redacted,
+            })
+    }
+
+    fn redact_all(&self, attributes: &mut [KeyValue]) {
+        for attribute in attributes {
+            if self.is_redacted(attribute.key.as_str()) {
+                attribute.value = Value::from(REDACTED);
+            }
+        }
+    }
+}
+
+impl<P: SpanProcessor> SpanProcessor for RedactionProcessor<P> {
+    fn on_start(&self, span: &mut Span, cx: &Context) {
+        self.inner.on_start(span, cx)
+    }
+
+    fn on_end(&self, mut span: SpanData) {
+        self.redact_all(&mut span.attributes);
+        for event in &mut span.events.events {
+            self.redact_all(&mut event.attributes);
+        }
+        for link in &mut span.links.links {
+            self.redact_all(&mut link.attributes);
+        }
+        self.inner.on_end(span)
+    }
+
+    fn force_flush(&self) -> OTelSdkResult {
+        self.inner.force_flush()
+    }
+
+    fn shutdown_with_timeout(&self, timeout: Duration) -> OTelSdkResult {
+        self.inner.shutdown_with_timeout(timeout)
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.inner.set_resource(resource)
+    }
+}
+
+/// Renames the attributes of the ended spans, their events and links by their key before
+/// handing them to the inner processor. An attribute whose new key is already set is dropped.
+#[derive(Debug)]
+pub(crate) struct AttributeRenameProcessor<P> {
+    renames: HashMap<Key, Key>,
+    inner: P,
+}
+
+impl<P> AttributeRenameProcessor<P> {
+    pub(crate) fn new(renames: HashMap<String, String>, inner: P) -> Self {
+        let renames = renames
+            .into_iter()
+            .map(|(key, new_key)| (Key::from(key), Key::from(new_key)))
+            .collect();
+        Self { renames, inner }
+    }
+
+    fn rename_all(&self, attributes: &mut Vec<KeyValue>) {
+        if !attributes
+            .iter()
+            .any(|kv| self.renames.contains_key(&kv.key))
+        {
+            return;
+        }
+        let renamed: Vec<_> = std::mem::take(attributes)
+            .into_iter()
+            .map(|kv| match self.renames.get(&kv.key) {
+                Some(new_key) => KeyValue::new(new_key.clone(), kv.value),
+                None => kv,
+            })
+            .collect();
+        for kv in renamed {
+            if !attributes.iter().any(|set| set.key == kv.key) {
+                attributes.push(kv);
+            }
+        }
+    }
+}
+
+impl<P: SpanProcessor> SpanProcessor for AttributeRenameProcessor<P> {
+    fn on_start(&self, span: &mut Span, cx: &Context) {
+        self.inner.on_start(span, cx)
+    }
+
+    fn on_end(&self, mut span: SpanData) {
+        self.rename_all(&mut span.attributes);
+        for event in &mut span.events.events {
+            self.rename_all(&mut event.attributes);
+        }
+        for link in &mut span.links.links {
+            self.rename_all(&mut link.attributes);
+        }
+        self.inner.on_end(span)
+    }
+
+    fn force_flush(&self) -> OTelSdkResult {
+        self.inner.force_flush()
+    }
+
+    fn shutdown_with_timeout(&self, timeout: Duration) -> OTelSdkResult {
+        self.inner.shutdown_with_timeout(timeout)
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.inner.set_resource(resource)
+    }
+}
+
+/// Exports the spans that ended with an error although they weren't sampled, recorded by the
+/// [`RecordUnsampledSampler`](crate::sampler::RecordUnsampledSampler), marking them as sampled
+/// for the inner processor. The other spans that weren't sampled are dropped.
+#[derive(Debug)]
+pub(crate) struct ErrorSpanProcessor<P> {
+    inner: P,
+}
+
+impl<P> ErrorSpanProcessor<P> {
+    pub(crate) fn new(inner: P) -> Self {
+        Self { inner }
+    }
+}
+
+impl<P: SpanProcessor> SpanProcessor for ErrorSpanProcessor<P> {
+    fn on_start(&self, span: &mut Span, cx: &Context) {
+        self.inner.on_start(span, cx)
+    }
+
+    fn on_end(&self, mut span: SpanData) {
+        if !span.span_context.is_sampled() {
+            if !matches!(span.status, Status::Error { .. }) {
+                return;
+            }
+            let span_context = &span.span_context;
+            span.span_context = SpanContext::new(
+                span_context.trace_id(),
+                span_context.span_id(),
+                span_context.trace_flags().with_sampled(true),
+                span_context.is_remote(),
+                span_context.trace_state().clone(),
+            );
+        }
+        self.inner.on_end(span)
+    }
+
+    fn force_flush(&self) -> OTelSdkResult {
+        self.inner.force_flush()
+    }
+
+    fn shutdown_with_timeout(&self, timeout: Duration) -> OTelSdkResult {
+        self.inner.shutdown_with_timeout(timeout)
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.inner.set_resource(resource)
+    }
+}
+
+/// Marks the ended spans whose `http.response.status_code` is at least `min_status_code` as
+/// errors, unless their status is already set, before handing them to the inner processor.
+#[derive(Debug)]
+pub(crate) struct HttpStatusProcessor<P> {
+    min_status_code: i64,
+    inner: P,
+}
+
+impl<P> HttpStatusProcessor<P> {
+    pub(crate) fn new(min_status_code: i64, inner: P) -> Self {
+        Self {
+            min_status_code,
+            inner,
+        }
+    }
+}
+
+/// The `http.response.status_code` of the span, recorded as an integer or a string.
+fn http_status_code(span: &SpanData) -> Option<i64> {
+    let attribute = span
+        .attributes
+        .iter()
+        .find(|attribute| attribute.key.as_str() == HTTP_RESPONSE_STATUS_CODE)?;
+    match &attribute.value {
+        Value::I64(status_code) => Some(*status_code),
+        Value::String(status_code) => status_code.as_str().parse().ok(),
+        _ => None,
+    }
+}
+
+impl<P: SpanProcessor> SpanProcessor for HttpStatusProcessor<P> {
+    fn on_start(&self, span: &mut Span, cx: &Context) {
+        self.inner.on_start(span, cx)
+    }
+
+    fn on_end(&self, mut span: SpanData) {
+        if span.status == Status::Unset
+            && http_status_code(&span).is_some_and(|code| code >= self.min_status_code)
+        {
+            span.status = Status::error("");
+        }
+        self.inner.on_end(span)
+    }
+
+    fn force_flush(&self) -> OTelSdkResult {
+        self.inner.force_flush()
+    }
+
+    fn shutdown_with_timeout(&self, timeout: Duration) -> OTelSdkResult {
+        self.inner.shutdown_with_timeout(timeout)
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.inner.set_resource(resource)
+    }
+}
+
+/// Adds the `service.name` and `service.version` of the resource as attributes of the log
+/// records. It's added before the processors exporting the records, which see its changes.
+#[derive(Debug)]
+pub(crate) struct ServiceAttributesProcessor {
+    attributes: Vec<(Key, AnyValue)>,
+}
+
+impl ServiceAttributesProcessor {
+    pub(crate) fn new(resource: &Resource) -> Self {
+        let attributes = [SERVICE_NAME, SERVICE_VERSION]
+            .into_iter()
+            .filter_map(|key| {
+                let value = resource.get(&Key::from_static_str(key))?;
+                Some((Key::from_static_str(key), AnyValue::from(value.to_string())))
+            })
+            .collect();
+        Self { attributes }
+    }
+}
+
+impl LogProcessor for ServiceAttributesProcessor {
+    fn emit(&self, record: &mut SdkLogRecord, _scope: &InstrumentationScope) {
+        for (key, value) in &self.attributes {
+            record.add_attribute(key.clone(), value.clone());
+        }
+    }
+
+    fn force_flush(&self) -> OTelSdkResult {
+        Ok(())
+    }
+}
+
+/// Copies the baggage entries of the parent context whose keys are configured, e.g. a
+/// `tenant.id` propagated by the caller, as attributes of the started spans. It's added before
+/// the processors exporting the spans, which see its changes.
+#[derive(Debug)]
+pub(crate) struct BaggageAttributesProcessor {
+    keys: Vec<Key>,
+}
+
+impl BaggageAttributesProcessor {
+    pub(crate) fn new(keys: Vec<String>) -> Self {
+        let keys = keys.into_iter().map(Key::from).collect();
+        Self { keys }
+    }
+}
+
+impl SpanProcessor for BaggageAttributesProcessor {
+    fn on_start(&self, span: &mut Span, cx: &Context) {
+        let baggage = cx.baggage();
+        for key in &self.keys {
+            if let Some(value) = baggage.get(key.clone()) {
+                span.set_attribute(KeyValue::new(key.clone(), value.clone()));
+            }
+        }
+    }
+
+    fn on_end(&self, _span: SpanData) {}
+
+    fn force_flush(&self) -> OTelSdkResult {
+        Ok(())
+    }
+
+    fn shutdown_with_timeout(&self, _timeout: Duration) -> OTelSdkResult {
+        Ok(())
+    }
+}
+
+/// Adds the attributes set by [`with_request_attributes`](crate::task::with_request_attributes)
+/// for the current task to the started spans and the log records. It's added before the
+/// processors exporting them, which see its changes.
+#[derive(Debug)]
+pub(crate) struct RequestAttributesProcessor;
+
+impl SpanProcessor for RequestAttributesProcessor {
+    fn on_start(&self, span: &mut Span, _cx: &Context) {
+        for attribute in crate::task::request_attributes()
+            .iter()
+            .flat_map(|a| a.iter())
+        {
+            span.set_attribute(attribute.clone());
+        }
+    }
+
+    fn on_end(&self, _span: SpanData) {}
+
+    fn force_flush(&self) -> OTelSdkResult {
+        Ok(())
+    }
+
+    fn shutdown_with_timeout(&self, _timeout: Duration) -> OTelSdkResult {
+        Ok(())
+    }
+}
+
+impl LogProcessor for RequestAttributesProcessor {
+    fn emit(&self, record: &mut SdkLogRecord, _scope: &InstrumentationScope) {
+        for attribute in crate::task::request_attributes()
+            .iter()
+            .flat_map(|a| a.iter())
+        {
+            record.add_attribute(attribute.key.clone(), any_value(&attribute.value));
+        }
+    }
+
+    fn force_flush(&self) -> OTelSdkResult {
+        Ok(())
+    }
+}
+
+/// The log attribute value of a span attribute value, the arrays are formatted.
+fn any_value(value: &Value) -> AnyValue {
+    match value {
+        Value::Bool(value) => AnyValue::Boolean(*value),
+        Value::I64(value) => AnyValue::Int(*value),
+        Value::F64(value) => AnyValue::Double(*value),
+        Value::String(value) => AnyValue::String(value.clone()),
+        value => AnyValue::from(value.to_string()),
+    }
+}
+
+/// The severities of the logs data model, by severity number.
+const SEVERITIES: [Severity; 24] = [
+    Severity::Trace,
+    Severity::Trace2,
+    Severity::Trace3,
+    Severity::Trace4,
+    Severity::Debug,
+    Severity::Debug2,
+    Severity::Debug3,
+    Severity::Debug4,
+    Severity::Info,
+    Severity::Info2,
+    Severity::Info3,
+    Severity::Info4,
+    Severity::Warn,
+    Severity::Warn2,
+    Severity::Warn3,
+    Severity::Warn4,
+    Severity::Error,
+    Severity::Error2,
+    Severity::Error3,
+    Severity::Error4,
+    Severity::Fatal,
+    Severity::Fatal2,
+    Severity::Fatal3,
+    Severity::Fatal4,
+];
+
+/// Overrides the severity number and text of the log records by the `tracing` level set as
+/// their severity text by the log bridge. It's added before the other log processors.
+#[derive(Debug)]
+pub(crate) struct SeverityProcessor {
+    /// The severity number and text of each level name.
+    severities: Vec<(&'static str, Option<Severity>, Option<&'static str>)>,
+}
+
+impl SeverityProcessor {
+    pub(crate) fn new(mapping: &LogsSeverityMapping) -> Self {
+        let severities = [
+            ("TRACE", &mapping.trace),
+            ("DEBUG", &mapping.debug),
+            ("INFO", &mapping.info),
+            ("WARN", &mapping.warn),
+            ("ERROR", &mapping.error),
+        ]
+        .into_iter()
+        .filter_map(|(level, severity)| {
+            let severity = severity.as_ref()?;
+            // the log records only hold static texts, the configured ones are leaked once
+            let text = severity
+                .text
+                .clone()
+                .map(|text| &*Box::leak(text.into_boxed_str()));
+            let number = severity
+                .number
+                .and_then(|number| SEVERITIES.get(usize::from(number).checked_sub(1)?))
+                .copied();
+            Some((level, number, text))
+        })
+        .collect();
+        Self { severities }
+    }
+}
+
+impl LogProcessor for SeverityProcessor {
+    fn emit(&self, record: &mut SdkLogRecord, _scope: &InstrumentationScope) {
+        let Some(level) = record.severity_text() else {
+            return;
+        };
+        let Some((_, number, text)) = self.severities.iter().find(|(name, ..)| *name == level)
+        else {
+            return;
+        };
+        if let Some(number) = number {
+            record.set_severity_number(*number);
+        }
+        if let Some(text) = text {
+            record.set_severity_text(text);
+        }
+    }
+
+    fn force_flush(&self) -> OTelSdkResult {
+        Ok(())
+    }
+}
+
+/// The span processors added in code, taken by the tracer provider when it's built.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct CustomSpanProcessors(Arc<Mutex<Vec<BoxedSpanProcessor>>>);
+
+impl CustomSpanProcessors {
+    pub(crate) fn push<P: SpanProcessor + 'static>(&self, processor: P) {
+        if let Ok(mut processors) = self.0.lock() {
+            processors.push(BoxedSpanProcessor(Box::new(processor)));
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0
+            .lock()
+            .map_or(true, |processors| processors.is_empty())
+    }
+
+    pub(crate) fn take(&self) -> Vec<BoxedSpanProcessor> {
+        self.0
+            .lock()
+            .map(|mut processors| std::mem::take(&mut *processors))
+            .unwrap_or_default()
+    }
+}
+
+/// A boxed span processor, to add to a tracer provider builder.
+#[derive(Debug)]
+pub(crate) struct BoxedSpanProcessor(Box<dyn SpanProcessor>);
+
+impl SpanProcessor for BoxedSpanProcessor {
+    fn on_start(&self, span: &mut Span, cx: &Context) {
+        self.0.on_start(span, cx)
+    }
+
+    fn on_end(&self, span: SpanData) {
+        self.0.on_end(span)
+    }
+
+    fn force_flush(&self) -> OTelSdkResult {
+        self.0.force_flush()
+    }
+
+    fn shutdown_with_timeout(&self, timeout: Duration) -> OTelSdkResult {
+        self.0.shutdown_with_timeout(timeout)
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.0.set_resource(resource)
+    }
+}
+
+/// The log processors added in code, taken by the logger provider when it's built.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct CustomLogProcessors(Arc<Mutex<Vec<BoxedLogProcessor>>>);
+
+impl CustomLogProcessors {
+    pub(crate) fn push<P: LogProcessor + 'static>(&self, processor: P) {
+        if let Ok(mut processors) = self.0.lock() {
+            processors.push(BoxedLogProcessor(Box::new(processor)));
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0
+            .lock()
+            .map_or(true, |processors| processors.is_empty())
+    }
+
+    pub(crate) fn take(&self) -> Vec<BoxedLogProcessor> {
+        self.0
+            .lock()
+            .map(|mut processors| std::mem::take(&mut *processors))
+            .unwrap_or_default()
+    }
+}
+
+/// A boxed log processor, to add to a logger provider builder.
+#[derive(Debug)]
+pub(crate) struct BoxedLogProcessor(Box<dyn LogProcessor>);
+
+impl LogProcessor for BoxedLogProcessor {
+    fn emit(&self, record: &mut SdkLogRecord, scope: &InstrumentationScope) {
+        self.0.emit(record, scope)
+    }
+
+    fn force_flush(&self) -> OTelSdkResult {
+        self.0.force_flush()
+    }
+
+    fn shutdown_with_timeout(&self, timeout: Duration) -> OTelSdkResult {
+        self.0.shutdown_with_timeout(timeout)
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.0.set_resource(resource)
+    }
+}
+
+/// Decides whether a log record is exported, set in code.
+#[derive(Clone)]
+pub(crate) struct LogFilter(Arc<dyn Fn(&SdkLogRecord) -> bool + Send + Sync>);
+
+impl LogFilter {
+    pub(crate) fn new<F>(filter: F) -> Self
+    where
+        F: Fn(&SdkLogRecord) -> bool + Send + Sync + 'static,
+    {
+        Self(Arc::new(filter))
+    }
+}
+
+impl std::fmt::Debug for LogFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("LogFilter")
+    }
+}
+
+/// Hands the log records accepted by the filter to the inner processor and drops the others.
+#[derive(Debug)]
+pub(crate) struct FilterLogProcessor<P> {
+    filter: LogFilter,
+    inner: P,
+}
+
+impl<P> FilterLogProcessor<P> {
+    pub(crate) fn new(filter: LogFilter, inner: P) -> Self {
+        Self { filter, inner }
+    }
+}
+
+impl<P: LogProcessor> LogProcessor for FilterLogProcessor<P> {
+    fn emit(&self, record: &mut SdkLogRecord, scope: &InstrumentationScope) {
+        if (self.filter.0)(record) {
+            self.inner.emit(record, scope)
+        }
+    }
+
+    fn force_flush(&self) -> OTelSdkResult {
+        self.inner.force_flush()
+    }
+
+    fn shutdown_with_timeout(&self, timeout: Duration) -> OTelSdkResult {
+        self.inner.shutdown_with_timeout(timeout)
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.inner.set_resource(resource)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry::trace::{Tracer, TracerProvider};
+    use opentelemetry_sdk::trace::{InMemorySpanExporter, SdkTracerProvider, SimpleSpanProcessor};
+
+    /// Tags the spans with the tenant of the request.
+    #[derive(Debug)]
+    struct TenantProcessor;
+
+    impl SpanProcessor for TenantProcessor {
+        fn on_start(&self, span: &mut Span, _cx: &Context) {
+            span.set_attribute(KeyValue::new("tenant.id", "acme"));
+        }
+
+        fn on_end(&self, _span: SpanData) {}
+
+        fn force_flush(&self) -> OTelSdkResult {
+            Ok(())
+        }
+
+        fn shutdown_with_timeout(&self, _timeout: Duration) -> OTelSdkResult {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_custom_span_processors() {
+        let processors = CustomSpanProcessors::default();
+        processors.clone().push(TenantProcessor);
+        assert!(!processors.is_empty());
+
+        let exporter = InMemorySpanExporter::default();
+        let provider = processors
+            .take()
+            .into_iter()
+            .fold(SdkTracerProvider::builder(), |builder, processor| {
+                builder.with_span_processor(processor)
+            })
+            .with_span_processor(SimpleSpanProcessor::new(exporter.clone()))
+            .build();
+        assert!(processors.is_empty());
+        provider.tracer("test").start("work").end();
+
+        let spans = exporter.get_finished_spans().expect("get spans failed");
+        assert_eq!(spans[0].attributes, [KeyValue::new("tenant.id", "acme")]);
+    }
+
+    #[test]
+    fn test_http_error_status() {
+        let exporter = InMemorySpanExporter::default();
+        let provider = SdkTracerProvider::builder()
+            .with_span_processor(HttpStatusProcessor::new(
+                500,
+                SimpleSpanProcessor::new(exporter.clone()),
+            ))
+            .build();
+        let tracer = provider.tracer("test");
+        for (name, status_code) in [("ok", Value::I64(200)), ("not_found", Value::I64(404))] {
+            let mut span = tracer.start(name);
+            span.set_attribute(KeyValue::new(HTTP_RESPONSE_STATUS_CODE, status_code));
+            span.end();
+        }
+        for name in ["unavailable", "handled"] {
+            let mut span = tracer.start(name);
+            span.set_attribute(KeyValue::new(HTTP_RESPONSE_STATUS_CODE, "503"));
+            if name == "handled" {
+                span.set_status(Status::Ok);
+            }
+            span.end();
+        }
+
+        let spans = exporter.get_finished_spans().expect("get spans failed");
+        let status = |name: &str| {
+            let span = spans.iter().find(|span| span.name == name).unwrap();
+            span.status.clone()
+        };
+        assert_eq!(status("ok"), Status::Unset);
+        assert_eq!(status("not_found"), Status::Unset);
+        assert_eq!(status("unavailable"), Status::error(""));
+        assert_eq!(status("handled"), Status::Ok);
+    }
+
+    #[test]
+    fn test_keep_error_spans() {
+        use crate::sampler::RecordUnsampledSampler;
+        use opentelemetry_sdk::trace::Sampler;
+
+        let exporter = InMemorySpanExporter::default();
+        let provider = SdkTracerProvider::builder()
+            .with_sampler(RecordUnsampledSampler::new(Sampler::ParentBased(Box::new(
+                Sampler::TraceIdRatioBased(0.01),
+            ))))
+            .with_span_processor(ErrorSpanProcessor::new(SimpleSpanProcessor::new(
+                exporter.clone(),
+            )))
+            .build();
+        let tracer = provider.tracer("test");
+        for _ in 0..1000 {
+            tracer.start("ok").end();
+            let mut span = tracer.start("failed");
+            span.set_status(Status::error("timeout"));
+            span.end();
+        }
+
+        let spans = exporter.get_finished_spans().expect("get spans failed");
+        let count = |name: &str| spans.iter().filter(|span| span.name == name).count();
+        assert_eq!(count("failed"), 1000);
+        assert!(count("ok") < 100);
+        assert!(spans.iter().all(|span| span.span_context.is_sampled()));
+    }
+
+    #[test]
+    fn test_normalize_span_name() {
+        let processor = SpanNameProcessor::new(
+            vec!["/users/{id}".into(), "/orders/{id}/items/{item_id}".into()],
+            SimpleSpanProcessor::new(InMemorySpanExporter::default()),
+        );
+        let normalize = |name: &str| processor.normalize(name);
+        assert_eq!(
+            normalize("GET /users/12345"),
+            Some("GET /users/{id}".into())
+        );
+        assert_eq!(
+            normalize("/orders/7/items/42?expand=true"),
+            Some("/orders/{id}/items/{item_id}".into())
+        );
+        assert_eq!(normalize("GET /users/12345/avatar"), None);
+        assert_eq!(normalize("GET /users/"), None);
+        assert_eq!(normalize("HTTP"), None);
+    }
+
+    #[test]
+    fn test_redact_attributes() {
+        let exporter = InMemorySpanExporter::default();
+        let processor = RedactionProcessor::new(
+            vec!["user.email".into(), "http.request.header.*".into()],
+            SimpleSpanProcessor::new(exporter.clone()),
+        );
+        let provider = SdkTracerProvider::builder()
+            .with_span_processor(processor)
+            .build();
+        let mut span = provider.tracer("test").start("work");
+        span.set_attribute(KeyValue::new("user.email", "jane@example.com"));
+        span.set_attribute(KeyValue::new(
+            "http.request.header.authorization",
+            "Bearer t0k3n",
+        ));
+        span.set_attribute(KeyValue::new("user.id", "42"));
+        span.add_event(
+            "login",
+            vec![KeyValue::new("user.email", "jane@example.com")],
+        );
+        span.end();
+
+        let spans = exporter.get_finished_spans().expect("get spans failed");
+        assert_eq!(
+            spans[0].attributes,
+            [
+                KeyValue::new("user.email", REDACTED),
+                KeyValue::new("http.request.header.authorization", REDACTED),
+                KeyValue::new("user.id", "42"),
+            ]
+        );
+        assert_eq!(
+            spans[0].events.events[0].attributes,
+            [KeyValue::new("user.email", REDACTED)]
+        );
+    }
+
+    #[test]
+    fn test_baggage_attributes() {
+        let exporter = InMemorySpanExporter::default();
+        let provider = SdkTracerProvider::builder()
+            .with_span_processor(BaggageAttributesProcessor::new(vec!["tenant.id".into()]))
+            .with_span_processor(SimpleSpanProcessor::new(exporter.clone()))
+            .build();
+        let cx = Context::new().with_baggage([
+            KeyValue::new("tenant.id", "acme"),
+            KeyValue::new("user.id", "42"),
+        ]);
+        let tracer = provider.tracer("test");
+        tracer.start_with_context("work", &cx).end();
+        tracer.start("no_baggage").end();
+
+        let spans = exporter.get_finished_spans().expect("get spans failed");
+        assert_eq!(spans[0].attributes, [KeyValue::new("tenant.id", "acme")]);
+        assert!(spans[1].attributes.is_empty());
+    }
+
+    #[test]
+    fn test_rename_attributes() {
+        let exporter = InMemorySpanExporter::default();
+        let processor = AttributeRenameProcessor::new(
+            HashMap::from([
+                ("http.request.method".into(), "http.method".into()),
+                ("url.path".into(), "http.target".into()),
+            ]),
+            SimpleSpanProcessor::new(exporter.clone()),
+        );
+        let provider = SdkTracerProvider::builder()
+            .with_span_processor(processor)
+            .build();
+        let mut span = provider.tracer("test").start("GET");
+        span.set_attribute(KeyValue::new("http.request.method", "GET"));
+        span.set_attribute(KeyValue::new("http.target", "/orders?page=2"));
+        span.set_attribute(KeyValue::new("url.path", "/orders"));
+        span.set_attribute(KeyValue::new("user.id", "42"));
+        span.end();
+
+        let spans = exporter.get_finished_spans().expect("get spans failed");
+        assert_eq!(
+            spans[0].attributes,
+            [
+                KeyValue::new("http.method", "GET"),
+                KeyValue::new("http.target", "/orders?page=2"),
+                KeyValue::new("user.id", "42"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_service_attributes() {
+        use opentelemetry::logs::{Logger, LoggerProvider};
+        use opentelemetry_sdk::logs::{InMemoryLogExporter, SdkLoggerProvider};
+
+        let resource = Resource::builder_empty()
+            .with_attributes([
+                KeyValue::new(SERVICE_NAME, "payments"),
+                KeyValue::new(SERVICE_VERSION, "1.2.0"),
+            ])
+            .build();
+        let exporter = InMemoryLogExporter::default();
+        let provider = SdkLoggerProvider::builder()
+            .with_resource(resource.clone())
+            .with_log_processor(ServiceAttributesProcessor::new(&resource))
+            .with_simple_exporter(exporter.clone())
+            .build();
+        let logger = provider.logger("test");
+        logger.emit(logger.create_log_record());
+
+        let logs = exporter.get_emitted_logs().expect("get logs failed");
+        let attributes: Vec<_> = logs[0]
+            .record
+            .attributes_iter()
+            .map(|(key, value)| (key.to_string(), value.clone()))
+            .collect();
+        assert_eq!(
+            attributes,
+            [
+                (SERVICE_NAME.to_string(), AnyValue::from("payments")),
+                (SERVICE_VERSION.to_string(), AnyValue::from("1.2.0")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_logs_severity() {
+        use opentelemetry::logs::{Logger, LoggerProvider};
+        use opentelemetry_sdk::logs::{InMemoryLogExporter, SdkLoggerProvider};
+        use serde::Deserialize;
+
+        let mapping = LogsSeverityMapping::deserialize(toml::toml! {
+            warn = { number = 14, text = "WARNING" }
+            error = { text = "ERR" }
+        })
+        .expect("mapping deserialize failed");
+        let exporter = InMemoryLogExporter::default();
+        let provider = SdkLoggerProvider::builder()
+            .with_log_processor(SeverityProcessor::new(&mapping))
+            .with_simple_exporter(exporter.clone())
+            .build();
+        let logger = provider.logger("test");
+        for (severity, text) in [
+            (Severity::Warn, "WARN"),
+            (Severity::Error, "ERROR"),
+            (Severity::Info, "INFO"),
+        ] {
+            let mut record = logger.create_log_record();
+            record.set_severity_number(severity);
+            record.set_severity_text(text);
+            logger.emit(record);
+        }
+
+        let logs = exporter.get_emitted_logs().expect("get logs failed");
+        let severities: Vec<_> = logs
+            .iter()
+            .map(|log| (log.record.severity_number(), log.record.severity_text()))
+            .collect();
+        assert_eq!(
+            severities,
+            [
+                (Some(Severity::Warn2), Some("WARNING")),
+                (Some(Severity::Error), Some("ERR")),
+                (Some(Severity::Info), Some("INFO")),
+            ]
+        );
+
+        let error = LogsSeverityMapping::deserialize(toml::toml! { warn = { number = 25 } })
+            .expect_err("25 is not a severity number");
+        assert!(error.to_string().contains("between 1 and 24"));
+    }
+
+    /// Tags the log records with the tenant of the request.
+    #[derive(Debug)]
+    struct TenantLogProcessor;
+
+    impl LogProcessor for TenantLogProcessor {
+        fn emit(&self, record: &mut SdkLogRecord, _scope: &InstrumentationScope) {
+            record.add_attribute("tenant.id", "acme");
+        }
+
+        fn force_flush(&self) -> OTelSdkResult {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_custom_log_processors() {
+        use opentelemetry::logs::{Logger, LoggerProvider};
+        use opentelemetry_sdk::logs::{InMemoryLogExporter, SdkLoggerProvider, SimpleLogProcessor};
+
+        let processors = CustomLogProcessors::default();
+        processors.push(TenantLogProcessor);
+        let filter = LogFilter::new(|record| record.body() != Some(&AnyValue::from("GET /health")));
+        let exporter = InMemoryLogExporter::default();
+        let provider = processors
+            .take()
+            .into_iter()
+            .fold(SdkLoggerProvider::builder(), |builder, processor| {
+                builder.with_log_processor(processor)
+            })
+            .with_log_processor(FilterLogProcessor::new(
+                filter,
+                SimpleLogProcessor::new(exporter.clone()),
+            ))
+            .build();
+        let logger = provider.logger("test");
+        for body in ["GET /health", "order created"] {
+            let mut record = logger.create_log_record();
+            record.set_body(body.into());
+            logger.emit(record);
+        }
+
+        let logs = exporter.get_emitted_logs().expect("get logs failed");
+        assert_eq!(logs.len(), 1);
+        let record = &logs[0].record;
+        assert_eq!(record.body(), Some(&AnyValue::from("order created")));
+        let attributes: Vec<_> = record.attributes_iter().collect();
+        assert_eq!(
+            attributes,
+            [&(Key::from("tenant.id"), AnyValue::from("acme"))]
+        );
+    }
+
+    #[test]
+    fn test_truncate() {
+        let mut value = Value::from("héllo world");
+        truncate(&mut value, 5);
+        assert_eq!(value, Value::from("héllo"));
+
+        let mut value = Value::from("short");
+        truncate(&mut value, 5);
+        assert_eq!(value, Value::from("short"));
+
+        let mut value = Value::Array(Array::String(vec!["abcdef".into(), "ab".into()]));
+        truncate(&mut value, 3);
+        assert_eq!(
+            value,
+            Value::Array(Array::String(vec!["abc".into(), "ab".into()]))
+        );
+
+        let mut value = Value::I64(123456);
+        truncate(&mut value, 3);
+        assert_eq!(value, Value::I64(123456));
+    }
+}
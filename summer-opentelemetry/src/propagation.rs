@@ -0,0 +1,169 @@
+//! Manual propagation of the trace context with the propagators of the plugin, e.g. for
+//! outbound requests of HTTP clients that aren't instrumented.
+//!
+//! ```rust,ignore
+//! let mut headers = http::HeaderMap::new();
+//! summer_opentelemetry::inject_context(&mut headers);
+//! let response = client.get(url).headers(headers).send().await?;
+//! ```
+
+#[cfg(feature = "datadog")]
+mod datadog;
+
+#[cfg(feature = "datadog")]
+pub(crate) use datadog::DatadogPropagator;
+use http::HeaderMap;
+use opentelemetry::trace::{Link, TraceContextExt};
+use opentelemetry::Context;
+use opentelemetry_http::{HeaderExtractor, HeaderInjector};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+/// The OpenTelemetry context of the current `tracing` span, or the current OpenTelemetry
+/// context when no span is entered, e.g. to capture it before handing work to another thread
+/// and restore it there with [`Context::attach`].
+pub fn current_context() -> Context {
+    let cx = tracing::Span::current().context();
+    if cx.has_active_span() {
+        cx
+    } else {
+        Context::current()
+    }
+}
+
+/// Injects the context of the current span into `headers`, with the global propagator set up
+/// by the plugin. The current OpenTelemetry context is injected when no span is entered.
+pub fn inject_context(headers: &mut HeaderMap) {
+    let cx = current_context();
+    opentelemetry::global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(&cx, &mut HeaderInjector(headers))
+    });
+}
+
+/// Extracts the context propagated in `headers`, with the global propagator set up by the plugin.
+///
+/// It can be set as the parent of a span with [`OpenTelemetrySpanExt::set_parent`].
+pub fn extract_context(headers: &HeaderMap) -> Context {
+    opentelemetry::global::get_text_map_propagator(|propagator| {
+        propagator.extract(&HeaderExtractor(headers))
+    })
+}
+
+/// The links to the spans of `contexts`, e.g. the contexts extracted from the messages of a
+/// batch, each with its own upstream trace. The contexts without a valid span are skipped.
+///
+/// They are attached to a span with [`add_links`], or with `SpanBuilder::with_links` when the
+/// span is started with the OpenTelemetry API.
+pub fn links(contexts: impl IntoIterator<Item = Context>) -> Vec<Link> {
+    contexts
+        .into_iter()
+        .filter_map(|cx| {
+            let span_context = cx.span().span_context().clone();
+            span_context
+                .is_valid()
+                .then(|| Link::with_context(span_context))
+        })
+        .collect()
+}
+
+/// The links to the spans of the contexts propagated in each of `headers`, see [`links`].
+pub fn extract_links<'a>(headers: impl IntoIterator<Item = &'a HeaderMap>) -> Vec<Link> {
+    links(headers.into_iter().map(extract_context))
+}
+
+/// Attaches `links` to `span`, before any span is started within it. The links exceeding the
+/// `max_links_per_span` of the span limits are dropped by the SDK.
+pub fn add_links(span: &tracing::Span, links: Vec<Link>) {
+    for link in links {
+        span.add_link_with_attributes(link.span_context, link.attributes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::OpenTelemetryConfig;
+    use opentelemetry::trace::{SpanContext, SpanId, TraceFlags, TraceId, TraceState};
+    use serde::Deserialize;
+
+    #[test]
+    fn test_inject_extract_round_trip() {
+        let config = OpenTelemetryConfig::deserialize(toml::toml! {
+            enable = true
+            propagators = ["tracecontext"]
+        })
+        .expect("config deserialize failed");
+        let _propagator = crate::tests::install_propagator(&config);
+
+        let span_context = SpanContext::new(
+            TraceId::from_hex("0af7651916cd43dd8448eb211c80319c").unwrap(),
+            SpanId::from_hex("b7ad6b7169203331").unwrap(),
+            TraceFlags::SAMPLED,
+            true,
+            TraceState::default(),
+        );
+        let _guard = Context::new()
+            .with_remote_span_context(span_context.clone())
+            .attach();
+
+        let mut headers = HeaderMap::new();
+        inject_context(&mut headers);
+        assert_eq!(
+            headers["traceparent"],
+            "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01"
+        );
+
+        let cx = extract_context(&headers);
+        assert_eq!(cx.span().span_context(), &span_context);
+    }
+
+    #[test]
+    fn test_batch_span_links() {
+        use opentelemetry::trace::TracerProvider;
+        use opentelemetry_sdk::trace::{InMemorySpanExporter, SdkTracerProvider};
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let config = OpenTelemetryConfig::deserialize(toml::toml! {
+            enable = true
+            propagators = ["tracecontext"]
+        })
+        .expect("config deserialize failed");
+        let _propagator = crate::tests::install_propagator(&config);
+        let messages: Vec<HeaderMap> = [
+            "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "invalid",
+        ]
+        .into_iter()
+        .map(|traceparent| {
+            let mut headers = HeaderMap::new();
+            headers.insert("traceparent", traceparent.parse().unwrap());
+            headers
+        })
+        .collect();
+
+        let exporter = InMemorySpanExporter::default();
+        let provider = SdkTracerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("process_batch");
+            add_links(&span, extract_links(&messages));
+        });
+
+        let spans = exporter.get_finished_spans().unwrap();
+        let trace_ids: Vec<_> = spans[0]
+            .links
+            .iter()
+            .map(|link| link.span_context.trace_id().to_string())
+            .collect();
+        assert_eq!(
+            trace_ids,
+            [
+                "0af7651916cd43dd8448eb211c80319c",
+                "4bf92f3577b34da6a3ce929d0e0e4736"
+            ]
+        );
+    }
+}
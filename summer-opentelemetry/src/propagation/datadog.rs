@@ -0,0 +1,174 @@
+//! Propagation of the trace context in the headers of the Datadog tracers, e.g. to keep the
+//! traces continuous while being migrated from Datadog APM.
+//!
+//! refs: <https://docs.datadoghq.com/tracing/trace_collection/trace_context_propagation/>
+
+use opentelemetry::propagation::TextMapPropagator;
+use opentelemetry::propagation::{text_map_propagator::FieldIter, Extractor, Injector};
+use opentelemetry::trace::{SpanContext, SpanId, TraceContextExt, TraceFlags, TraceId, TraceState};
+use opentelemetry::Context;
+use std::sync::OnceLock;
+
+const TRACE_ID_HEADER: &str = "x-datadog-trace-id";
+const PARENT_ID_HEADER: &str = "x-datadog-parent-id";
+const SAMPLING_PRIORITY_HEADER: &str = "x-datadog-sampling-priority";
+const TAGS_HEADER: &str = "x-datadog-tags";
+
+/// The tag of the `x-datadog-tags` header holding the upper 64 bits of a 128-bit trace id,
+/// the `x-datadog-trace-id` header holding the lower 64 bits.
+const TRACE_ID_HIGH_TAG: &str = "_dd.p.tid";
+
+fn fields() -> &'static [String] {
+    static FIELDS: OnceLock<Vec<String>> = OnceLock::new();
+    FIELDS.get_or_init(|| {
+        [
+            TRACE_ID_HEADER,
+            PARENT_ID_HEADER,
+            SAMPLING_PRIORITY_HEADER,
+            TAGS_HEADER,
+        ]
+        .map(String::from)
+        .to_vec()
+    })
+}
+
+/// Extracts and injects the `x-datadog-*` headers, with decimal ids.
+///
+/// A trace is sampled unless the sampling priority rejects it, i.e. is `0` or negative.
+#[derive(Debug, Default)]
+pub(crate) struct DatadogPropagator;
+
+impl DatadogPropagator {
+    fn extract_span_context(extractor: &dyn Extractor) -> Option<SpanContext> {
+        let low: u64 = extractor.get(TRACE_ID_HEADER)?.trim().parse().ok()?;
+        let span_id: u64 = extractor.get(PARENT_ID_HEADER)?.trim().parse().ok()?;
+        let high = extractor
+            .get(TAGS_HEADER)
+            .and_then(|tags| {
+                tags.split(',')
+                    .find_map(|tag| {
+                        tag.trim()
+                            .strip_prefix(TRACE_ID_HIGH_TAG)?
+                            .strip_prefix('=')
+                    })
+                    .and_then(|high| u64::from_str_radix(high, 16).ok())
+            })
+            .unwrap_or_default();
+        let rejected = extractor
+            .get(SAMPLING_PRIORITY_HEADER)
+            .and_then(|priority| priority.trim().parse::<i32>().ok())
+            .is_some_and(|priority| priority <= 0);
+        let flags = if rejected {
+            TraceFlags::default()
+        } else {
+            TraceFlags::SAMPLED
+        };
+        let trace_id = TraceId::from(((high as u128) << 64) | low as u128);
+        let span_context = SpanContext::new(
+            trace_id,
+            SpanId::from(span_id),
+            flags,
+            true,
+            TraceState::default(),
+        );
+        span_context.is_valid().then_some(span_context)
+    }
+}
+
+impl TextMapPropagator for DatadogPropagator {
+    fn inject_context(&self, cx: &Context, injector: &mut dyn Injector) {
+        let span = cx.span();
+        let span_context = span.span_context();
+        if !span_context.is_valid() {
+            return;
+        }
+        let trace_id = u128::from_be_bytes(span_context.trace_id().to_bytes());
+        let span_id = u64::from_be_bytes(span_context.span_id().to_bytes());
+        injector.set(TRACE_ID_HEADER, (trace_id as u64).to_string());
+        injector.set(PARENT_ID_HEADER, span_id.to_string());
+        let priority = if span_context.is_sampled() { "1" } else { "0" };
+        injector.set(SAMPLING_PRIORITY_HEADER, priority.to_string());
+        let high = (trace_id >> 64) as u64;
+        if high != 0 {
+            injector.set(TAGS_HEADER, format!("{TRACE_ID_HIGH_TAG}={high:016x}"));
+        }
+    }
+
+    fn extract_with_context(&self, cx: &Context, extractor: &dyn Extractor) -> Context {
+        match Self::extract_span_context(extractor) {
+            Some(span_context) => cx.with_remote_span_context(span_context),
+            None => cx.clone(),
+        }
+    }
+
+    fn fields(&self) -> FieldIter<'_> {
+        FieldIter::new(fields())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn extract(headers: &[(&str, &str)]) -> SpanContext {
+        let headers: HashMap<String, String> = headers
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        let cx = DatadogPropagator.extract(&headers);
+        cx.span().span_context().clone()
+    }
+
+    #[test]
+    fn test_extract_datadog_headers() {
+        let span_context = extract(&[
+            (TRACE_ID_HEADER, "7277407061855694839"),
+            (PARENT_ID_HEADER, "1178849433637479299"),
+            (SAMPLING_PRIORITY_HEADER, "1"),
+        ]);
+        assert_eq!(
+            span_context.trace_id().to_string(),
+            "000000000000000064fe8b2a57d3eff7"
+        );
+        assert_eq!(span_context.span_id().to_string(), "105c1d5067a1e383");
+        assert!(span_context.is_sampled());
+        assert!(span_context.is_remote());
+
+        let span_context = extract(&[
+            (TRACE_ID_HEADER, "7277407061855694839"),
+            (PARENT_ID_HEADER, "1178849433637479299"),
+            (SAMPLING_PRIORITY_HEADER, "-1"),
+            (TAGS_HEADER, "_dd.p.dm=-4,_dd.p.tid=640cfd8d00000000"),
+        ]);
+        assert_eq!(
+            span_context.trace_id().to_string(),
+            "640cfd8d0000000064fe8b2a57d3eff7"
+        );
+        assert!(!span_context.is_sampled());
+
+        assert!(!extract(&[(TRACE_ID_HEADER, "0"), (PARENT_ID_HEADER, "1")]).is_valid());
+        assert!(!extract(&[(TRACE_ID_HEADER, "abc"), (PARENT_ID_HEADER, "1")]).is_valid());
+    }
+
+    #[test]
+    fn test_inject_round_trip() {
+        let span_context = SpanContext::new(
+            TraceId::from_hex("640cfd8d0000000064fe8b2a57d3eff7").unwrap(),
+            SpanId::from_hex("105c1d5067a1e383").unwrap(),
+            TraceFlags::SAMPLED,
+            true,
+            TraceState::default(),
+        );
+        let cx = Context::new().with_remote_span_context(span_context.clone());
+        let mut headers = HashMap::new();
+        DatadogPropagator.inject_context(&cx, &mut headers);
+        assert_eq!(headers[TRACE_ID_HEADER], "7277407061855694839");
+        assert_eq!(headers[PARENT_ID_HEADER], "1178849433637479299");
+        assert_eq!(headers[SAMPLING_PRIORITY_HEADER], "1");
+        assert_eq!(headers[TAGS_HEADER], "_dd.p.tid=640cfd8d00000000");
+
+        let extracted = DatadogPropagator.extract(&headers);
+        assert_eq!(extracted.span().span_context(), &span_context);
+    }
+}
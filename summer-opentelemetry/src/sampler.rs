@@ -0,0 +1,486 @@
+//! Samplers built from the configuration.
+
+use opentelemetry::trace::{
+    Link, SamplingDecision, SamplingResult, SpanContext, SpanKind, TraceContextExt, TraceId,
+};
+use opentelemetry::{Context, KeyValue, Value};
+use opentelemetry_sdk::trace::{Sampler, ShouldSample};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Attributes holding the path of an HTTP request: `url.path` of the current semantic
+/// conventions and the deprecated `http.target`.
+const PATH_ATTRIBUTES: [&str; 2] = ["url.path", "http.target"];
+
+/// Drops the spans of requests whose path starts with one of the excluded prefixes, and the
+/// spans whose name matches one of the excluded names, where `*` and `?` are wildcards. All
+/// other spans are delegated to the inner sampler.
+///
+/// Child spans of a dropped span are dropped too when the inner sampler respects the
+/// sampling decision of the parent.
+#[derive(Debug, Clone)]
+pub(crate) struct ExcludedSpansSampler<S> {
+    excluded_paths: Vec<String>,
+    excluded_names: Vec<String>,
+    inner: S,
+}
+
+impl<S> ExcludedSpansSampler<S> {
+    pub(crate) fn new(excluded_paths: Vec<String>, excluded_names: Vec<String>, inner: S) -> Self {
+        Self {
+            excluded_paths,
+            excluded_names,
+            inner,
+        }
+    }
+
+    fn is_excluded(&self, name: &str, attributes: &[KeyValue]) -> bool {
+        let excluded_name = self
+            .excluded_names
+            .iter()
+            .any(|pattern| crate::view::wildcard_match(pattern, name));
+        excluded_name
+            || request_paths(attributes).any(|path| {
+                self.excluded_paths
+                    .iter()
+                    .any(|prefix| path.starts_with(prefix.as_str()))
+            })
+    }
+}
+
+/// The request paths in the attributes of a span.
+fn request_paths(attributes: &[KeyValue]) -> impl Iterator<Item = &str> {
+    attributes
+        .iter()
+        .filter(|kv| PATH_ATTRIBUTES.contains(&kv.key.as_str()))
+        .filter_map(|kv| match &kv.value {
+            Value::String(path) => Some(path.as_str()),
+            _ => None,
+        })
+}
+
+impl<S: ShouldSample + Clone + 'static> ShouldSample for ExcludedSpansSampler<S> {
+    fn should_sample(
+        &self,
+        parent_context: Option<&Context>,
+        trace_id: TraceId,
+        name: &str,
+        span_kind: &SpanKind,
+        attributes: &[KeyValue],
+        links: &[Link],
+    ) -> SamplingResult {
+        if self.is_excluded(name, attributes) {
+            return SamplingResult {
+                decision: SamplingDecision::Drop,
+                attributes: Vec::new(),
+                trace_state: parent_context
+                    .map(|cx| cx.span().span_context().trace_state().clone())
+                    .unwrap_or_default(),
+            };
+        }
+        self.inner
+            .should_sample(parent_context, trace_id, name, span_kind, attributes, links)
+    }
+}
+
+/// Samples the spans whose parent is sampled, e.g. by an upstream service propagating the
+/// `sampled` flag, and delegates the other spans to the inner sampler.
+///
+/// The sampling decision of a trace is then made once by its first service, even when the
+/// inner sampler doesn't respect the parent, e.g. a ratio sampler set with the builder.
+#[derive(Debug, Clone)]
+pub(crate) struct SampledParentSampler<S> {
+    inner: S,
+}
+
+impl<S> SampledParentSampler<S> {
+    pub(crate) fn new(inner: S) -> Self {
+        Self { inner }
+    }
+}
+
+impl<S: ShouldSample + Clone + 'static> ShouldSample for SampledParentSampler<S> {
+    fn should_sample(
+        &self,
+        parent_context: Option<&Context>,
+        trace_id: TraceId,
+        name: &str,
+        span_kind: &SpanKind,
+        attributes: &[KeyValue],
+        links: &[Link],
+    ) -> SamplingResult {
+        match sampled_parent(parent_context) {
+            Some(parent) => SamplingResult {
+                decision: SamplingDecision::RecordAndSample,
+                attributes: Vec::new(),
+                trace_state: parent.trace_state().clone(),
+            },
+            None => self.inner.should_sample(
+                parent_context,
+                trace_id,
+                name,
+                span_kind,
+                attributes,
+                links,
+            ),
+        }
+    }
+}
+
+/// The span context of the parent of a span, when it's sampled.
+fn sampled_parent(parent_context: Option<&Context>) -> Option<SpanContext> {
+    parent_context
+        .filter(|cx| cx.has_active_span())
+        .map(|cx| cx.span().span_context().clone())
+        .filter(|parent| parent.is_valid() && parent.is_sampled())
+}
+
+/// Drops the spans whose parent isn't sampled, e.g. the root spans of new requests, once the
+/// application is draining, and delegates all spans to the inner sampler otherwise.
+///
+/// The requests in flight keep recording their child spans, so that their traces are complete
+/// when the buffered telemetry is flushed.
+#[derive(Debug, Clone)]
+pub(crate) struct DrainingSampler<S> {
+    draining: Arc<AtomicBool>,
+    inner: S,
+}
+
+impl<S> DrainingSampler<S> {
+    pub(crate) fn new(draining: Arc<AtomicBool>, inner: S) -> Self {
+        Self { draining, inner }
+    }
+}
+
+impl<S: ShouldSample + Clone + 'static> ShouldSample for DrainingSampler<S> {
+    fn should_sample(
+        &self,
+        parent_context: Option<&Context>,
+        trace_id: TraceId,
+        name: &str,
+        span_kind: &SpanKind,
+        attributes: &[KeyValue],
+        links: &[Link],
+    ) -> SamplingResult {
+        if self.draining.load(Ordering::Relaxed) && sampled_parent(parent_context).is_none() {
+            return SamplingResult {
+                decision: SamplingDecision::Drop,
+                attributes: Vec::new(),
+                trace_state: parent_context
+                    .map(|cx| cx.span().span_context().trace_state().clone())
+                    .unwrap_or_default(),
+            };
+        }
+        self.inner
+            .should_sample(parent_context, trace_id, name, span_kind, attributes, links)
+    }
+}
+
+/// Records the spans dropped by the inner sampler without sampling them, so that the
+/// [`ErrorSpanProcessor`](crate::processor::ErrorSpanProcessor) sees them when they end
+/// and exports the ones that ended with an error.
+///
+/// The recorded spans are still propagated as not sampled.
+#[derive(Debug, Clone)]
+pub(crate) struct RecordUnsampledSampler<S> {
+    inner: S,
+}
+
+impl<S> RecordUnsampledSampler<S> {
+    pub(crate) fn new(inner: S) -> Self {
+        Self { inner }
+    }
+}
+
+impl<S: ShouldSample + Clone + 'static> ShouldSample for RecordUnsampledSampler<S> {
+    fn should_sample(
+        &self,
+        parent_context: Option<&Context>,
+        trace_id: TraceId,
+        name: &str,
+        span_kind: &SpanKind,
+        attributes: &[KeyValue],
+        links: &[Link],
+    ) -> SamplingResult {
+        let mut result =
+            self.inner
+                .should_sample(parent_context, trace_id, name, span_kind, attributes, links);
+        if result.decision == SamplingDecision::Drop {
+            result.decision = SamplingDecision::RecordOnly;
+        }
+        result
+    }
+}
+
+/// Samples the spans of requests whose path starts with the prefix of a rule with the ratio of
+/// the first matching rule, and delegates all other spans to the default sampler.
+#[derive(Debug, Clone)]
+pub(crate) struct RouteSampler<S> {
+    rules: Vec<(String, Sampler)>,
+    default: S,
+}
+
+impl<S> RouteSampler<S> {
+    pub(crate) fn new(rules: Vec<(String, f64)>, default: S) -> Self {
+        let rules = rules
+            .into_iter()
+            .map(|(prefix, ratio)| (prefix, Sampler::TraceIdRatioBased(ratio)))
+            .collect();
+        Self { rules, default }
+    }
+}
+
+impl<S: ShouldSample + Clone + 'static> ShouldSample for RouteSampler<S> {
+    fn should_sample(
+        &self,
+        parent_context: Option<&Context>,
+        trace_id: TraceId,
+        name: &str,
+        span_kind: &SpanKind,
+        attributes: &[KeyValue],
+        links: &[Link],
+    ) -> SamplingResult {
+        let rule = request_paths(attributes).find_map(|path| {
+            self.rules
+                .iter()
+                .find(|(prefix, _)| path.starts_with(prefix.as_str()))
+        });
+        match rule {
+            Some((_, sampler)) => {
+                sampler.should_sample(parent_context, trace_id, name, span_kind, attributes, links)
+            }
+            None => self.default.should_sample(
+                parent_context,
+                trace_id,
+                name,
+                span_kind,
+                attributes,
+                links,
+            ),
+        }
+    }
+}
+
+/// The sampler polling the strategy of `service_name` from the Jaeger remote sampling endpoint
+/// every `polling_interval_ms`, `default` samples the traces until the first strategy is
+/// fetched. The polling task is spawned on the current Tokio runtime.
+#[cfg(feature = "jaeger-remote-sampler")]
+pub(crate) fn jaeger_remote_sampler(
+    config: &crate::config::JaegerRemoteSamplerConfig,
+    service_name: &str,
+    default: Sampler,
+) -> anyhow::Result<Sampler> {
+    use anyhow::Context as _;
+
+    // the SDK falls back to the default endpoint when the endpoint is invalid
+    reqwest::Url::parse(&config.endpoint).with_context(|| {
+        format!(
+            "invalid jaeger_remote_sampler endpoint `{}`",
+            config.endpoint
+        )
+    })?;
+    if config.polling_interval_ms == 0 {
+        anyhow::bail!("jaeger_remote_sampler polling_interval_ms must be greater than 0");
+    }
+    let client = reqwest::Client::new();
+    Sampler::jaeger_remote(
+        opentelemetry_sdk::runtime::Tokio,
+        client,
+        default,
+        service_name,
+    )
+    .with_endpoint(config.endpoint.as_str())
+    .with_update_interval(std::time::Duration::from_millis(config.polling_interval_ms))
+    .build()
+    .context("build jaeger remote sampler failed")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decision(sampler: &impl ShouldSample, attributes: &[KeyValue]) -> SamplingDecision {
+        decision_of_trace(sampler, TraceId::from(1), attributes)
+    }
+
+    fn decision_of_trace(
+        sampler: &impl ShouldSample,
+        trace_id: TraceId,
+        attributes: &[KeyValue],
+    ) -> SamplingDecision {
+        sampler
+            .should_sample(None, trace_id, "GET", &SpanKind::Server, attributes, &[])
+            .decision
+    }
+
+    #[test]
+    fn test_excluded_paths() {
+        let sampler = ExcludedSpansSampler::new(
+            vec!["/health".to_string(), "/metrics".to_string()],
+            Vec::new(),
+            Sampler::AlwaysOn,
+        );
+        let path = |path: &'static str| [KeyValue::new("url.path", path)];
+
+        assert_eq!(
+            decision(&sampler, &path("/healthz")),
+            SamplingDecision::Drop
+        );
+        assert_eq!(
+            decision(&sampler, &path("/metrics")),
+            SamplingDecision::Drop
+        );
+        assert_eq!(
+            decision(&sampler, &[KeyValue::new("http.target", "/health?full=1")]),
+            SamplingDecision::Drop
+        );
+        assert_eq!(
+            decision(&sampler, &path("/api/orders")),
+            SamplingDecision::RecordAndSample
+        );
+        assert_eq!(decision(&sampler, &[]), SamplingDecision::RecordAndSample);
+    }
+
+    #[test]
+    fn test_excluded_names() {
+        let sampler = ExcludedSpansSampler::new(
+            Vec::new(),
+            vec!["pool.acquire".to_string(), "redis.*".to_string()],
+            Sampler::AlwaysOn,
+        );
+        let decision = |name: &str| {
+            sampler
+                .should_sample(None, TraceId::from(1), name, &SpanKind::Internal, &[], &[])
+                .decision
+        };
+
+        assert_eq!(decision("pool.acquire"), SamplingDecision::Drop);
+        assert_eq!(decision("redis.ping"), SamplingDecision::Drop);
+        assert_eq!(
+            decision("pool.acquire_timeout"),
+            SamplingDecision::RecordAndSample
+        );
+        assert_eq!(decision("GET /orders"), SamplingDecision::RecordAndSample);
+    }
+
+    #[test]
+    fn test_sampled_parent_respected() {
+        use opentelemetry::trace::{
+            SpanContext, SpanId, TraceFlags, TraceState, Tracer, TracerProvider,
+        };
+        use opentelemetry_sdk::trace::{InMemorySpanExporter, SdkTracerProvider};
+
+        // a downsampled service receiving a 64-bit B3 trace id, padded to 128 bits
+        let exporter = InMemorySpanExporter::default();
+        let provider = SdkTracerProvider::builder()
+            .with_sampler(SampledParentSampler::new(Sampler::TraceIdRatioBased(0.0)))
+            .with_simple_exporter(exporter.clone())
+            .build();
+        let tracer = provider.tracer("test");
+        let trace_id = TraceId::from_hex("a3ce929d0e0e4736").unwrap();
+        assert_eq!(trace_id.to_string(), "0000000000000000a3ce929d0e0e4736");
+        let parent = |flags| {
+            let span_context = SpanContext::new(
+                trace_id,
+                SpanId::from_hex("00f067aa0ba902b7").unwrap(),
+                flags,
+                true,
+                TraceState::default(),
+            );
+            Context::new().with_remote_span_context(span_context)
+        };
+
+        tracer.start_with_context("sampled", &parent(TraceFlags::SAMPLED));
+        tracer.start_with_context("not sampled", &parent(TraceFlags::default()));
+        tracer.start("root");
+
+        let spans = exporter.get_finished_spans().expect("get spans failed");
+        let names: Vec<_> = spans.iter().map(|span| span.name.as_ref()).collect();
+        assert_eq!(names, ["sampled"]);
+        assert_eq!(spans[0].span_context.trace_id(), trace_id);
+    }
+
+    #[test]
+    fn test_route_rules() {
+        let sampler = RouteSampler::new(
+            vec![("/checkout".to_string(), 1.0), ("/search".to_string(), 0.0)],
+            Sampler::TraceIdRatioBased(0.01),
+        );
+        let path = |path: &'static str| [KeyValue::new("url.path", path)];
+        let sampled = |path: &[KeyValue]| {
+            // trace ids spread over the range of the id bits compared with the ratio
+            (1..=100u128)
+                .map(|id| TraceId::from(id * (u64::MAX as u128 / 100)))
+                .map(|trace_id| decision_of_trace(&sampler, trace_id, path))
+                .filter(|decision| *decision == SamplingDecision::RecordAndSample)
+                .count()
+        };
+
+        assert_eq!(sampled(&path("/checkout/pay")), 100);
+        assert_eq!(sampled(&path("/search")), 0);
+        assert!(sampled(&path("/api/orders")) < 100);
+    }
+
+    #[cfg(feature = "jaeger-remote-sampler")]
+    #[test]
+    fn test_jaeger_remote_sampler() {
+        use crate::config::JaegerRemoteSamplerConfig;
+        use std::io::{Read, Write};
+        use std::sync::{Arc, Mutex};
+        use std::time::{Duration, Instant};
+
+        // a Jaeger agent serving the current sampling rate of the service
+        let rate = Arc::new(Mutex::new(0.0));
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("bind failed");
+        let endpoint = format!("http://{}/sampling", listener.local_addr().unwrap());
+        let served = rate.clone();
+        std::thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                let mut request = [0; 1024];
+                let _ = stream.read(&mut request);
+                let body = format!(
+                    r#"{{"strategyType":"PROBABILISTIC","probabilisticSampling":{{"samplingRate":{}}}}}"#,
+                    served.lock().unwrap()
+                );
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\n\
+                     content-length: {}\r\nconnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+            }
+        });
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("build runtime failed");
+        let config = JaegerRemoteSamplerConfig {
+            endpoint,
+            service_name: None,
+            polling_interval_ms: 50,
+        };
+        let sampler = runtime.block_on(async {
+            jaeger_remote_sampler(&config, "payments", Sampler::AlwaysOn)
+                .expect("build sampler failed")
+        });
+        let wait_for = |expected: SamplingDecision| {
+            runtime.block_on(async {
+                let deadline = Instant::now() + Duration::from_secs(5);
+                while decision(&sampler, &[]) != expected {
+                    assert!(Instant::now() < deadline, "{expected:?} timed out");
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+            })
+        };
+        wait_for(SamplingDecision::Drop);
+        *rate.lock().unwrap() = 1.0;
+        wait_for(SamplingDecision::RecordAndSample);
+
+        let invalid = JaegerRemoteSamplerConfig {
+            endpoint: "localhost:5778 sampling".to_string(),
+            ..config
+        };
+        assert!(jaeger_remote_sampler(&invalid, "payments", Sampler::AlwaysOn).is_err());
+    }
+}
@@ -0,0 +1,185 @@
+//! Propagation of the span context to the background tasks spawned by a request, whose spans
+//! have no parent otherwise since a spawned future runs outside of the spans entered by the
+//! code spawning it, and attributes scoped to a request, e.g. its tenant.
+//!
+//! ```rust,ignore
+//! #[post("/orders")]
+//! async fn create_order() -> impl IntoResponse {
+//!     summer_opentelemetry::spawn_with_context(async {
+//!         // the spans of the task are children of the request span
+//!         send_confirmation_email().await
+//!     });
+//! }
+//! ```
+
+use opentelemetry::context::FutureExt as _;
+use opentelemetry::KeyValue;
+use std::future::Future;
+use std::sync::Arc;
+use tokio::task::JoinHandle;
+use tracing::Instrument;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+tokio::task_local! {
+    /// The attributes of the request handled by the task, set by [`with_request_attributes`].
+    static REQUEST_ATTRIBUTES: Arc<[KeyValue]>;
+}
+
+/// Spawns `future` on the Tokio runtime within the current span and OpenTelemetry context,
+/// see [`with_current_context`].
+pub fn spawn_with_context<F>(future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    tokio::spawn(with_current_context(future))
+}
+
+/// Wraps `future` to run it within the current `tracing` span and the OpenTelemetry context
+/// of that span, e.g. before spawning it on another executor.
+///
+/// The spans created in the future with `tracing` or with the OpenTelemetry API are children
+/// of the current span.
+///
+/// The [request attributes](with_request_attributes) of the current task apply to the future
+/// as well.
+pub fn with_current_context<F: Future>(future: F) -> impl Future<Output = F::Output> {
+    let cx = crate::propagation::current_context();
+    let attributes = request_attributes();
+    let future = future.in_current_span().with_context(cx);
+    async move {
+        match attributes {
+            Some(attributes) => REQUEST_ATTRIBUTES.scope(attributes, future).await,
+            None => future.await,
+        }
+    }
+}
+
+/// Runs `future` with request-scoped attributes, e.g. the user or tenant of the request, set
+/// once at the entry point of the request:
+///
+/// ```rust,ignore
+/// with_request_attributes([KeyValue::new("tenant.id", tenant)], async move {
+///     // the spans started and the logs emitted here carry `tenant.id`
+///     handle(order).await
+/// })
+/// .await
+/// ```
+///
+/// The attributes are added to the current span, and to the spans started and the log records
+/// emitted while `future` runs, including in the tasks spawned with [`spawn_with_context`].
+/// Nested calls add their attributes to the ones of the enclosing call.
+pub async fn with_request_attributes<F: Future>(
+    attributes: impl IntoIterator<Item = KeyValue>,
+    future: F,
+) -> F::Output {
+    let mut scoped: Vec<_> = request_attributes()
+        .map(|attributes| attributes.to_vec())
+        .unwrap_or_default();
+    scoped.extend(attributes);
+    let span = tracing::Span::current();
+    for attribute in &scoped {
+        span.set_attribute(attribute.key.clone(), attribute.value.clone());
+    }
+    REQUEST_ATTRIBUTES.scope(scoped.into(), future).await
+}
+
+/// The attributes set by [`with_request_attributes`] for the current task, if any.
+pub(crate) fn request_attributes() -> Option<Arc<[KeyValue]>> {
+    REQUEST_ATTRIBUTES.try_with(Arc::clone).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry::trace::{Tracer, TracerProvider};
+    use opentelemetry_sdk::trace::{InMemorySpanExporter, SdkTracerProvider};
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn test_spawn_with_context() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .expect("build runtime failed");
+        let _guard = runtime.enter();
+        let exporter = InMemorySpanExporter::default();
+        let provider = SdkTracerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+        let tracer = provider.tracer("test");
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(tracer.clone()));
+
+        tracing::subscriber::with_default(subscriber, || {
+            let handler = tracing::info_span!("handler");
+            let task = handler.in_scope(|| {
+                spawn_with_context(async move {
+                    tracing::info_span!("background").in_scope(|| {});
+                    tracer.in_span("otel_background", |_| {});
+                })
+            });
+            drop(handler);
+            runtime.block_on(task).expect("task failed");
+        });
+
+        let spans = exporter.get_finished_spans().unwrap();
+        let span = |name: &str| spans.iter().find(|span| span.name == name).unwrap();
+        let handler_id = span("handler").span_context.span_id();
+        assert_eq!(span("background").parent_span_id, handler_id);
+        assert_eq!(span("otel_background").parent_span_id, handler_id);
+    }
+
+    #[test]
+    fn test_request_attributes() {
+        use crate::processor::RequestAttributesProcessor;
+        use opentelemetry::logs::AnyValue;
+        use opentelemetry::Key;
+        use opentelemetry_appender_tracing::layer::OpenTelemetryTracingBridge;
+        use opentelemetry_sdk::logs::{InMemoryLogExporter, SdkLoggerProvider};
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .expect("build runtime failed");
+        let spans = InMemorySpanExporter::default();
+        let tracer_provider = SdkTracerProvider::builder()
+            .with_span_processor(RequestAttributesProcessor)
+            .with_simple_exporter(spans.clone())
+            .build();
+        let logs = InMemoryLogExporter::default();
+        let logger_provider = SdkLoggerProvider::builder()
+            .with_log_processor(RequestAttributesProcessor)
+            .with_simple_exporter(logs.clone())
+            .build();
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(tracer_provider.tracer("test")))
+            .with(OpenTelemetryTracingBridge::new(&logger_provider));
+
+        tracing::subscriber::with_default(subscriber, || {
+            let request = tracing::info_span!("request");
+            let handler = with_request_attributes([KeyValue::new("tenant.id", "acme")], async {
+                tracing::info_span!("query").in_scope(|| tracing::info!("orders listed"));
+                spawn_with_context(async { tracing::info_span!("background").in_scope(|| {}) })
+                    .await
+            });
+            let _guard = runtime.enter();
+            runtime
+                .block_on(handler.instrument(request))
+                .expect("task failed");
+            tracing::info_span!("other").in_scope(|| {});
+        });
+
+        let tenant = KeyValue::new("tenant.id", "acme");
+        let spans = spans.get_finished_spans().unwrap();
+        for span in &spans {
+            let tagged = span.attributes.contains(&tenant);
+            assert_eq!(tagged, span.name != "other", "{}", span.name);
+        }
+        let logs = logs.get_emitted_logs().unwrap();
+        let tenant = logs[0]
+            .record
+            .attributes_iter()
+            .find(|(key, _)| *key == Key::from_static_str("tenant.id"))
+            .map(|(_, value)| value.clone());
+        assert_eq!(tenant, Some(AnyValue::from("acme")));
+    }
+}
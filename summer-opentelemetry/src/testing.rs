@@ -0,0 +1,199 @@
+//! Telemetry captured in memory when `exporter = "in_memory"`, for the tests asserting on the
+//! spans, metrics and logs recorded by the application.
+//!
+//! ```rust,ignore
+//! let telemetry = app.get_component::<InMemoryTelemetry>().unwrap();
+//! let span = telemetry.spans().into_iter().find(|span| span.name == "checkout").unwrap();
+//! assert!(span.attributes.contains(&KeyValue::new("amount", 42)));
+//! ```
+//!
+//! The free functions assert on the telemetry of the last app built with the `in_memory`
+//! exporter, without getting the component:
+//!
+//! ```rust,ignore
+//! use summer_opentelemetry::testing as otel_test;
+//!
+//! let span = otel_test::assert_span_exported("checkout");
+//! let metrics = otel_test::collected_metrics();
+//! ```
+
+use opentelemetry_sdk::logs::{InMemoryLogExporter, SdkLogRecord};
+use opentelemetry_sdk::metrics::data::ResourceMetrics;
+use opentelemetry_sdk::metrics::{
+    InMemoryMetricExporter, InMemoryMetricExporterBuilder, SdkMeterProvider, Temporality,
+};
+use opentelemetry_sdk::trace::{InMemorySpanExporter, SpanData};
+use std::sync::{Mutex, PoisonError};
+
+/// The telemetry of the last app built with the `in_memory` exporter.
+static CURRENT: Mutex<Option<InMemoryTelemetry>> = Mutex::new(None);
+
+/// Sets the telemetry the free functions assert on.
+pub(crate) fn set_current(telemetry: InMemoryTelemetry) {
+    *CURRENT.lock().unwrap_or_else(PoisonError::into_inner) = Some(telemetry);
+}
+
+/// The telemetry of the last app built with the `in_memory` exporter.
+///
+/// # Panics
+///
+/// If no app was built with the `in_memory` exporter.
+#[track_caller]
+pub fn current() -> InMemoryTelemetry {
+    CURRENT
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+        .expect("no app was built with `exporter = \"in_memory\"` and the `testing` feature")
+}
+
+/// Asserts that a span named `name` ended in the last app built with the `in_memory`
+/// exporter, see [`InMemoryTelemetry::assert_span_exported`].
+#[track_caller]
+pub fn assert_span_exported(name: &str) -> SpanData {
+    current().assert_span_exported(name)
+}
+
+/// The metrics collected so far in the last app built with the `in_memory` exporter, see
+/// [`InMemoryTelemetry::metrics`].
+#[track_caller]
+pub fn collected_metrics() -> Vec<ResourceMetrics> {
+    current().metrics()
+}
+
+/// The exporters of the signals with the `in_memory` exporter, registered as a component.
+///
+/// The spans and logs are exported as soon as they end or are emitted, without batching.
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryTelemetry {
+    spans: InMemorySpanExporter,
+    metrics: InMemoryMetricExporter,
+    logs: InMemoryLogExporter,
+    pub(crate) meter_provider: Option<SdkMeterProvider>,
+}
+
+impl InMemoryTelemetry {
+    pub(crate) fn span_exporter(&self) -> InMemorySpanExporter {
+        self.spans.clone()
+    }
+
+    /// Replaces the metric exporter with one of the configured temporality.
+    pub(crate) fn metric_exporter(&mut self, temporality: Temporality) -> InMemoryMetricExporter {
+        self.metrics = InMemoryMetricExporterBuilder::new()
+            .with_temporality(temporality)
+            .build();
+        self.metrics.clone()
+    }
+
+    pub(crate) fn log_exporter(&self) -> InMemoryLogExporter {
+        self.logs.clone()
+    }
+
+    /// The ended spans.
+    pub fn spans(&self) -> Vec<SpanData> {
+        self.spans.get_finished_spans().unwrap_or_default()
+    }
+
+    /// Asserts that a span named `name` ended, returning it to assert on its attributes.
+    ///
+    /// # Panics
+    ///
+    /// If no span named `name` ended, with the names of the spans that did.
+    #[track_caller]
+    pub fn assert_span_exported(&self, name: &str) -> SpanData {
+        let spans = self.spans();
+        if let Some(index) = spans.iter().position(|span| span.name == name) {
+            return spans.into_iter().nth(index).unwrap();
+        }
+        let names: Vec<_> = spans.iter().map(|span| span.name.as_ref()).collect();
+        panic!("span `{name}` was not exported, the exported spans are {names:?}");
+    }
+
+    /// The metrics collected so far, the meter provider is flushed first.
+    pub fn metrics(&self) -> Vec<ResourceMetrics> {
+        if let Some(meter_provider) = &self.meter_provider {
+            let _ = meter_provider.force_flush();
+        }
+        self.metrics.get_finished_metrics().unwrap_or_default()
+    }
+
+    /// The emitted log records.
+    pub fn logs(&self) -> Vec<SdkLogRecord> {
+        self.logs
+            .get_emitted_logs()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|log| log.record)
+            .collect()
+    }
+
+    /// Clears the captured telemetry, e.g. between the cases of a test.
+    pub fn reset(&self) {
+        self.spans.reset();
+        self.metrics.reset();
+        self.logs.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::OpenTelemetryPlugin;
+    use opentelemetry::metrics::MeterProvider;
+    use opentelemetry::trace::{Span, Tracer, TracerProvider};
+    use opentelemetry::KeyValue;
+    use opentelemetry_sdk::metrics::data::{AggregatedMetrics, MetricData};
+    use opentelemetry_sdk::trace::SdkTracerProvider;
+    use summer::app::AppBuilder;
+    use summer::plugin::ComponentRegistry;
+
+    #[test]
+    fn test_in_memory_exporter() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("build runtime failed");
+        let _guard = runtime.enter();
+
+        let mut app = AppBuilder::default();
+        app.use_config_str("[opentelemetry]\nenable = true\nexporter = \"in_memory\"");
+        OpenTelemetryPlugin::try_build(&mut app).expect("plugin build failed");
+        let telemetry = app
+            .get_component::<InMemoryTelemetry>()
+            .expect("telemetry not registered");
+
+        let tracer_provider = app.get_component::<SdkTracerProvider>().unwrap();
+        let mut span = tracer_provider.tracer("test").start("checkout");
+        span.set_attribute(KeyValue::new("amount", 42));
+        span.end();
+        let spans = telemetry.spans();
+        assert_eq!(spans[0].name, "checkout");
+        assert_eq!(spans[0].attributes, [KeyValue::new("amount", 42)]);
+
+        let meter_provider = app.get_component::<SdkMeterProvider>().unwrap();
+        let orders = meter_provider.meter("test").u64_counter("orders").build();
+        orders.add(3, &[]);
+        let metrics = telemetry.metrics();
+        let metric = metrics
+            .iter()
+            .flat_map(|metrics| metrics.scope_metrics())
+            .flat_map(|scope| scope.metrics())
+            .find(|metric| metric.name() == "orders")
+            .expect("orders not exported");
+        let AggregatedMetrics::U64(MetricData::Sum(sum)) = metric.data() else {
+            panic!("orders is not a u64 sum");
+        };
+        assert_eq!(sum.data_points().next().map(|point| point.value()), Some(3));
+
+        assert_eq!(
+            assert_span_exported("checkout").attributes,
+            [KeyValue::new("amount", 42)]
+        );
+        assert!(!collected_metrics().is_empty());
+        let missing = std::panic::catch_unwind(|| assert_span_exported("refund"));
+        assert!(missing.is_err());
+
+        telemetry.reset();
+        assert!(telemetry.spans().is_empty());
+    }
+}
@@ -1,8 +1,14 @@
 mod grpc;
+mod historical;
 mod http;
+mod messaging;
 
 pub use crate::trace::grpc::GrpcLayer;
+pub use crate::trace::historical::HistoricalTracer;
 pub use crate::trace::http::HttpLayer;
+pub use crate::trace::messaging::{
+    extract_message_context, inject_message_context, MessagingOperation, MessagingSpan,
+};
 
 use std::env::VarError;
 
@@ -0,0 +1,106 @@
+//! Spans with explicit start and end times, to replay historical events, e.g. when backfilling
+//! traces from logs, instead of the spans of `tracing` which start and end at the current time.
+
+use opentelemetry::trace::{SpanBuilder, Tracer as _, TracerProvider as _};
+use opentelemetry::{Context, InstrumentationScope};
+use opentelemetry_sdk::trace::{SdkTracer, SdkTracerProvider, Span};
+use std::borrow::Cow;
+use std::time::SystemTime;
+
+/// Starts spans at a given time with the tracer of the plugin, so they report the configured
+/// resource and instrumentation scope, and are exported by its span processors with their
+/// timestamps as is.
+///
+/// It is registered as a component when traces are enabled and is cheap to clone:
+///
+/// ```rust,ignore
+/// use opentelemetry::trace::{Span as _, TraceContextExt};
+///
+/// let order = tracer.start_at("import order", event.received_at);
+/// let cx = opentelemetry::Context::new().with_span(order);
+/// tracer
+///     .start_with_parent_at("charge card", event.charged_at, &cx)
+///     .end_with_timestamp(event.charged_at + event.charge_duration);
+/// cx.span().end_with_timestamp(event.completed_at);
+/// ```
+///
+/// The spans are ended with [`end_with_timestamp`](opentelemetry::trace::Span::end_with_timestamp),
+/// `end` sets the current time.
+#[derive(Debug, Clone)]
+pub struct HistoricalTracer {
+    tracer: SdkTracer,
+}
+
+impl HistoricalTracer {
+    pub(crate) fn new(provider: &SdkTracerProvider, scope: InstrumentationScope) -> Self {
+        Self {
+            tracer: provider.tracer_with_scope(scope),
+        }
+    }
+
+    /// The tracer of the instrumentation scope of the application.
+    pub fn tracer(&self) -> &SdkTracer {
+        &self.tracer
+    }
+
+    /// Starts the root span of a trace at `start`, regardless of the current context.
+    pub fn start_at(&self, name: impl Into<Cow<'static, str>>, start: SystemTime) -> Span {
+        self.start_with_parent_at(name, start, &Context::new())
+    }
+
+    /// Starts a span at `start`, child of the span of `parent`, or the root of a trace when
+    /// `parent` has no span.
+    pub fn start_with_parent_at(
+        &self,
+        name: impl Into<Cow<'static, str>>,
+        start: SystemTime,
+        parent: &Context,
+    ) -> Span {
+        self.build_with_parent(SpanBuilder::from_name(name).with_start_time(start), parent)
+    }
+
+    /// Starts the span of `builder`, e.g. with its kind, attributes and links set, child of the
+    /// span of `parent` like [`start_with_parent_at`](Self::start_with_parent_at). The builder
+    /// sets the start time with [`with_start_time`](SpanBuilder::with_start_time).
+    pub fn build_with_parent(&self, builder: SpanBuilder, parent: &Context) -> Span {
+        self.tracer.build_with_context(builder, parent)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry::trace::{Span as _, TraceContextExt};
+    use opentelemetry_sdk::trace::InMemorySpanExporter;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn test_historical_spans() {
+        let exporter = InMemorySpanExporter::default();
+        let provider = SdkTracerProvider::builder()
+            .with_batch_exporter(exporter.clone())
+            .build();
+        let tracer =
+            HistoricalTracer::new(&provider, InstrumentationScope::builder("import").build());
+        let at = |secs| UNIX_EPOCH + Duration::from_secs(secs);
+
+        let cx = Context::new().with_span(tracer.start_at("import order", at(1_000)));
+        tracer
+            .start_with_parent_at("charge card", at(1_001), &cx)
+            .end_with_timestamp(at(1_003));
+        cx.span().end_with_timestamp(at(1_005));
+        provider.force_flush().expect("force flush failed");
+
+        let spans = exporter.get_finished_spans().expect("get spans failed");
+        let span = |name: &str| spans.iter().find(|span| span.name == name).unwrap();
+        let (order, charge) = (span("import order"), span("charge card"));
+        assert_eq!((order.start_time, order.end_time), (at(1_000), at(1_005)));
+        assert_eq!((charge.start_time, charge.end_time), (at(1_001), at(1_003)));
+        assert_eq!(charge.parent_span_id, order.span_context.span_id());
+        assert_eq!(
+            charge.span_context.trace_id(),
+            order.span_context.trace_id()
+        );
+        assert_eq!(order.instrumentation_scope.name(), "import");
+    }
+}
@@ -121,13 +121,21 @@ impl<S> HttpService<S> {
                     "exception.message" = Empty,
                     "http.request.method" = tracing::field::display(request.method()),
                     "http.response.status_code" = Empty,
+                    "http.route" = Empty,
                     "network.protocol.name" = "http",
-                    "network.protocol.version" = tracing::field::debug(request.version()),
+                    "network.protocol.version" = http_util::http_version(request.version()),
                     "otel.kind" = tracing::field::debug(kind),
+                    "otel.name" = Empty,
                     "otel.status_code" = Empty,
+                    "server.address" = http_util::server_address(request),
                     "url.full" = tracing::field::display(request.uri()),
                     "url.path" = request.uri().path(),
                     "url.query" = request.uri().query(),
+                    "url.scheme" = http_util::url_scheme(request),
+                    "user_agent.original" = request
+                        .headers()
+                        .get(http::header::USER_AGENT)
+                        .and_then(|v| v.to_str().ok()),
                 )
             }};
         }
@@ -161,8 +169,17 @@ impl<S> HttpService<S> {
                 });
             }
             SpanKind::Server => {
-                if let Some(http_route) = http_util::http_route(request) {
-                    span.record(HTTP_ROUTE, http_route);
+                // named `{method} {route}`, or `{method}` alone since the path isn't a
+                // low-cardinality name
+                let method = http_util::http_method(request.method());
+                match http_util::http_route(request) {
+                    Some(http_route) => {
+                        span.record(HTTP_ROUTE, http_route);
+                        span.record("otel.name", format!("{method} {http_route}"));
+                    }
+                    None => {
+                        span.record("otel.name", method);
+                    }
                 }
                 let context = opentelemetry::global::get_text_map_propagator(|extractor| {
                     extractor.extract(&HeaderExtractor(request.headers()))
@@ -260,3 +277,122 @@ where
         span.record(EXCEPTION_MESSAGE, err.to_string());
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::FutureExt;
+    use opentelemetry::trace::TracerProvider;
+    use opentelemetry_sdk::propagation::TraceContextPropagator;
+    use opentelemetry_sdk::trace::{InMemorySpanExporter, SdkTracerProvider};
+    use std::convert::Infallible;
+    use std::sync::{Arc, Mutex};
+    use tracing_subscriber::layer::SubscriberExt;
+
+    /// Records the `traceparent` header of the outbound requests, like a downstream service.
+    #[derive(Debug, Clone, Default)]
+    struct Downstream(Arc<Mutex<Vec<String>>>);
+
+    impl Service<Request<String>> for Downstream {
+        type Response = Response<String>;
+        type Error = Infallible;
+        type Future = std::future::Ready<Result<Self::Response, Self::Error>>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, req: Request<String>) -> Self::Future {
+            if let Some(traceparent) = req.headers().get("traceparent") {
+                let traceparent = traceparent.to_str().unwrap_or_default().to_string();
+                self.0.lock().unwrap().push(traceparent);
+            }
+            std::future::ready(Ok(Response::new(String::new())))
+        }
+    }
+
+    #[test]
+    fn test_client_span_propagated() {
+        let _propagator = crate::tests::lock_global_propagator();
+        opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new());
+        let exporter = InMemorySpanExporter::default();
+        let provider = SdkTracerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+        let downstream = Downstream::default();
+        let mut client = HttpLayer::client(Level::INFO).layer(downstream.clone());
+
+        tracing::subscriber::with_default(subscriber, || {
+            let _handler = tracing::info_span!("handler").entered();
+            let request = Request::get("http://inventory/items").body(String::new());
+            let response = client.call(request.unwrap()).now_or_never();
+            assert!(matches!(response, Some(Ok(_))));
+        });
+
+        let spans = exporter.get_finished_spans().unwrap();
+        let client_span = spans.iter().find(|span| span.name == "HTTP").unwrap();
+        let handler_span = spans.iter().find(|span| span.name == "handler").unwrap();
+        assert_eq!(
+            client_span.parent_span_id,
+            handler_span.span_context.span_id()
+        );
+        let span_context = &client_span.span_context;
+        let traceparent = format!(
+            "00-{}-{}-01",
+            span_context.trace_id(),
+            span_context.span_id()
+        );
+        assert_eq!(*downstream.0.lock().unwrap(), [traceparent]);
+    }
+
+    #[test]
+    fn test_server_span_kind_and_attributes() {
+        use opentelemetry::KeyValue;
+
+        let _propagator = crate::tests::lock_global_propagator();
+        opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new());
+        let exporter = InMemorySpanExporter::default();
+        let provider = SdkTracerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+        let mut server = HttpLayer::server(Level::INFO).layer(Downstream::default());
+
+        tracing::subscriber::with_default(subscriber, || {
+            let request = Request::get("/orders?page=2")
+                .header("host", "shop.example.com:8080")
+                .header("user-agent", "curl/8.5.0")
+                .header(
+                    "traceparent",
+                    "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+                )
+                .body(String::new());
+            let response = server.call(request.unwrap()).now_or_never();
+            assert!(matches!(response, Some(Ok(_))));
+        });
+
+        let spans = exporter.get_finished_spans().unwrap();
+        let span = &spans[0];
+        assert_eq!(span.span_kind, opentelemetry::trace::SpanKind::Server);
+        assert_eq!(span.name, "GET");
+        assert_eq!(
+            span.span_context.trace_id().to_string(),
+            "4bf92f3577b34da6a3ce929d0e0e4736"
+        );
+        for attribute in [
+            KeyValue::new("http.request.method", "GET"),
+            KeyValue::new("http.response.status_code", 200),
+            KeyValue::new("network.protocol.version", "1.1"),
+            KeyValue::new("server.address", "shop.example.com"),
+            KeyValue::new("url.path", "/orders"),
+            KeyValue::new("url.query", "page=2"),
+            KeyValue::new("url.scheme", "http"),
+            KeyValue::new("user_agent.original", "curl/8.5.0"),
+        ] {
+            assert!(span.attributes.contains(&attribute), "{attribute:?}");
+        }
+    }
+}
@@ -0,0 +1,272 @@
+//! Spans of the producers and consumers of a messaging system, e.g. Kafka or RabbitMQ, with the
+//! context propagated in the headers of the messages.
+//! refs: https://opentelemetry.io/docs/specs/semconv/messaging/messaging-spans/
+
+use opentelemetry::propagation::{Extractor, Injector};
+use opentelemetry::trace::TraceContextExt;
+use std::fmt;
+use tracing::{Level, Span};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+/// The type of a messaging operation, recorded as `messaging.operation.type`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MessagingOperation {
+    /// A message is created, before being sent in a batch.
+    Create,
+    /// One or more messages are sent to the broker.
+    Send,
+    /// One or more messages are requested by a consumer.
+    Receive,
+    /// One or more messages are processed by a consumer.
+    Process,
+    /// One or more messages are acknowledged or rejected.
+    Settle,
+}
+
+impl MessagingOperation {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Create => "create",
+            Self::Send => "send",
+            Self::Receive => "receive",
+            Self::Process => "process",
+            Self::Settle => "settle",
+        }
+    }
+
+    /// The span kinds of the operations, the receive and settle calls are made by the client.
+    fn span_kind(&self) -> &'static str {
+        match self {
+            Self::Create | Self::Send => "producer",
+            Self::Receive | Self::Settle => "client",
+            Self::Process => "consumer",
+        }
+    }
+}
+
+impl fmt::Display for MessagingOperation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Builder of the [`Span`]s of the operations of a messaging system, named
+/// `{operation} {destination}`, e.g. `send orders`.
+///
+/// The `messaging.message.id`, `messaging.destination.partition.id` and
+/// `messaging.consumer.group.name` fields are declared empty, to be recorded on the span.
+///
+/// ```rust,ignore
+/// // producer
+/// let span = MessagingSpan::producer("kafka").destination("orders").start();
+/// inject_message_context(&span, &mut headers);
+///
+/// // consumer, child of the producer span
+/// let span = MessagingSpan::consumer("kafka").destination("orders").start_from_message(&headers);
+/// ```
+#[derive(Clone, Debug)]
+pub struct MessagingSpan {
+    level: Level,
+    system: String,
+    operation: MessagingOperation,
+    operation_name: Option<String>,
+    destination: Option<String>,
+}
+
+impl MessagingSpan {
+    /// The spans of the `operation` of `system`, e.g. `kafka` or `rabbitmq`, at the `INFO` level.
+    pub fn new(system: impl Into<String>, operation: MessagingOperation) -> Self {
+        Self {
+            level: Level::INFO,
+            system: system.into(),
+            operation,
+            operation_name: None,
+            destination: None,
+        }
+    }
+
+    /// The spans of the messages sent by a producer.
+    pub fn producer(system: impl Into<String>) -> Self {
+        Self::new(system, MessagingOperation::Send)
+    }
+
+    /// The spans of the messages processed by a consumer.
+    pub fn consumer(system: impl Into<String>) -> Self {
+        Self::new(system, MessagingOperation::Process)
+    }
+
+    /// The level of the spans.
+    pub fn level(mut self, level: Level) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// The name of the operation specific to the system, e.g. `publish` or `ack`, recorded as
+    /// `messaging.operation.name`, the operation type by default.
+    pub fn operation_name(mut self, name: impl Into<String>) -> Self {
+        self.operation_name = Some(name.into());
+        self
+    }
+
+    /// The topic or queue of the messages, recorded as `messaging.destination.name`.
+    pub fn destination(mut self, name: impl Into<String>) -> Self {
+        self.destination = Some(name.into());
+        self
+    }
+
+    /// Starts a span, child of the current span.
+    pub fn start(&self) -> Span {
+        let operation_name = self
+            .operation_name
+            .as_deref()
+            .unwrap_or(self.operation.as_str());
+
+        macro_rules! make_span {
+            ($level:expr) => {{
+                use tracing::field::Empty;
+
+                tracing::span!(
+                    $level,
+                    "MESSAGING",
+                    "exception.message" = Empty,
+                    "messaging.batch.message_count" = Empty,
+                    "messaging.consumer.group.name" = Empty,
+                    "messaging.destination.name" = self.destination.as_deref(),
+                    "messaging.destination.partition.id" = Empty,
+                    "messaging.message.id" = Empty,
+                    "messaging.operation.name" = operation_name,
+                    "messaging.operation.type" = self.operation.as_str(),
+                    "messaging.system" = self.system.as_str(),
+                    "otel.kind" = self.operation.span_kind(),
+                    "otel.name" = Empty,
+                    "otel.status_code" = Empty,
+                )
+            }};
+        }
+
+        let span = match self.level {
+            Level::ERROR => make_span!(Level::ERROR),
+            Level::WARN => make_span!(Level::WARN),
+            Level::INFO => make_span!(Level::INFO),
+            Level::DEBUG => make_span!(Level::DEBUG),
+            Level::TRACE => make_span!(Level::TRACE),
+        };
+        match &self.destination {
+            Some(destination) => {
+                span.record("otel.name", format!("{operation_name} {destination}"))
+            }
+            None => span.record("otel.name", operation_name),
+        };
+        span
+    }
+
+    /// Starts a span, child of the context propagated in the headers of a message, or of the
+    /// current span if the message has none.
+    pub fn start_from_message(&self, extractor: &dyn Extractor) -> Span {
+        let span = self.start();
+        let cx = extract_message_context(extractor);
+        if cx.span().span_context().is_valid() {
+            let _ = span.set_parent(cx);
+        }
+        span
+    }
+
+    /// Starts a span for a batch of messages, linked to the context propagated in the headers
+    /// of each message since they may belong to different traces.
+    pub fn start_from_messages<'a>(
+        &self,
+        extractors: impl IntoIterator<Item = &'a dyn Extractor>,
+    ) -> Span {
+        let span = self.start();
+        let contexts: Vec<_> = extractors
+            .into_iter()
+            .map(extract_message_context)
+            .collect();
+        span.record("messaging.batch.message_count", contexts.len());
+        crate::propagation::add_links(&span, crate::propagation::links(contexts));
+        span
+    }
+}
+
+/// Injects the context of `span` into the headers of a message, with the global propagator set
+/// up by the plugin, so that the consumer spans continue the trace.
+pub fn inject_message_context(span: &Span, injector: &mut dyn Injector) {
+    let cx = span.context();
+    opentelemetry::global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(&cx, injector)
+    });
+}
+
+/// Extracts the context propagated in the headers of a message, with the global propagator set
+/// up by the plugin.
+pub fn extract_message_context(extractor: &dyn Extractor) -> opentelemetry::Context {
+    opentelemetry::global::get_text_map_propagator(|propagator| propagator.extract(extractor))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::OpenTelemetryConfig;
+    use opentelemetry::trace::{SpanKind, TracerProvider};
+    use opentelemetry::KeyValue;
+    use opentelemetry_sdk::trace::{InMemorySpanExporter, SdkTracerProvider};
+    use serde::Deserialize;
+    use std::collections::HashMap;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn test_producer_consumer_spans() {
+        let config = OpenTelemetryConfig::deserialize(toml::toml! {
+            enable = true
+            propagators = ["tracecontext"]
+        })
+        .expect("config deserialize failed");
+        let _propagator = crate::tests::install_propagator(&config);
+        let exporter = InMemorySpanExporter::default();
+        let provider = SdkTracerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+
+        tracing::subscriber::with_default(subscriber, || {
+            let mut headers = HashMap::new();
+            let producer = MessagingSpan::producer("kafka")
+                .destination("orders")
+                .start();
+            inject_message_context(&producer, &mut headers);
+            drop(producer);
+
+            let consumer = MessagingSpan::consumer("kafka")
+                .destination("orders")
+                .start_from_message(&headers);
+            consumer.record("messaging.message.id", "42");
+        });
+
+        let spans = exporter.get_finished_spans().unwrap();
+        let producer = spans
+            .iter()
+            .find(|span| span.name == "send orders")
+            .unwrap();
+        let consumer = spans
+            .iter()
+            .find(|span| span.name == "process orders")
+            .unwrap();
+        assert_eq!(producer.span_kind, SpanKind::Producer);
+        assert_eq!(consumer.span_kind, SpanKind::Consumer);
+        assert_eq!(
+            consumer.span_context.trace_id(),
+            producer.span_context.trace_id()
+        );
+        assert_eq!(consumer.parent_span_id, producer.span_context.span_id());
+        for attribute in [
+            KeyValue::new("messaging.system", "kafka"),
+            KeyValue::new("messaging.destination.name", "orders"),
+            KeyValue::new("messaging.operation.type", "process"),
+            KeyValue::new("messaging.operation.name", "process"),
+            KeyValue::new("messaging.message.id", "42"),
+        ] {
+            assert!(consumer.attributes.contains(&attribute), "{attribute:?}");
+        }
+    }
+}
@@ -50,4 +50,31 @@ pub fn http_response_size<B: Body>(res: &http::Response<B>) -> Option<u64> {
 pub fn http_route<B>(req: &http::Request<B>) -> Option<&str> {
     use axum::extract::MatchedPath;
     req.extensions().get::<MatchedPath>().map(|matched_path| matched_path.as_str())
-}
\ No newline at end of file
+}
+
+/// The host of the server the request was sent to, from the URI or the `Host` header, without
+/// the port.
+pub fn server_address<B>(req: &http::Request<B>) -> Option<&str> {
+    if let Some(host) = req.uri().host() {
+        return Some(host);
+    }
+    let host = req.headers().get(http::header::HOST)?.to_str().ok()?;
+    // the port follows the last colon, unless it's part of an IPv6 address
+    match host.rsplit_once(':') {
+        Some((address, port)) if !port.contains(']') => Some(address),
+        _ => Some(host),
+    }
+}
+
+/// The scheme of the request, from the URI or the `X-Forwarded-Proto` header set by the
+/// proxies, `http` otherwise.
+pub fn url_scheme<B>(req: &http::Request<B>) -> &str {
+    req.uri()
+        .scheme_str()
+        .or_else(|| {
+            req.headers()
+                .get("x-forwarded-proto")
+                .and_then(|v| v.to_str().ok())
+        })
+        .unwrap_or("http")
+}
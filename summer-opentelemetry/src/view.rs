@@ -0,0 +1,325 @@
+//! Metrics views built from the `metrics_views` config.
+//!
+//! refs: <https://opentelemetry.io/docs/specs/otel/metrics/sdk/#view>
+
+use crate::config::MetricsView;
+use opentelemetry::Key;
+use opentelemetry_sdk::metrics::{Aggregation, Instrument, InstrumentKind, Stream};
+
+/// Builds a single view function from the configured views, the `cardinality_limit` of all
+/// instruments and the `namespace` prefixing their names, `None` if none is configured.
+///
+/// The SDK creates a separate stream for every matching view, so the configured views are
+/// composed into one view function where the first view matching an instrument wins.
+pub(crate) fn build_view(
+    views: &[MetricsView],
+    cardinality_limit: Option<usize>,
+    namespace: Option<&str>,
+) -> anyhow::Result<Option<impl Fn(&Instrument) -> Option<Stream> + Send + Sync + 'static>> {
+    let namespace = namespace.filter(|namespace| !namespace.is_empty());
+    if views.is_empty() && cardinality_limit.is_none() && namespace.is_none() {
+        return Ok(None);
+    }
+    for view in views {
+        validate(view)?;
+    }
+    validate_cardinality_limit(cardinality_limit, "metrics_cardinality_limit")?;
+    if let Some(namespace) = namespace {
+        validate_namespace(namespace)?;
+    }
+    let views = views.to_vec();
+    let namespace = namespace.map(str::to_string);
+    Ok(Some(move |instrument: &Instrument| {
+        let view = views
+            .iter()
+            .find(|view| wildcard_match(&view.instrument_name, instrument.name()));
+        let mut stream = Stream::builder();
+        let view_limit = view.and_then(|view| view.cardinality_limit);
+        match (view, view_limit.or(cardinality_limit), &namespace) {
+            (None, None, None) => return None,
+            (_, Some(limit), _) => stream = stream.with_cardinality_limit(limit),
+            _ => {}
+        }
+        if let Some(namespace) = &namespace {
+            stream = stream.with_name(format!("{namespace}{}", instrument.name()));
+        }
+        if let Some(keys) = view.and_then(|view| view.attribute_keys.as_ref()) {
+            stream = stream.with_allowed_attribute_keys(keys.iter().cloned().map(Key::from));
+        }
+        if let Some(boundaries) = view.and_then(|view| view.bucket_boundaries.as_ref()) {
+            if instrument.kind() == InstrumentKind::Histogram {
+                stream = stream.with_aggregation(Aggregation::ExplicitBucketHistogram {
+                    boundaries: boundaries.clone(),
+                    record_min_max: true,
+                });
+            }
+        }
+        stream.build().ok()
+    }))
+}
+
+fn validate_cardinality_limit(limit: Option<usize>, name: &str) -> anyhow::Result<()> {
+    if limit == Some(0) {
+        anyhow::bail!("{name} must be greater than 0");
+    }
+    Ok(())
+}
+
+/// The prefixed names must still be valid instrument names, so the namespace starts with a
+/// letter and holds the characters allowed in the names.
+fn validate_namespace(namespace: &str) -> anyhow::Result<()> {
+    let valid = namespace.starts_with(|c: char| c.is_ascii_alphabetic())
+        && namespace
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-' | '/'));
+    if !valid {
+        anyhow::bail!(
+            "metrics_namespace must start with a letter and only contain letters, digits, `_`, `.`, `-` and `/`: {namespace:?}"
+        );
+    }
+    Ok(())
+}
+
+fn validate(view: &MetricsView) -> anyhow::Result<()> {
+    validate_cardinality_limit(
+        view.cardinality_limit,
+        &format!(
+            "cardinality_limit of metrics view `{}`",
+            view.instrument_name
+        ),
+    )?;
+    if let Some(boundaries) = &view.bucket_boundaries {
+        let increasing = boundaries.windows(2).all(|w| w[0] < w[1]);
+        if !increasing || boundaries.iter().any(|b| !b.is_finite()) {
+            anyhow::bail!(
+                "bucket_boundaries of metrics view `{}` must be finite and strictly increasing: {boundaries:?}",
+                view.instrument_name
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Matches `name` against `pattern`, where `*` matches any sequence of characters
+/// and `?` matches a single character.
+pub(crate) fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, n));
+            p += 1;
+        } else if let Some((star_p, star_n)) = backtrack {
+            p = star_p + 1;
+            n = star_n + 1;
+            backtrack = Some((star_p, star_n + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wildcard_match() {
+        assert!(wildcard_match(
+            "http.server.request.duration",
+            "http.server.request.duration"
+        ));
+        assert!(wildcard_match(
+            "http.server.*",
+            "http.server.request.duration"
+        ));
+        assert!(wildcard_match("*.duration", "http.client.request.duration"));
+        assert!(wildcard_match(
+            "http.?erver.*",
+            "http.server.active_requests"
+        ));
+        assert!(wildcard_match("*", "anything"));
+        assert!(!wildcard_match(
+            "http.server.*",
+            "http.client.request.duration"
+        ));
+        assert!(!wildcard_match(
+            "http.server",
+            "http.server.request.duration"
+        ));
+    }
+
+    #[test]
+    fn test_cardinality_limit() {
+        use opentelemetry::metrics::MeterProvider;
+        use opentelemetry::KeyValue;
+        use opentelemetry_sdk::metrics::data::{AggregatedMetrics, MetricData};
+        use opentelemetry_sdk::metrics::{
+            InMemoryMetricExporter, PeriodicReader, SdkMeterProvider,
+        };
+
+        let views = [MetricsView {
+            instrument_name: "orders".to_string(),
+            bucket_boundaries: None,
+            cardinality_limit: Some(5),
+            attribute_keys: None,
+        }];
+        let exporter = InMemoryMetricExporter::default();
+        let provider = SdkMeterProvider::builder()
+            .with_view(build_view(&views, Some(10), None).unwrap().unwrap())
+            .with_reader(PeriodicReader::builder(exporter.clone()).build())
+            .build();
+        let meter = provider.meter("test");
+        let orders = meter.u64_counter("orders").build();
+        let logins = meter.u64_counter("logins").build();
+        for user_id in 0..100 {
+            orders.add(1, &[KeyValue::new("user.id", user_id)]);
+            logins.add(1, &[KeyValue::new("user.id", user_id)]);
+        }
+        provider.force_flush().expect("flush failed");
+
+        let metrics = exporter.get_finished_metrics().expect("get metrics failed");
+        let points = |name: &str| {
+            let metric = metrics[0]
+                .scope_metrics()
+                .flat_map(|scope| scope.metrics())
+                .find(|metric| metric.name() == name)
+                .unwrap();
+            let AggregatedMetrics::U64(MetricData::Sum(sum)) = metric.data() else {
+                panic!("{name} is not a u64 sum");
+            };
+            sum.data_points()
+                .map(|point| point.value())
+                .collect::<Vec<_>>()
+        };
+        // the attribute sets over the limit are summed into one more overflow data point
+        let orders = points("orders");
+        assert_eq!(orders.len(), 6);
+        assert_eq!(orders.iter().sum::<u64>(), 100);
+        assert_eq!(points("logins").len(), 11);
+
+        assert!(build_view(&[], Some(0), None).is_err());
+        assert!(build_view(&[], None, None).unwrap().is_none());
+        assert!(build_view(&[], None, Some("")).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_namespace() {
+        use opentelemetry::metrics::MeterProvider;
+        use opentelemetry_sdk::metrics::{
+            InMemoryMetricExporter, PeriodicReader, SdkMeterProvider,
+        };
+
+        let views = [MetricsView {
+            instrument_name: "orders".to_string(),
+            bucket_boundaries: None,
+            cardinality_limit: Some(5),
+            attribute_keys: None,
+        }];
+        let exporter = InMemoryMetricExporter::default();
+        let provider = SdkMeterProvider::builder()
+            .with_view(build_view(&views, None, Some("myapp_")).unwrap().unwrap())
+            .with_reader(PeriodicReader::builder(exporter.clone()).build())
+            .build();
+        let meter = provider.meter("test");
+        meter.u64_counter("orders").build().add(1, &[]);
+        meter
+            .f64_histogram("http.server.request.duration")
+            .build()
+            .record(0.1, &[]);
+        provider.force_flush().expect("flush failed");
+
+        let metrics = exporter.get_finished_metrics().expect("get metrics failed");
+        let mut names: Vec<_> = metrics[0]
+            .scope_metrics()
+            .flat_map(|scope| scope.metrics())
+            .map(|metric| metric.name().to_string())
+            .collect();
+        names.sort();
+        assert_eq!(
+            names,
+            ["myapp_http.server.request.duration", "myapp_orders"]
+        );
+
+        assert!(build_view(&[], None, Some("1app_")).is_err());
+        assert!(build_view(&[], None, Some("my app")).is_err());
+    }
+
+    #[test]
+    fn test_attribute_keys() {
+        use opentelemetry::metrics::MeterProvider;
+        use opentelemetry::KeyValue;
+        use opentelemetry_sdk::metrics::data::{AggregatedMetrics, MetricData};
+        use opentelemetry_sdk::metrics::{
+            InMemoryMetricExporter, PeriodicReader, SdkMeterProvider,
+        };
+
+        let views = [MetricsView {
+            instrument_name: "http.server.request.duration".to_string(),
+            bucket_boundaries: None,
+            cardinality_limit: None,
+            attribute_keys: Some(vec![
+                "http.route".to_string(),
+                "http.response.status_code".to_string(),
+            ]),
+        }];
+        let exporter = InMemoryMetricExporter::default();
+        let provider = SdkMeterProvider::builder()
+            .with_view(build_view(&views, None, None).unwrap().unwrap())
+            .with_reader(PeriodicReader::builder(exporter.clone()).build())
+            .build();
+        let duration = provider
+            .meter("test")
+            .f64_histogram("http.server.request.duration")
+            .build();
+        for order_id in 0..10 {
+            duration.record(
+                0.1,
+                &[
+                    KeyValue::new("http.route", "/orders/{id}"),
+                    KeyValue::new("http.response.status_code", 200),
+                    KeyValue::new("url.path", format!("/orders/{order_id}")),
+                ],
+            );
+        }
+        provider.force_flush().expect("flush failed");
+
+        let metrics = exporter.get_finished_metrics().expect("get metrics failed");
+        let metric = metrics[0]
+            .scope_metrics()
+            .flat_map(|scope| scope.metrics())
+            .next()
+            .unwrap();
+        let AggregatedMetrics::F64(MetricData::Histogram(histogram)) = metric.data() else {
+            panic!("the duration is not a f64 histogram");
+        };
+        let points: Vec<_> = histogram.data_points().collect();
+        assert_eq!(points.len(), 1);
+        assert_eq!(points[0].count(), 10);
+        let mut keys: Vec<_> = points[0]
+            .attributes()
+            .map(|kv| kv.key.as_str().to_string())
+            .collect();
+        keys.sort();
+        assert_eq!(keys, ["http.response.status_code", "http.route"]);
+    }
+
+    #[test]
+    fn test_invalid_bucket_boundaries() {
+        let view = |boundaries: Vec<f64>| MetricsView {
+            instrument_name: "*".to_string(),
+            bucket_boundaries: Some(boundaries),
+            cardinality_limit: None,
+            attribute_keys: None,
+        };
+        assert!(validate(&view(vec![0.001, 0.01, 0.1, 1.0])).is_ok());
+        assert!(validate(&view(vec![0.1, 0.01])).is_err());
+        assert!(validate(&view(vec![0.1, f64::NAN])).is_err());
+    }
+}